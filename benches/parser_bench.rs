@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
-use fastq_parser::{parallel::ParallelParser, FastqReader, Parser};
+use fastq_parser::{parallel::ParallelParser, parser::IndexedParser, FastqReader, Parser};
 use std::io::Write;
 use tempfile::NamedTempFile;
 
@@ -16,9 +16,7 @@ fn generate_fastq_data(num_records: usize, seq_len: usize) -> Vec<u8> {
 
         writeln!(data, "+").unwrap();
 
-        for _ in 0..seq_len {
-            data.push(b'I');
-        }
+        data.extend(std::iter::repeat_n(b'I', seq_len));
         data.push(b'\n');
     }
 
@@ -38,6 +36,14 @@ fn bench_basic_parser(c: &mut Criterion) {
         });
     });
 
+    group.bench_function("indexed_parse_10k_records", |b| {
+        b.iter(|| {
+            let parser = IndexedParser::new(&data).unwrap();
+            let count = parser.count();
+            black_box(count);
+        });
+    });
+
     group.finish();
 }
 
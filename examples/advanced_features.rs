@@ -248,7 +248,7 @@ fn metrics_example() -> Result<()> {
         println!("  Duplicate rate: {:.2}%", summary.duplicate_rate * 100.0);
 
         let pos_stats = metrics.position_quality_stats();
-        if pos_stats.len() > 0 {
+        if !pos_stats.is_empty() {
             println!("\nPer-position quality (first 5 positions):");
             for stat in pos_stats.iter().take(5) {
                 println!(
@@ -1,12 +1,13 @@
 use crate::{
     error::Result,
     record::{OwnedRecord, Record},
-    writer::FastqWriter,
+    split::PartitionedWriter,
 };
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
 use std::path::Path;
 
+const UNDETERMINED: &[u8] = b"undetermined";
+
 type ExtractedBarcode = Option<(Vec<u8>, Option<Vec<u8>>)>;
 
 #[derive(Debug, Clone)]
@@ -17,6 +18,8 @@ pub struct BarcodeConfig {
     pub umi_length: Option<usize>,
     pub max_mismatches: usize,
     pub in_header: bool,
+    pub barcode_read: usize,
+    pub umi_read: Option<usize>,
 }
 
 impl Default for BarcodeConfig {
@@ -28,6 +31,8 @@ impl Default for BarcodeConfig {
             umi_length: None,
             max_mismatches: 1,
             in_header: false,
+            barcode_read: 0,
+            umi_read: None,
         }
     }
 }
@@ -56,6 +61,20 @@ impl BarcodeConfig {
         self.in_header = in_header;
         self
     }
+
+    /// Position of the read holding the barcode within a synchronized
+    /// record tuple, e.g. 2 for I1 when reading `[R1, R2, I1, I2]`.
+    pub fn barcode_read(mut self, index: usize) -> Self {
+        self.barcode_read = index;
+        self
+    }
+
+    /// Position of the read holding the UMI within a synchronized record
+    /// tuple. Defaults to the barcode read.
+    pub fn umi_read(mut self, index: usize) -> Self {
+        self.umi_read = Some(index);
+        self
+    }
 }
 
 pub struct BarcodeExtractor {
@@ -68,11 +87,30 @@ impl BarcodeExtractor {
     }
 
     pub fn extract(&self, record: &Record) -> Option<(Vec<u8>, Option<Vec<u8>>)> {
-        let source = if self.config.in_header {
-            record.id()
-        } else {
-            record.seq()
+        self.extract_split(record, record)
+    }
+
+    /// Extracts from one step of a `SynchronizedReader`, taking the barcode
+    /// from `barcode_read` and the UMI from `umi_read` (or the barcode read).
+    pub fn extract_synchronized(&self, records: &[OwnedRecord]) -> ExtractedBarcode {
+        let barcode_record = records.get(self.config.barcode_read)?.as_record();
+        let umi_record = match self.config.umi_read {
+            Some(index) => records.get(index)?.as_record(),
+            None => barcode_record.clone(),
         };
+        self.extract_split(&barcode_record, &umi_record)
+    }
+
+    fn source<'r>(&self, record: &Record<'r>) -> &'r [u8] {
+        if self.config.in_header {
+            record.id
+        } else {
+            record.seq
+        }
+    }
+
+    fn extract_split(&self, barcode_record: &Record, umi_record: &Record) -> ExtractedBarcode {
+        let source = self.source(barcode_record);
 
         if source.len() < self.config.barcode_start + self.config.barcode_length {
             return None;
@@ -85,6 +123,7 @@ impl BarcodeExtractor {
         let umi = if let (Some(umi_start), Some(umi_length)) =
             (self.config.umi_start, self.config.umi_length)
         {
+            let source = self.source(umi_record);
             if source.len() >= umi_start + umi_length {
                 Some(source[umi_start..umi_start + umi_length].to_vec())
             } else {
@@ -191,9 +230,10 @@ impl Demultiplexer {
         let output_dir = output_dir.as_ref();
         fs::create_dir_all(output_dir)?;
 
-        let mut writers: HashMap<String, FastqWriter<File>> = HashMap::new();
-        let mut undetermined_writer =
-            FastqWriter::to_file(output_dir.join(format!("{}_undetermined.fastq", prefix)))?;
+        let mut file_prefix = output_dir.join(prefix).into_os_string();
+        file_prefix.push("_");
+        let mut writer = PartitionedWriter::with_affixes(file_prefix, ".fastq")?;
+        writer.open_partition(UNDETERMINED)?;
 
         let mut stats = DemultiplexStats::new();
         let extractor = BarcodeExtractor::new(self.config.clone());
@@ -210,13 +250,6 @@ impl Demultiplexer {
                     stats.assigned_reads += 1;
                     *stats.sample_counts.entry(sample.clone()).or_insert(0) += 1;
 
-                    if !writers.contains_key(&sample) {
-                        let output_path = output_dir.join(format!("{}_{}.fastq", prefix, sample));
-                        writers.insert(sample.clone(), FastqWriter::to_file(output_path)?);
-                    }
-
-                    let writer = writers.get_mut(&sample).unwrap();
-
                     let mut modified_record = OwnedRecord::from_record(&trimmed_record);
                     if let Some(umi) = umi {
                         let umi_str = String::from_utf8_lossy(&umi);
@@ -230,21 +263,18 @@ impl Demultiplexer {
                         modified_record.id = new_id.into_bytes();
                     }
 
-                    writer.write_owned_record(&modified_record)?;
+                    writer.write_keyed(sample.as_bytes(), &modified_record.as_record())?;
                 } else {
                     stats.undetermined_reads += 1;
-                    undetermined_writer.write_record(&trimmed_record)?;
+                    writer.write_keyed(UNDETERMINED, &trimmed_record)?;
                 }
             } else {
                 stats.no_barcode_reads += 1;
-                undetermined_writer.write_owned_record(&record)?;
+                writer.write_keyed(UNDETERMINED, &record_ref)?;
             }
         }
 
-        for writer in writers.values_mut() {
-            writer.flush()?;
-        }
-        undetermined_writer.flush()?;
+        writer.finish()?;
 
         Ok(stats)
     }
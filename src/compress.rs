@@ -0,0 +1,495 @@
+use crate::error::Result;
+use byteorder::{LittleEndian, WriteBytesExt};
+use crossbeam_channel::{bounded, Receiver, Sender};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::{Compression, Crc};
+use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+
+/// Uncompressed bytes per gzip member in [`GzipFormat::Gzip`] mode.
+const GZIP_BLOCK_SIZE: usize = 256 * 1024;
+/// Largest BGZF payload that always fits a block of at most 64 KiB.
+const BGZF_BLOCK_SIZE: usize = 0xff00;
+/// The empty block that terminates a BGZF file.
+const BGZF_EOF: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// How compressed blocks are framed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GzipFormat {
+    /// One standard gzip member per block, as written by pigz `--independent`.
+    #[default]
+    Gzip,
+    /// BGZF, as used by samtools and htslib. Still readable as plain gzip.
+    Bgzf,
+}
+
+/// Settings for [`ParallelGzEncoder`].
+#[derive(Debug, Clone, Copy)]
+pub struct ParallelGzip {
+    level: Compression,
+    threads: usize,
+    format: GzipFormat,
+    block_size: usize,
+}
+
+impl Default for ParallelGzip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParallelGzip {
+    pub fn new() -> Self {
+        ParallelGzip {
+            level: Compression::default(),
+            threads: rayon::current_num_threads(),
+            format: GzipFormat::Gzip,
+            block_size: GZIP_BLOCK_SIZE,
+        }
+    }
+
+    pub fn level(mut self, level: Compression) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn format(mut self, format: GzipFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Uncompressed bytes per block. BGZF caps this at 65280.
+    pub fn block_size(mut self, size: usize) -> Self {
+        self.block_size = size.max(1);
+        self
+    }
+
+    fn effective_block_size(&self) -> usize {
+        match self.format {
+            GzipFormat::Gzip => self.block_size,
+            GzipFormat::Bgzf => self.block_size.min(BGZF_BLOCK_SIZE),
+        }
+    }
+}
+
+/// Settings for zstd output.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy)]
+pub struct ZstdOptions {
+    level: i32,
+    window_log: Option<u32>,
+}
+
+#[cfg(feature = "zstd")]
+impl Default for ZstdOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdOptions {
+    pub fn new() -> Self {
+        ZstdOptions {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+            window_log: None,
+        }
+    }
+
+    /// Compression level, 1 to 22 (negative levels trade ratio for speed).
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Enables long-distance matching with a `2^window_log` byte window,
+    /// like `zstd --long=27`. Windows above 27 must also be passed to the
+    /// decompressor (`zstd -d --long=N`).
+    pub fn long_window(mut self, window_log: u32) -> Self {
+        self.window_log = Some(window_log);
+        self
+    }
+}
+
+/// Compression applied by [`FastqWriter`](crate::writer::FastqWriter) and
+/// [`FastaWriter`](crate::writer::FastaWriter).
+///
+/// Zstd, bzip2 and xz are only available with the `zstd`, `bzip2` and `xz`
+/// cargo features.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum OutputCompression {
+    #[default]
+    None,
+    /// Single-threaded gzip.
+    Gzip(Compression),
+    /// Gzip or BGZF compressed in blocks on a thread pool.
+    ParallelGzip(ParallelGzip),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdOptions),
+    /// Bzip2 with a block size level from 1 to 9.
+    #[cfg(feature = "bzip2")]
+    Bzip2(u32),
+    /// Xz with a preset level from 0 to 9.
+    #[cfg(feature = "xz")]
+    Xz(u32),
+}
+
+impl OutputCompression {
+    /// Picks compression from the file extension:
+    ///
+    /// | Extension | Compression |
+    /// |-----------|-------------|
+    /// | `.gz` | gzip |
+    /// | `.bgz`, `.bgzf` | multi-threaded BGZF |
+    /// | `.zst`, `.zstd` | zstd |
+    /// | `.bz2` | bzip2, level 9 |
+    /// | `.xz` | xz, level 6 |
+    ///
+    /// Other extensions are written uncompressed. A codec extension whose
+    /// cargo feature is disabled is an error rather than a silently
+    /// uncompressed file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let extension = path.as_ref().extension().and_then(|s| s.to_str());
+        let compression = match extension {
+            Some("gz") => OutputCompression::Gzip(Compression::default()),
+            Some("bgz") | Some("bgzf") => {
+                OutputCompression::ParallelGzip(ParallelGzip::new().format(GzipFormat::Bgzf))
+            }
+            #[cfg(feature = "zstd")]
+            Some("zst") | Some("zstd") => OutputCompression::Zstd(ZstdOptions::new()),
+            #[cfg(feature = "bzip2")]
+            Some("bz2") => OutputCompression::Bzip2(9),
+            #[cfg(feature = "xz")]
+            Some("xz") => OutputCompression::Xz(6),
+            #[cfg(not(feature = "zstd"))]
+            Some(ext @ ("zst" | "zstd")) => {
+                return Err(crate::error::FastqError::UnsupportedCompression {
+                    extension: ext.to_string(),
+                    feature: "zstd",
+                })
+            }
+            #[cfg(not(feature = "bzip2"))]
+            Some(ext @ "bz2") => {
+                return Err(crate::error::FastqError::UnsupportedCompression {
+                    extension: ext.to_string(),
+                    feature: "bzip2",
+                })
+            }
+            #[cfg(not(feature = "xz"))]
+            Some(ext @ "xz") => {
+                return Err(crate::error::FastqError::UnsupportedCompression {
+                    extension: ext.to_string(),
+                    feature: "xz",
+                })
+            }
+            _ => OutputCompression::None,
+        };
+        Ok(compression)
+    }
+}
+
+/// The compressing layer under the FASTQ and FASTA writers.
+pub(crate) enum Encoder<W: Write> {
+    Plain(BufWriter<W>),
+    Gzip(GzEncoder<BufWriter<W>>),
+    ParallelGzip(ParallelGzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdEncoder<BufWriter<W>>),
+    #[cfg(feature = "bzip2")]
+    Bzip2(bzip2::write::BzEncoder<BufWriter<W>>),
+    #[cfg(feature = "xz")]
+    Xz(xz2::write::XzEncoder<BufWriter<W>>),
+}
+
+impl<W: Write> Encoder<W> {
+    pub(crate) fn new(writer: W, compression: OutputCompression) -> Result<Self> {
+        Ok(match compression {
+            OutputCompression::None => Encoder::Plain(BufWriter::new(writer)),
+            OutputCompression::Gzip(level) => {
+                Encoder::Gzip(GzEncoder::new(BufWriter::new(writer), level))
+            }
+            OutputCompression::ParallelGzip(options) => {
+                Encoder::ParallelGzip(ParallelGzEncoder::new(writer, options))
+            }
+            #[cfg(feature = "zstd")]
+            OutputCompression::Zstd(options) => {
+                Encoder::Zstd(ZstdEncoder::new(BufWriter::new(writer), options)?)
+            }
+            #[cfg(feature = "bzip2")]
+            OutputCompression::Bzip2(level) => Encoder::Bzip2(bzip2::write::BzEncoder::new(
+                BufWriter::new(writer),
+                bzip2::Compression::new(level.clamp(1, 9)),
+            )),
+            #[cfg(feature = "xz")]
+            OutputCompression::Xz(level) => Encoder::Xz(xz2::write::XzEncoder::new(
+                BufWriter::new(writer),
+                level.min(9),
+            )),
+        })
+    }
+
+    pub(crate) fn as_write(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Plain(w) => w,
+            Encoder::Gzip(w) => w,
+            Encoder::ParallelGzip(w) => w,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w,
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(w) => w,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(w) => w,
+        }
+    }
+
+    /// Writes any codec trailer and returns the underlying writer.
+    pub(crate) fn finish(self) -> Result<W> {
+        let buffered = match self {
+            Encoder::Plain(w) => w,
+            Encoder::Gzip(w) => w.finish()?,
+            Encoder::ParallelGzip(w) => return Ok(w.finish()?),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.finish()?,
+            #[cfg(feature = "bzip2")]
+            Encoder::Bzip2(w) => w.finish()?,
+            #[cfg(feature = "xz")]
+            Encoder::Xz(w) => w.finish()?,
+        };
+        Ok(buffered.into_inner().map_err(|e| e.into_error())?)
+    }
+}
+
+/// Zstd encoder that, like the flate2 encoders, ends the frame when
+/// dropped without [`finish`](ZstdEncoder::finish).
+#[cfg(feature = "zstd")]
+pub(crate) struct ZstdEncoder<W: Write>(Option<zstd::stream::write::Encoder<'static, W>>);
+
+#[cfg(feature = "zstd")]
+impl<W: Write> ZstdEncoder<W> {
+    fn new(writer: W, options: ZstdOptions) -> io::Result<Self> {
+        let mut encoder = zstd::stream::write::Encoder::new(writer, options.level)?;
+        if let Some(window_log) = options.window_log {
+            encoder.long_distance_matching(true)?;
+            encoder.window_log(window_log)?;
+        }
+        Ok(ZstdEncoder(Some(encoder)))
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.0.take().unwrap().finish()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> Write for ZstdEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.as_mut().unwrap().flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> Drop for ZstdEncoder<W> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.0.take() {
+            let _ = encoder.finish();
+        }
+    }
+}
+
+type Job = (u64, Vec<u8>);
+type Done = (u64, io::Result<Vec<u8>>);
+
+/// Gzip encoder that compresses independent blocks on worker threads and
+/// writes them to the inner writer in order.
+///
+/// The output is a valid multi-member gzip stream (or BGZF file), which
+/// `MultiGzDecoder`, `gzip -d` and htslib all read back. The inner writer
+/// stays on the calling thread, so it need not be `Send`.
+pub struct ParallelGzEncoder<W: Write> {
+    writer: Option<W>,
+    buffer: Vec<u8>,
+    block_size: usize,
+    format: GzipFormat,
+    max_in_flight: u64,
+    jobs: Option<Sender<Job>>,
+    results: Receiver<Done>,
+    workers: Vec<JoinHandle<()>>,
+    pending: BTreeMap<u64, Vec<u8>>,
+    next_block: u64,
+    next_write: u64,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    pub fn new(writer: W, options: ParallelGzip) -> Self {
+        let in_flight = 2 * options.threads;
+        let (job_tx, job_rx) = bounded::<Job>(in_flight);
+        let (done_tx, done_rx) = bounded::<Done>(in_flight);
+
+        let workers = (0..options.threads)
+            .map(|_| {
+                let job_rx = job_rx.clone();
+                let done_tx = done_tx.clone();
+                thread::spawn(move || {
+                    while let Ok((seq, data)) = job_rx.recv() {
+                        let block = compress_block(&data, options.level, options.format);
+                        if done_tx.send((seq, block)).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let block_size = options.effective_block_size();
+        ParallelGzEncoder {
+            writer: Some(writer),
+            buffer: Vec::with_capacity(block_size),
+            block_size,
+            format: options.format,
+            max_in_flight: in_flight as u64,
+            jobs: Some(job_tx),
+            results: done_rx,
+            workers,
+            pending: BTreeMap::new(),
+            next_block: 0,
+            next_write: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().unwrap()
+    }
+
+    /// Compresses any buffered data, writes the BGZF end-of-file block if
+    /// needed, stops the workers and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.writer.take().unwrap())
+    }
+
+    fn try_finish(&mut self) -> io::Result<()> {
+        if self.jobs.is_none() {
+            return Ok(());
+        }
+        self.submit()?;
+        self.drain(0)?;
+        if self.format == GzipFormat::Bgzf {
+            self.writer.as_mut().unwrap().write_all(&BGZF_EOF)?;
+        }
+        self.writer.as_mut().unwrap().flush()?;
+        self.shutdown();
+        Ok(())
+    }
+
+    fn shutdown(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+
+    fn submit(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.drain(self.max_in_flight - 1)?;
+
+        let data = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.block_size));
+        let jobs = self.jobs.as_ref().expect("encoder used after finish");
+        jobs.send((self.next_block, data))
+            .map_err(|_| io::Error::other("compression worker exited"))?;
+        self.next_block += 1;
+        Ok(())
+    }
+
+    /// Writes finished blocks in order until at most `limit` are in flight.
+    fn drain(&mut self, limit: u64) -> io::Result<()> {
+        while self.next_block - self.next_write > limit {
+            while let Some(block) = self.pending.remove(&self.next_write) {
+                self.writer.as_mut().unwrap().write_all(&block)?;
+                self.next_write += 1;
+            }
+            if self.next_block - self.next_write <= limit {
+                break;
+            }
+
+            let (seq, block) = self
+                .results
+                .recv()
+                .map_err(|_| io::Error::other("compression worker exited"))?;
+            self.pending.insert(seq, block?);
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.block_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        if self.buffer.len() == self.block_size {
+            self.submit()?;
+        }
+        Ok(n)
+    }
+
+    /// Compresses the partial block and waits for every block to be
+    /// written. Frequent flushes produce small blocks and hurt the ratio.
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit()?;
+        self.drain(0)?;
+        self.writer.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for ParallelGzEncoder<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.try_finish();
+        }
+        self.shutdown();
+    }
+}
+
+fn compress_block(data: &[u8], level: Compression, format: GzipFormat) -> io::Result<Vec<u8>> {
+    match format {
+        GzipFormat::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::with_capacity(data.len() / 3), level);
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        GzipFormat::Bgzf => {
+            let mut encoder = DeflateEncoder::new(Vec::with_capacity(data.len() / 3), level);
+            encoder.write_all(data)?;
+            let deflated = encoder.finish()?;
+            let mut crc = Crc::new();
+            crc.update(data);
+
+            let total = 18 + deflated.len() + 8;
+            let mut block = Vec::with_capacity(total);
+            block.extend_from_slice(&[
+                0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C',
+                0x02, 0x00,
+            ]);
+            block.write_u16::<LittleEndian>((total - 1) as u16)?;
+            block.extend_from_slice(&deflated);
+            block.write_u32::<LittleEndian>(crc.sum())?;
+            block.write_u32::<LittleEndian>(data.len() as u32)?;
+            Ok(block)
+        }
+    }
+}
@@ -33,11 +33,49 @@ pub enum FastqError {
     #[error("Paired-end read ID mismatch: R1={r1_id}, R2={r2_id}")]
     PairedEndMismatch { r1_id: String, r2_id: String },
 
+    #[error("Paired-end files appear to be swapped: first R1 read is read 2")]
+    SwappedPairedFiles,
+
     #[error("Paired-end files have different number of reads")]
     PairedEndLengthMismatch,
 
-    #[error("Interleaved file has odd number of reads")]
-    InterleavedOddCount,
+    #[error("Synchronized read ID mismatch in input {file} at record {record}: expected {expected}, found {found}")]
+    SynchronizedIdMismatch {
+        file: usize,
+        record: usize,
+        expected: String,
+        found: String,
+    },
+
+    #[error("Synchronized input {file} ended after {records} records while other inputs continue")]
+    SynchronizedLengthMismatch { file: usize, records: usize },
+
+    #[error("Invalid index file: {msg}")]
+    InvalidIndex { msg: String },
+
+    #[error("Unsupported index format version {found} (this build reads version {supported})")]
+    UnsupportedIndexVersion { found: u32, supported: u32 },
+
+    #[error("Index for {path} is stale: {reason}")]
+    StaleIndex { path: String, reason: String },
+
+    #[error("Invalid region {region}: {msg}")]
+    InvalidRegion { region: String, msg: String },
+
+    #[error("Region {region} names a read that is not in the index")]
+    RegionNotFound { region: String },
+
+    #[error("Writing .{extension} output requires the `{feature}` cargo feature")]
+    UnsupportedCompression {
+        extension: String,
+        feature: &'static str,
+    },
+
+    #[error("Output template {template} has no {{}} placeholder")]
+    InvalidOutputTemplate { template: String },
+
+    #[error("Interleaved file has odd number of reads: record {record} has no mate")]
+    InterleavedOddCount { record: usize },
 }
 
 pub type Result<T> = std::result::Result<T, FastqError>;
@@ -1,4 +1,4 @@
-use crate::{record::QualityEncoding, record::Record};
+use crate::{record::OwnedRecord, record::QualityEncoding, record::Record};
 use regex::Regex;
 use std::collections::HashSet;
 
@@ -59,6 +59,8 @@ impl QualityFilter {
         true
     }
 
+    /// Records that need no trimming come back unchanged, so they keep
+    /// their raw bytes for [`FastqWriter::write_raw`](crate::writer::FastqWriter::write_raw).
     pub fn trim<'a>(&self, record: &Record<'a>) -> Option<Record<'a>> {
         if let Some(trim_qual) = self.trim_quality {
             let (start, end) = self.sliding_window_trim(record, trim_qual);
@@ -73,6 +75,9 @@ impl QualityFilter {
             if trimmed_seq.len() < self.min_length {
                 return None;
             }
+            if trimmed_seq.len() == record.seq.len() {
+                return Some(record.clone());
+            }
 
             Some(Record::new(
                 record.id,
@@ -81,7 +86,7 @@ impl QualityFilter {
                 trimmed_qual,
             ))
         } else {
-            Some(Record::new(record.id, record.desc, record.seq, record.qual))
+            Some(record.clone())
         }
     }
 
@@ -192,7 +197,7 @@ impl AdapterTrimmer {
                 &record.qual[..best_pos],
             )
         } else {
-            Record::new(record.id, record.desc, record.seq, record.qual)
+            record.clone()
         }
     }
 
@@ -222,6 +227,123 @@ impl AdapterTrimmer {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimMethod {
+    /// Cut at the insert boundary inferred from the R1/R2 overlap.
+    Overlap,
+    /// Cut where a known adapter sequence was found.
+    Sequence,
+    Untrimmed,
+}
+
+pub struct PairedTrim<'a> {
+    pub r1: Record<'a>,
+    pub r2: Record<'a>,
+    pub insert_size: Option<usize>,
+    pub method: TrimMethod,
+}
+
+/// fastp-style paired-end adapter trimming. When R1 and the reverse
+/// complement of R2 overlap with R2 starting before R1, the insert is
+/// shorter than the reads and both mates are cut at the insert boundary,
+/// with no need to know the adapter sequence. When no overlap is found, or
+/// several placements fit equally well (as in low-complexity reads), each
+/// mate falls back to sequence-based `AdapterTrimmer` detection.
+pub struct PairedAdapterTrimmer {
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+    fallback: Option<AdapterTrimmer>,
+}
+
+impl Default for PairedAdapterTrimmer {
+    fn default() -> Self {
+        PairedAdapterTrimmer {
+            min_overlap: 30,
+            max_mismatch_rate: 0.1,
+            fallback: Some(AdapterTrimmer::default()),
+        }
+    }
+}
+
+impl PairedAdapterTrimmer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_overlap(mut self, overlap: usize) -> Self {
+        self.min_overlap = overlap;
+        self
+    }
+
+    pub fn max_mismatch_rate(mut self, rate: f64) -> Self {
+        self.max_mismatch_rate = rate;
+        self
+    }
+
+    pub fn fallback(mut self, trimmer: Option<AdapterTrimmer>) -> Self {
+        self.fallback = trimmer;
+        self
+    }
+
+    pub fn trim<'a>(&self, r1: &Record<'a>, r2: &Record<'a>) -> PairedTrim<'a> {
+        let r2_rc = crate::simd::reverse_complement(r2.seq);
+        let (overlap, candidates) = crate::merge::find_overlaps(
+            r1.seq,
+            &r2_rc,
+            self.min_overlap,
+            self.max_mismatch_rate,
+            true,
+        );
+
+        // A second placement within the mismatch budget means the overlap
+        // cannot be trusted to locate the insert boundary.
+        if let Some(overlap) = overlap.filter(|_| candidates == 1) {
+            if overlap.shift >= 0 {
+                return PairedTrim {
+                    r1: Record::new(r1.id, r1.desc, r1.seq, r1.qual),
+                    r2: Record::new(r2.id, r2.desc, r2.seq, r2.qual),
+                    insert_size: Some(overlap.insert_size(r1.len(), r2.len())),
+                    method: TrimMethod::Untrimmed,
+                };
+            }
+
+            let insert = r2.len() - (-overlap.shift) as usize;
+            let r1_end = insert.min(r1.len());
+            let r2_end = insert.min(r2.len());
+            return PairedTrim {
+                r1: Record::new(r1.id, r1.desc, &r1.seq[..r1_end], &r1.qual[..r1_end]),
+                r2: Record::new(r2.id, r2.desc, &r2.seq[..r2_end], &r2.qual[..r2_end]),
+                insert_size: Some(insert),
+                method: TrimMethod::Overlap,
+            };
+        }
+
+        match &self.fallback {
+            Some(trimmer) => {
+                let t1 = trimmer.trim(r1);
+                let t2 = trimmer.trim(r2);
+                let method = if t1.len() < r1.len() || t2.len() < r2.len() {
+                    TrimMethod::Sequence
+                } else {
+                    TrimMethod::Untrimmed
+                };
+                PairedTrim {
+                    r1: t1,
+                    r2: t2,
+                    insert_size: None,
+                    method,
+                }
+            }
+            None => PairedTrim {
+                r1: Record::new(r1.id, r1.desc, r1.seq, r1.qual),
+                r2: Record::new(r2.id, r2.desc, r2.seq, r2.qual),
+                insert_size: None,
+                method: TrimMethod::Untrimmed,
+            },
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct AdvancedFilter {
     min_length: Option<usize>,
@@ -286,11 +408,7 @@ impl AdvancedFilter {
             }
         }
 
-        let n_count = record
-            .seq()
-            .iter()
-            .filter(|&&b| b == b'N' || b == b'n')
-            .count();
+        let n_count = crate::simd::n_count(record.seq());
 
         if let Some(max_n) = self.max_n_count {
             if n_count > max_n {
@@ -331,6 +449,185 @@ impl AdvancedFilter {
     }
 }
 
+/// How a pair is kept when its mates pass or fail filtering independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PairPolicy {
+    /// Keep the pair only when both mates pass.
+    #[default]
+    Both,
+    /// Keep the pair when at least one mate passes.
+    Either,
+    /// Keep the pair when both pass; when only one passes, write it to the
+    /// singleton output.
+    Rescue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairDecision<'a> {
+    Pair(Record<'a>, Record<'a>),
+    SingleR1(Record<'a>),
+    SingleR2(Record<'a>),
+    Discard,
+}
+
+/// Applies single-read filters and trimming to each mate independently and
+/// decides the fate of the pair from `PairPolicy`, so R1 and R2 outputs stay
+/// synchronized.
+#[derive(Default)]
+pub struct PairedFilter {
+    policy: PairPolicy,
+    quality_filter: Option<QualityFilter>,
+    advanced_filter: Option<AdvancedFilter>,
+    adapter_trimmer: Option<PairedAdapterTrimmer>,
+}
+
+impl PairedFilter {
+    pub fn new(policy: PairPolicy) -> Self {
+        PairedFilter {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn quality_filter(mut self, filter: QualityFilter) -> Self {
+        self.quality_filter = Some(filter);
+        self
+    }
+
+    pub fn advanced_filter(mut self, filter: AdvancedFilter) -> Self {
+        self.advanced_filter = Some(filter);
+        self
+    }
+
+    pub fn adapter_trimmer(mut self, trimmer: PairedAdapterTrimmer) -> Self {
+        self.adapter_trimmer = Some(trimmer);
+        self
+    }
+
+    pub fn policy(&self) -> PairPolicy {
+        self.policy
+    }
+
+    fn process_mate<'a>(&self, record: Record<'a>) -> (Record<'a>, bool) {
+        let mut passes = self
+            .advanced_filter
+            .as_ref()
+            .is_none_or(|filter| filter.filter(&record));
+
+        let record = match &self.quality_filter {
+            Some(filter) => {
+                let mut scored = Record::new(record.id, record.desc, record.seq, record.qual);
+                passes &= filter.filter(&mut scored);
+                match filter.trim(&record) {
+                    Some(trimmed) => trimmed,
+                    None => {
+                        passes = false;
+                        record
+                    }
+                }
+            }
+            None => record,
+        };
+
+        (record, passes)
+    }
+
+    pub fn apply<'a>(&self, r1: &Record<'a>, r2: &Record<'a>) -> PairDecision<'a> {
+        let (r1, r2) = match &self.adapter_trimmer {
+            Some(trimmer) => {
+                let trimmed = trimmer.trim(r1, r2);
+                (trimmed.r1, trimmed.r2)
+            }
+            None => (
+                Record::new(r1.id, r1.desc, r1.seq, r1.qual),
+                Record::new(r2.id, r2.desc, r2.seq, r2.qual),
+            ),
+        };
+
+        let (r1, r1_pass) = self.process_mate(r1);
+        let (r2, r2_pass) = self.process_mate(r2);
+
+        match (self.policy, r1_pass, r2_pass) {
+            (_, true, true) => PairDecision::Pair(r1, r2),
+            (PairPolicy::Either, true, false) | (PairPolicy::Either, false, true) => {
+                PairDecision::Pair(r1, r2)
+            }
+            (PairPolicy::Rescue, true, false) => PairDecision::SingleR1(r1),
+            (PairPolicy::Rescue, false, true) => PairDecision::SingleR2(r2),
+            _ => PairDecision::Discard,
+        }
+    }
+
+    pub fn filter_pairs<I, W>(
+        &self,
+        pairs: I,
+        out_r1: &mut crate::writer::FastqWriter<W>,
+        out_r2: &mut crate::writer::FastqWriter<W>,
+        mut singletons: Option<&mut crate::writer::FastqWriter<W>>,
+    ) -> crate::error::Result<PairedFilterStats>
+    where
+        I: Iterator<Item = crate::error::Result<(OwnedRecord, OwnedRecord)>>,
+        W: std::io::Write,
+    {
+        let mut stats = PairedFilterStats::new();
+
+        for pair in pairs {
+            let (r1, r2) = pair?;
+            let decision = self.apply(&r1.as_record(), &r2.as_record());
+            stats.record(&decision);
+
+            match decision {
+                PairDecision::Pair(r1, r2) => {
+                    out_r1.write_record(&r1)?;
+                    out_r2.write_record(&r2)?;
+                }
+                PairDecision::SingleR1(record) | PairDecision::SingleR2(record) => {
+                    if let Some(writer) = singletons.as_deref_mut() {
+                        writer.write_record(&record)?;
+                    }
+                }
+                PairDecision::Discard => {}
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PairedFilterStats {
+    pub total_pairs: usize,
+    pub kept_pairs: usize,
+    pub r1_singletons: usize,
+    pub r2_singletons: usize,
+    pub discarded_pairs: usize,
+}
+
+impl PairedFilterStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, decision: &PairDecision) {
+        self.total_pairs += 1;
+        match decision {
+            PairDecision::Pair(..) => self.kept_pairs += 1,
+            PairDecision::SingleR1(_) => self.r1_singletons += 1,
+            PairDecision::SingleR2(_) => self.r2_singletons += 1,
+            PairDecision::Discard => self.discarded_pairs += 1,
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!("Paired Filtering Statistics:");
+        println!("  Total pairs: {}", self.total_pairs);
+        println!("  Kept pairs: {}", self.kept_pairs);
+        println!("  R1 singletons: {}", self.r1_singletons);
+        println!("  R2 singletons: {}", self.r2_singletons);
+        println!("  Discarded pairs: {}", self.discarded_pairs);
+    }
+}
+
 pub struct FilterStats {
     pub total_reads: usize,
     pub filtered_reads: usize,
@@ -0,0 +1,373 @@
+use crate::{
+    error::{FastqError, Result},
+    index::FileStamp,
+    paired::SpillDir,
+    parser::Parser,
+    record::{OwnedRecord, Record},
+};
+use memmap2::{Mmap, MmapOptions};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const HASH_INDEX_MAGIC: &[u8; 4] = b"FQHX";
+const HASH_INDEX_VERSION: u32 = 1;
+const HEADER_LEN: usize = 48;
+const SLOT_LEN: usize = 16;
+const NO_MTIME: u64 = u64::MAX;
+
+/// Stable 64-bit hash of a read ID (FNV-1a followed by the MurmurHash3
+/// finalizer), so index files are portable across builds and platforms.
+pub fn id_hash(id: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in id {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// On-disk index of read-ID hashes to record offsets, queried in place
+/// through mmap.
+///
+/// The file is a 48-byte header (magic `FQHX`, version, slot count and the
+/// FASTQ [`FileStamp`]) followed by 16-byte `(hash, offset)` slots sorted
+/// by hash and then offset, all little-endian. Hashes are uniform, so a
+/// lookup interpolates its way to the right slot in a handful of probes.
+/// Nothing is deserialized on open.
+pub struct HashIndex {
+    mmap: Mmap,
+    len: usize,
+    stamp: FileStamp,
+}
+
+impl HashIndex {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let invalid = |msg: &str| FastqError::InvalidIndex {
+            msg: msg.to_string(),
+        };
+
+        if mmap.len() < HEADER_LEN || &mmap[..4] != HASH_INDEX_MAGIC {
+            return Err(invalid("missing FQHX magic"));
+        }
+        let version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
+        if version != HASH_INDEX_VERSION {
+            return Err(FastqError::UnsupportedIndexVersion {
+                found: version,
+                supported: HASH_INDEX_VERSION,
+            });
+        }
+
+        let word = |at: usize| u64::from_le_bytes(mmap[at..at + 8].try_into().unwrap());
+        let len = usize::try_from(word(8)).map_err(|_| invalid("slot count is out of range"))?;
+        let modified_ns = word(24);
+        let stamp = FileStamp {
+            size: word(16),
+            modified_ns: (modified_ns != NO_MTIME).then_some(modified_ns),
+            fingerprint: u32::from_le_bytes(mmap[32..36].try_into().unwrap()),
+        };
+        let expected_len = len
+            .checked_mul(SLOT_LEN)
+            .and_then(|n| n.checked_add(HEADER_LEN));
+        if expected_len != Some(mmap.len()) {
+            return Err(invalid("slot table is truncated"));
+        }
+
+        Ok(HashIndex { mmap, len, stamp })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn stamp(&self) -> &FileStamp {
+        &self.stamp
+    }
+
+    /// Fails with `StaleIndex` if `fastq_path` changed since indexing.
+    pub fn verify<P: AsRef<Path>>(&self, fastq_path: P) -> Result<()> {
+        self.stamp.verify(fastq_path)
+    }
+
+    #[inline]
+    fn slot(&self, i: usize) -> (u64, u64) {
+        let at = HEADER_LEN + i * SLOT_LEN;
+        let bytes = &self.mmap[at..at + SLOT_LEN];
+        (
+            u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            u64::from_le_bytes(bytes[8..].try_into().unwrap()),
+        )
+    }
+
+    #[inline]
+    fn hash_at(&self, i: usize) -> u64 {
+        self.slot(i).0
+    }
+
+    /// Index of the first slot whose hash is `>= hash`.
+    fn lower_bound(&self, hash: u64) -> usize {
+        let (mut lo, mut hi) = (0, self.len);
+        // Interpolate while the range is large; a few rounds suffice for
+        // uniform hashes, and the bisection below bounds the worst case.
+        for _ in 0..8 {
+            if hi - lo <= 32 {
+                break;
+            }
+            let (lo_hash, hi_hash) = (self.hash_at(lo), self.hash_at(hi - 1));
+            if hash <= lo_hash {
+                return lo;
+            }
+            if hash > hi_hash {
+                return hi;
+            }
+            let fraction = (hash - lo_hash) as u128 * (hi - 1 - lo) as u128;
+            let guess = lo + (fraction / (hi_hash - lo_hash) as u128) as usize;
+            if self.hash_at(guess) < hash {
+                lo = guess + 1;
+            } else {
+                hi = guess;
+            }
+        }
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.hash_at(mid) < hash {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    /// Offsets of all records whose ID hashes like `id`, in file order.
+    /// Callers must check the ID at each offset to rule out collisions.
+    pub fn candidates(&self, id: &[u8]) -> impl Iterator<Item = u64> + '_ {
+        let hash = id_hash(id);
+        (self.lower_bound(hash)..self.len)
+            .map(move |i| self.slot(i))
+            .take_while(move |&(h, _)| h == hash)
+            .map(|(_, offset)| offset)
+    }
+}
+
+/// Builds a [`HashIndex`] with an external sort: `(hash, offset)` pairs are
+/// sorted in runs of at most `memory_budget` bytes, spilled to disk, and
+/// k-way merged into the index file.
+pub struct HashIndexBuilder {
+    memory_budget: usize,
+    spill_dir: Option<PathBuf>,
+}
+
+impl Default for HashIndexBuilder {
+    fn default() -> Self {
+        HashIndexBuilder {
+            memory_budget: 512 * 1024 * 1024,
+            spill_dir: None,
+        }
+    }
+}
+
+impl HashIndexBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    pub fn spill_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.spill_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        fastq_path: P,
+        index_path: Q,
+    ) -> Result<HashIndex> {
+        let fastq_path = fastq_path.as_ref();
+        let stamp = FileStamp::of(fastq_path)?;
+        let file = File::open(fastq_path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let data = &mmap[..];
+        let base = data.as_ptr() as usize;
+
+        let run_capacity = (self.memory_budget / SLOT_LEN).max(1);
+        let mut buffer: Vec<(u64, u64)> = Vec::with_capacity(run_capacity.min(1 << 20));
+        let mut spill: Option<SpillDir> = None;
+        let mut runs = Vec::new();
+        let mut count = 0usize;
+
+        let mut parser = Parser::new(data);
+        while let Some(record) = parser.parse_record()? {
+            let offset = (record.id.as_ptr() as usize - base - 1) as u64;
+            buffer.push((id_hash(record.id), offset));
+            count += 1;
+
+            if buffer.len() == run_capacity {
+                let dir = match &spill {
+                    Some(dir) => dir,
+                    None => spill.insert(SpillDir::create(self.spill_dir.as_deref())?),
+                };
+                runs.push(write_run(&mut buffer, &dir.0, runs.len())?);
+            }
+        }
+        buffer.sort_unstable();
+
+        let mut out = BufWriter::new(File::create(index_path.as_ref())?);
+        write_header(&mut out, count, &stamp)?;
+        if runs.is_empty() {
+            for &slot in &buffer {
+                write_slot(&mut out, slot)?;
+            }
+        } else {
+            merge_runs(&runs, buffer, &mut out)?;
+        }
+        out.flush()?;
+        drop(out);
+        drop(spill);
+
+        HashIndex::open(index_path)
+    }
+}
+
+fn write_header<W: Write>(out: &mut W, count: usize, stamp: &FileStamp) -> Result<()> {
+    let mut header = [0u8; HEADER_LEN];
+    header[..4].copy_from_slice(HASH_INDEX_MAGIC);
+    header[4..8].copy_from_slice(&HASH_INDEX_VERSION.to_le_bytes());
+    header[8..16].copy_from_slice(&(count as u64).to_le_bytes());
+    header[16..24].copy_from_slice(&stamp.size.to_le_bytes());
+    header[24..32].copy_from_slice(&stamp.modified_ns.unwrap_or(NO_MTIME).to_le_bytes());
+    header[32..36].copy_from_slice(&stamp.fingerprint.to_le_bytes());
+    out.write_all(&header)?;
+    Ok(())
+}
+
+#[inline]
+fn write_slot<W: Write>(out: &mut W, (hash, offset): (u64, u64)) -> Result<()> {
+    out.write_all(&hash.to_le_bytes())?;
+    out.write_all(&offset.to_le_bytes())?;
+    Ok(())
+}
+
+fn write_run(buffer: &mut Vec<(u64, u64)>, dir: &Path, n: usize) -> Result<PathBuf> {
+    buffer.sort_unstable();
+    let path = dir.join(format!("run_{:05}.bin", n));
+    let mut out = BufWriter::new(File::create(&path)?);
+    for &slot in buffer.iter() {
+        write_slot(&mut out, slot)?;
+    }
+    out.flush()?;
+    buffer.clear();
+    Ok(path)
+}
+
+fn read_slot<R: Read>(input: &mut R) -> Result<Option<(u64, u64)>> {
+    let mut bytes = [0u8; SLOT_LEN];
+    match input.read_exact(&mut bytes) {
+        Ok(()) => Ok(Some((
+            u64::from_le_bytes(bytes[..8].try_into().unwrap()),
+            u64::from_le_bytes(bytes[8..].try_into().unwrap()),
+        ))),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Merges the spilled runs and the final in-memory run (already sorted).
+fn merge_runs<W: Write>(runs: &[PathBuf], last: Vec<(u64, u64)>, out: &mut W) -> Result<()> {
+    let mut readers = runs
+        .iter()
+        .map(|path| Ok(BufReader::new(File::open(path)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut last = last.into_iter();
+
+    let mut heap = BinaryHeap::with_capacity(readers.len() + 1);
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(slot) = read_slot(reader)? {
+            heap.push(Reverse((slot, i)));
+        }
+    }
+    let memory_run = readers.len();
+    if let Some(slot) = last.next() {
+        heap.push(Reverse((slot, memory_run)));
+    }
+
+    while let Some(Reverse((slot, run))) = heap.pop() {
+        write_slot(out, slot)?;
+        let next = if run == memory_run {
+            last.next()
+        } else {
+            read_slot(&mut readers[run])?
+        };
+        if let Some(slot) = next {
+            heap.push(Reverse((slot, run)));
+        }
+    }
+    Ok(())
+}
+
+/// Random access by read ID through a [`HashIndex`]. Every hit is checked
+/// against the ID parsed at its offset, so hash collisions never return
+/// the wrong read.
+pub struct HashIndexedReader {
+    mmap: Mmap,
+    index: HashIndex,
+}
+
+impl HashIndexedReader {
+    /// Fails with `StaleIndex` if `index` was built from a different
+    /// version of `fastq_path`.
+    pub fn new<P: AsRef<Path>>(fastq_path: P, index: HashIndex) -> Result<Self> {
+        index.verify(&fastq_path)?;
+        let file = File::open(fastq_path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        Ok(HashIndexedReader { mmap, index })
+    }
+
+    pub fn from_paths<P: AsRef<Path>>(fastq_path: P, index_path: P) -> Result<Self> {
+        Self::new(fastq_path, HashIndex::open(index_path)?)
+    }
+
+    pub fn index(&self) -> &HashIndex {
+        &self.index
+    }
+
+    /// Offset of the first record with this ID.
+    pub fn offset(&self, id: &str) -> Option<u64> {
+        self.index.candidates(id.as_bytes()).find(|&offset| {
+            self.record_at(offset)
+                .is_some_and(|r| r.id == id.as_bytes())
+        })
+    }
+
+    pub fn get_record(&self, id: &str) -> Option<Record<'_>> {
+        self.index
+            .candidates(id.as_bytes())
+            .filter_map(|offset| self.record_at(offset))
+            .find(|record| record.id == id.as_bytes())
+    }
+
+    pub fn get_owned_record(&self, id: &str) -> Option<OwnedRecord> {
+        self.get_record(id).map(|r| OwnedRecord::from_record(&r))
+    }
+
+    fn record_at(&self, offset: u64) -> Option<Record<'_>> {
+        let data = self.mmap.get(offset as usize..)?;
+        Parser::new(data).parse_record().ok().flatten()
+    }
+}
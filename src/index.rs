@@ -1,26 +1,356 @@
 use crate::{
+    barcode::BarcodeExtractor,
     error::{FastqError, Result},
+    parser::{residue_count, residues, Parser},
     record::{OwnedRecord, Record},
 };
 use memmap2::{Mmap, MmapOptions};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
+const INDEX_MAGIC: &[u8; 4] = b"FQIX";
+const INDEX_VERSION: u32 = 5;
+const FINGERPRINT_WINDOW: u64 = 64 * 1024;
+
+/// Location of one record. `offset` and `length` cover the whole record
+/// including its trailing newline; the sequence and quality fields are the
+/// byte ranges [`Parser`] finds for them, so for wrapped records they span
+/// the embedded line breaks. `bases`, `line_bases` and `line_width` are
+/// the samtools `.fai` LENGTH, LINEBASES and LINEWIDTH columns.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexEntry {
+    pub id: String,
     pub offset: u64,
     pub length: usize,
+    pub seq_offset: u64,
     pub seq_length: usize,
+    pub qual_offset: u64,
+    pub qual_length: usize,
+    pub bases: usize,
+    pub line_bases: usize,
+    pub line_width: usize,
+}
+
+impl IndexEntry {
+    /// Rebuilds the record from `data`, which must start at `self.offset`.
+    fn record<'a>(&self, data: &'a [u8]) -> Option<Record<'a>> {
+        let data = data.get(..self.length)?;
+        let header_end = memchr::memchr(b'\n', data).unwrap_or(data.len());
+        let mut header = data.get(1..header_end)?;
+        if let [rest @ .., b'\r'] = header {
+            header = rest;
+        }
+        let (id, desc) = Parser::parse_header(header).ok()?;
+
+        let seq_start = (self.seq_offset - self.offset) as usize;
+        let qual_start = (self.qual_offset - self.offset) as usize;
+        let seq = data.get(seq_start..seq_start + self.seq_length)?;
+        let qual = data.get(qual_start..qual_start + self.qual_length)?;
+
+        Some(Record::new(id, desc, seq, qual).with_raw(data))
+    }
+
+    /// Whether the record spans more than four lines, so that its sequence
+    /// and quality ranges contain line breaks.
+    #[inline]
+    fn is_wrapped(&self) -> bool {
+        self.seq_length != self.bases
+    }
+
+    /// Absolute byte range holding residues `start..end` (0-based) of the
+    /// field at `field_offset`, following the entry's line layout.
+    fn residue_bytes(&self, field_offset: u64, start: usize, end: usize) -> (u64, u64) {
+        if start >= end || self.line_bases == 0 {
+            return (field_offset, field_offset);
+        }
+        let byte = |k: usize| {
+            field_offset + ((k / self.line_bases) * self.line_width + k % self.line_bases) as u64
+        };
+        (byte(start), byte(end - 1) + 1)
+    }
+}
+
+/// A samtools-style region, `read_id:start-end`, with 1-based inclusive
+/// coordinates. A missing `start` means the start of the read and a missing
+/// `end` means its end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub id: String,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl Region {
+    pub fn new(id: impl Into<String>, start: Option<usize>, end: Option<usize>) -> Self {
+        Region {
+            id: id.into(),
+            start,
+            end,
+        }
+    }
+
+    /// Parses `id`, `id:start`, `id:start-` or `id:start-end`; as in
+    /// samtools, `id:start` runs to the end of the read. Thousands
+    /// separators are allowed in coordinates. If the text after the last
+    /// `:` is not a range, the whole string is taken as the ID.
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = |msg: &str| FastqError::InvalidRegion {
+            region: text.to_string(),
+            msg: msg.to_string(),
+        };
+
+        let Some((id, range)) = text.rsplit_once(':') else {
+            return Ok(Region::new(text, None, None));
+        };
+        let range = range.replace(',', "");
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start, Some(end)),
+            None => (range.as_str(), None),
+        };
+        let Ok(start) = start.parse::<usize>() else {
+            return Ok(Region::new(text, None, None));
+        };
+        let end = match end {
+            None | Some("") => None,
+            Some(end) => match end.parse::<usize>() {
+                Ok(end) => Some(end),
+                Err(_) => return Ok(Region::new(text, None, None)),
+            },
+        };
+
+        if start == 0 {
+            return Err(invalid("coordinates are 1-based"));
+        }
+        if end.is_some_and(|end| end < start) {
+            return Err(invalid("end is before start"));
+        }
+        Ok(Region::new(id, Some(start), end))
+    }
+
+    /// Reads one region per line, skipping blank lines and `#` comments.
+    pub fn read_list<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut regions = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                regions.push(line.to_string());
+            }
+        }
+        Ok(regions)
+    }
+
+    /// 0-based half-open residue range within a read of `bases` residues.
+    fn bounds(&self, bases: usize) -> (usize, usize) {
+        let end = self.end.unwrap_or(bases).min(bases);
+        let start = (self.start.unwrap_or(1) - 1).min(end);
+        (start, end)
+    }
+
+    fn output_id(&self, bases: usize, reverse_complement: bool) -> Vec<u8> {
+        let mut id = match self.start {
+            Some(_) => {
+                let (start, end) = self.bounds(bases);
+                format!("{}:{}-{}", self.id, start + 1, end)
+            }
+            None => self.id.clone(),
+        };
+        if reverse_complement {
+            id.push_str("/rc");
+        }
+        id.into_bytes()
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)?;
+        if let Some(start) = self.start {
+            write!(f, ":{}-", start)?;
+            if let Some(end) = self.end {
+                write!(f, "{}", end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builds the record for `region` from its raw sequence and quality bytes,
+/// dropping the line breaks of wrapped reads.
+fn region_record(
+    region: &Region,
+    entry: &IndexEntry,
+    seq: &[u8],
+    qual: &[u8],
+    reverse_complement: bool,
+) -> OwnedRecord {
+    let mut record = OwnedRecord {
+        id: region.output_id(entry.bases, reverse_complement),
+        desc: None,
+        seq: residues(seq),
+        qual: residues(qual),
+    };
+    if reverse_complement {
+        record.seq = crate::simd::reverse_complement(&record.seq);
+        record.qual.reverse();
+    }
+    record
+}
+
+/// Identity of the FASTQ file an index was built from: its size, its
+/// modification time and a CRC32 of its first and last 64 KiB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    pub modified_ns: Option<u64>,
+    pub fingerprint: u32,
+}
+
+impl FileStamp {
+    pub fn of<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        Ok(FileStamp {
+            size: metadata.len(),
+            modified_ns: modified_ns(&metadata),
+            fingerprint: fingerprint(&mut file, metadata.len())?,
+        })
+    }
+
+    /// Checks `path` against this stamp. The size must match. When the
+    /// modification time also matches, the file is taken as unchanged;
+    /// otherwise (e.g. after a copy or `touch`) the fingerprint decides.
+    pub fn verify<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let stale = |reason: String| FastqError::StaleIndex {
+            path: path.display().to_string(),
+            reason,
+        };
+
+        let mut file = File::open(path)?;
+        let metadata = file.metadata()?;
+        if metadata.len() != self.size {
+            return Err(stale(format!(
+                "file is {} bytes, index was built for {} bytes",
+                metadata.len(),
+                self.size
+            )));
+        }
+        if self.modified_ns.is_some() && modified_ns(&metadata) == self.modified_ns {
+            return Ok(());
+        }
+        if fingerprint(&mut file, self.size)? != self.fingerprint {
+            return Err(stale("file content has changed".to_string()));
+        }
+        Ok(())
+    }
 }
 
+fn modified_ns(metadata: &std::fs::Metadata) -> Option<u64> {
+    let modified = metadata.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+fn fingerprint(file: &mut File, size: u64) -> Result<u32> {
+    let mut crc = flate2::Crc::new();
+    let mut buffer = vec![0u8; FINGERPRINT_WINDOW.min(size) as usize];
+
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut buffer)?;
+    crc.update(&buffer);
+
+    let tail = size
+        .saturating_sub(FINGERPRINT_WINDOW)
+        .max(buffer.len() as u64);
+    buffer.resize((size - tail) as usize, 0);
+    file.seek(SeekFrom::Start(tail))?;
+    file.read_exact(&mut buffer)?;
+    crc.update(&buffer);
+
+    Ok(crc.sum())
+}
+
+type KeyFn = Box<dyn Fn(&Record) -> Option<Vec<u8>>>;
+
+/// How a secondary index or a
+/// [`PartitionedWriter`](crate::split::PartitionedWriter) derives its key
+/// from a record. Records for which no key can be extracted are left out of
+/// that secondary index.
+pub enum KeyExtractor {
+    /// The barcode found by a `BarcodeExtractor`.
+    Barcode(BarcodeExtractor),
+    /// The UMI found by a `BarcodeExtractor`.
+    Umi(BarcodeExtractor),
+    /// The `field`th (0-based) `delimiter`-separated field of the read ID.
+    IdField { delimiter: u8, field: usize },
+    /// The `field`th (0-based) `delimiter`-separated field of the
+    /// description, e.g. field 3 of `1:N:0:ACGTACGT` for the sample index.
+    DescField { delimiter: u8, field: usize },
+    /// A stable hash of the ID modulo `buckets`, written as a decimal
+    /// number. A trailing `/1` or `/2` is ignored so mates agree, which
+    /// makes it suitable for deterministic train/test splits.
+    IdHash { buckets: u64 },
+    /// Any key computed from the record.
+    Custom(KeyFn),
+}
+
+impl KeyExtractor {
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(&Record) -> Option<Vec<u8>> + 'static,
+    {
+        KeyExtractor::Custom(Box::new(f))
+    }
+
+    pub fn extract(&self, record: &Record) -> Option<Vec<u8>> {
+        let field = |text: &[u8], delimiter: u8, field: usize| {
+            text.split(|&b| b == delimiter)
+                .nth(field)
+                .map(<[u8]>::to_vec)
+        };
+        match self {
+            KeyExtractor::Barcode(extractor) => {
+                extractor.extract(record).map(|(barcode, _)| barcode)
+            }
+            KeyExtractor::Umi(extractor) => extractor.extract(record).and_then(|(_, umi)| umi),
+            KeyExtractor::IdField {
+                delimiter,
+                field: n,
+            } => field(record.id, *delimiter, *n),
+            KeyExtractor::DescField {
+                delimiter,
+                field: n,
+            } => field(record.desc?, *delimiter, *n),
+            KeyExtractor::IdHash { buckets } => {
+                let id = match record.id {
+                    [id @ .., b'/', b'1' | b'2'] => id,
+                    id => id,
+                };
+                let bucket = crate::hash_index::id_hash(id) % (*buckets).max(1);
+                Some(bucket.to_string().into_bytes())
+            }
+            KeyExtractor::Custom(f) => f(record),
+        }
+    }
+}
+
+/// Record locations in file order, plus a permutation of those positions
+/// sorted by ID for lookups and prefix queries. Indexes built from a file
+/// carry its [`FileStamp`], which readers check before trusting offsets.
+/// Named secondary indexes map other keys, such as cell barcodes, to the
+/// positions of every record carrying them.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FastqIndex {
-    entries: HashMap<String, IndexEntry>,
-    total_records: usize,
-    file_size: u64,
+    entries: Vec<IndexEntry>,
+    sorted: Vec<usize>,
+    stamp: Option<FileStamp>,
+    secondary: BTreeMap<String, BTreeMap<Vec<u8>, Vec<usize>>>,
 }
 
 impl Default for FastqIndex {
@@ -32,158 +362,641 @@ impl Default for FastqIndex {
 impl FastqIndex {
     pub fn new() -> Self {
         FastqIndex {
-            entries: HashMap::new(),
-            total_records: 0,
-            file_size: 0,
+            entries: Vec::new(),
+            sorted: Vec::new(),
+            stamp: None,
+            secondary: BTreeMap::new(),
         }
     }
 
+    /// Indexes every record with [`Parser`] in its wrapped-record mode, so
+    /// wrapped sequences, CRLF line endings and a missing final newline are
+    /// all accepted, and malformed input fails with the parser's line
+    /// numbers.
     pub fn build<P: AsRef<Path>>(fastq_path: P) -> Result<Self> {
         let path = fastq_path.as_ref();
         let file = File::open(path)?;
-        let file_size = file.metadata()?.len();
-
         let mmap = unsafe { MmapOptions::new().map(&file)? };
         let mut index = FastqIndex::new();
-        index.file_size = file_size;
+        index.stamp = Some(FileStamp::of(path)?);
+
+        index.entries = index_range(&mmap, 0, false)?;
+        index.sort_ids();
+        Ok(index)
+    }
+
+    /// Like [`build`](Self::build), but indexes record-aligned chunks on
+    /// all rayon threads and concatenates the results.
+    pub fn build_parallel<P: AsRef<Path>>(fastq_path: P) -> Result<Self> {
+        Self::build_parallel_with_threads(fastq_path, rayon::current_num_threads())
+    }
 
-        let mut pos = 0;
+    /// Parallel build splitting the file into about `num_threads` chunks.
+    /// Chunks come from the local resync `ParallelParser` uses, whose cuts
+    /// are only guaranteed to fall between records of strict four-line
+    /// FASTQ. Each chunk is therefore validated as it is indexed: a chunk
+    /// that fails to parse or holds a wrapped record sends the whole file
+    /// through the sequential build, so errors carry file-wide line numbers.
+    pub fn build_parallel_with_threads<P: AsRef<Path>>(
+        fastq_path: P,
+        num_threads: usize,
+    ) -> Result<Self> {
+        let path = fastq_path.as_ref();
+        let file = File::open(path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
         let data = &mmap[..];
 
-        while pos < data.len() {
-            if data[pos] != b'@' {
-                return Err(FastqError::InvalidHeader { line: 0 });
-            }
+        let Some(chunks) = crate::parallel::exact_record_chunks(data, num_threads) else {
+            return Self::build(path);
+        };
+        let parts = chunks
+            .par_iter()
+            .map(|&(start, end)| {
+                let entries = index_range(&data[..end], start, false).ok()?;
+                let strict = entries.iter().all(|entry| !entry.is_wrapped());
+                strict.then_some(entries)
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(parts) = parts else {
+            return Self::build(path);
+        };
 
-            let record_start = pos;
+        let mut index = FastqIndex::new();
+        index.stamp = Some(FileStamp::of(path)?);
+        index.entries = parts.concat();
+        index.sort_ids();
+        Ok(index)
+    }
 
-            let header_end =
-                memchr::memchr(b'\n', &data[pos..]).ok_or(FastqError::UnexpectedEof)?;
-            let header = &data[pos + 1..pos + header_end];
+    /// Indexes records appended to `fastq_path` since the index was built
+    /// or last updated, e.g. by a live basecaller, and returns how many
+    /// were added. The previously indexed part of the file must be
+    /// unchanged, otherwise this fails with `StaleIndex`. An incomplete
+    /// record at the end of the file is left for the next update. An index
+    /// from [`new`](Self::new) indexes the file from the start.
+    ///
+    /// Secondary indexes are dropped, since their key extractors are not
+    /// stored; add them again after updating.
+    pub fn update<P: AsRef<Path>>(&mut self, fastq_path: P) -> Result<usize> {
+        let path = fastq_path.as_ref();
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
 
-            let id_end = header
-                .iter()
-                .position(|&b| b == b' ')
-                .unwrap_or(header.len());
-            let id = String::from_utf8_lossy(&header[..id_end]).into_owned();
+        match self.stamp {
+            Some(old) => {
+                let stale = |reason: &str| FastqError::StaleIndex {
+                    path: path.display().to_string(),
+                    reason: reason.to_string(),
+                };
+                if size < old.size {
+                    return Err(stale("file is shorter than when it was indexed"));
+                }
+                if fingerprint(&mut file, old.size)? != old.fingerprint {
+                    return Err(stale("indexed part of the file has changed"));
+                }
+            }
+            None if !self.entries.is_empty() => {
+                return Err(FastqError::InvalidIndex {
+                    msg: "index has entries but no file stamp".to_string(),
+                });
+            }
+            None => {}
+        }
+
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let resume = self
+            .entries
+            .last()
+            .map_or(0, |entry| entry.offset as usize + entry.length);
+        let appended = index_range(&mmap, resume, true)?;
+        let count = appended.len();
 
-            pos += header_end + 1;
+        let first_new = self.entries.len();
+        self.entries.extend(appended);
+        self.merge_sorted(first_new);
+        self.stamp = Some(FileStamp::of(path)?);
+        self.secondary.clear();
+        Ok(count)
+    }
 
-            let seq_end = memchr::memchr(b'\n', &data[pos..]).ok_or(FastqError::UnexpectedEof)?;
-            let seq_length = seq_end;
-            pos += seq_end + 1;
+    /// Merges entries `first_new..` into the sorted ID table. Older entries
+    /// win ties, which keeps duplicate IDs in file order.
+    fn merge_sorted(&mut self, first_new: usize) {
+        let entries = &self.entries;
+        let mut new: Vec<usize> = (first_new..entries.len()).collect();
+        new.sort_by(|&a, &b| entries[a].id.cmp(&entries[b].id));
 
-            if data[pos] != b'+' {
-                return Err(FastqError::InvalidSeparator { line: 0 });
+        let old = std::mem::take(&mut self.sorted);
+        let mut merged = Vec::with_capacity(entries.len());
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() && j < new.len() {
+            if entries[new[j]].id < entries[old[i]].id {
+                merged.push(new[j]);
+                j += 1;
+            } else {
+                merged.push(old[i]);
+                i += 1;
             }
+        }
+        merged.extend_from_slice(&old[i..]);
+        merged.extend_from_slice(&new[j..]);
+        self.sorted = merged;
+    }
 
-            let sep_end = memchr::memchr(b'\n', &data[pos..]).ok_or(FastqError::UnexpectedEof)?;
-            pos += sep_end + 1;
+    /// Conventional location of the samtools index for `fastq_path`.
+    pub fn fai_path<P: AsRef<Path>>(fastq_path: P) -> PathBuf {
+        let mut path = fastq_path.as_ref().as_os_str().to_owned();
+        path.push(".fai");
+        PathBuf::from(path)
+    }
 
-            let qual_end = memchr::memchr(b'\n', &data[pos..]).ok_or(FastqError::UnexpectedEof)?;
-            pos += qual_end + 1;
+    /// Loads a `samtools fqidx` index. The FASTQ file is needed as well,
+    /// because `.fai` files do not record where each header line starts.
+    /// As in samtools, a `.fai` older than its FASTQ file is stale; only
+    /// then is the loaded index stamped with the FASTQ's current state.
+    pub fn read_fai<P: AsRef<Path>, Q: AsRef<Path>>(fastq_path: P, fai_path: Q) -> Result<Self> {
+        let file = File::open(&fastq_path)?;
+        let fai = File::open(fai_path)?;
+        let fastq_modified = modified_ns(&file.metadata()?);
+        let fai_modified = modified_ns(&fai.metadata()?);
+        if let (Some(fastq_modified), Some(fai_modified)) = (fastq_modified, fai_modified) {
+            if fai_modified < fastq_modified {
+                return Err(FastqError::StaleIndex {
+                    path: fastq_path.as_ref().display().to_string(),
+                    reason: "the .fai is older than the FASTQ file".to_string(),
+                });
+            }
+        }
 
-            let record_length = pos - record_start;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let data = &mmap[..];
 
-            index.entries.insert(
-                id,
-                IndexEntry {
-                    offset: record_start as u64,
-                    length: record_length,
-                    seq_length,
-                },
-            );
+        let mut index = FastqIndex::new();
+        index.stamp = Some(FileStamp::of(&fastq_path)?);
 
-            index.total_records += 1;
+        let reader = BufReader::new(fai);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry = parse_fai_line(&line, data).map_err(|msg| FastqError::InvalidIndex {
+                msg: format!(".fai line {}: {}", i + 1, msg),
+            })?;
+            index.entries.push(entry);
         }
 
+        index.sort_ids();
         Ok(index)
     }
 
+    /// Writes the index in the `samtools fqidx` layout: NAME, LENGTH,
+    /// OFFSET, LINEBASES, LINEWIDTH and QUALOFFSET, tab-separated.
+    pub fn write_fai<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for entry in &self.entries {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                entry.id,
+                entry.bases,
+                entry.seq_offset,
+                entry.line_bases,
+                entry.line_width,
+                entry.qual_offset
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn sort_ids(&mut self) {
+        let entries = &self.entries;
+        let mut sorted: Vec<usize> = (0..entries.len()).collect();
+        // Stable, so duplicate IDs keep file order and lookups find the first.
+        sorted.par_sort_by(|&a, &b| entries[a].id.cmp(&entries[b].id));
+        self.sorted = sorted;
+    }
+
+    /// Writes the index as a 4-byte magic, a little-endian `u32` format
+    /// version and the bincode-encoded body.
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let file = File::create(path)?;
-        let writer = BufWriter::new(file);
-        bincode::serialize_into(writer, self)
+        let mut writer = BufWriter::new(file);
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_all(&INDEX_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, self)
             .map_err(|e| FastqError::Io(std::io::Error::other(e)))?;
+        writer.flush()?;
         Ok(())
     }
 
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        bincode::deserialize_from(reader).map_err(|e| FastqError::Io(std::io::Error::other(e)))
+        let mut reader = BufReader::new(file);
+
+        let mut header = [0u8; 8];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| FastqError::InvalidIndex {
+                msg: "file is too short to be an index".to_string(),
+            })?;
+        if &header[..4] != INDEX_MAGIC {
+            return Err(FastqError::InvalidIndex {
+                msg: "missing FQIX magic".to_string(),
+            });
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version != INDEX_VERSION {
+            return Err(FastqError::UnsupportedIndexVersion {
+                found: version,
+                supported: INDEX_VERSION,
+            });
+        }
+
+        let index: FastqIndex = bincode::deserialize_from(reader)
+            .map_err(|e| FastqError::Io(std::io::Error::other(e)))?;
+        if index.sorted.len() != index.entries.len() {
+            return Err(FastqError::InvalidIndex {
+                msg: "sorted ID table does not match entry count".to_string(),
+            });
+        }
+        Ok(index)
+    }
+
+    /// File position of the first record with this ID.
+    pub fn position(&self, id: &str) -> Option<usize> {
+        let i = self
+            .sorted
+            .partition_point(|&p| self.entries[p].id.as_str() < id);
+        let &p = self.sorted.get(i)?;
+        (self.entries[p].id == id).then_some(p)
     }
 
     pub fn get(&self, id: &str) -> Option<&IndexEntry> {
-        self.entries.get(id)
+        self.position(id).map(|p| &self.entries[p])
+    }
+
+    pub fn get_by_position(&self, n: usize) -> Option<&IndexEntry> {
+        self.entries.get(n)
     }
 
     pub fn contains(&self, id: &str) -> bool {
-        self.entries.contains_key(id)
+        self.position(id).is_some()
     }
 
     pub fn len(&self) -> usize {
-        self.total_records
+        self.entries.len()
     }
 
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
 
+    pub fn file_size(&self) -> u64 {
+        self.stamp.map_or(0, |stamp| stamp.size)
+    }
+
+    pub fn stamp(&self) -> Option<&FileStamp> {
+        self.stamp.as_ref()
+    }
+
+    /// Fails with `StaleIndex` if `fastq_path` is not the file this index
+    /// was built from. Indexes created with `new` carry no stamp and always
+    /// pass.
+    pub fn verify<P: AsRef<Path>>(&self, fastq_path: P) -> Result<()> {
+        match &self.stamp {
+            Some(stamp) => stamp.verify(fastq_path),
+            None => Ok(()),
+        }
+    }
+
+    /// Conventional location of the cached index for `fastq_path`.
+    pub fn sidecar_path<P: AsRef<Path>>(fastq_path: P) -> PathBuf {
+        let mut path = fastq_path.as_ref().as_os_str().to_owned();
+        path.push(".fqi");
+        PathBuf::from(path)
+    }
+
+    /// Loads the sidecar index of `fastq_path`, rebuilding and re-saving it
+    /// when it is missing, unreadable, from another format version or
+    /// stale. A sidecar that cannot be written (e.g. a read-only directory)
+    /// does not fail the call; the freshly built index is still returned.
+    pub fn load_or_build<P: AsRef<Path>>(fastq_path: P) -> Result<Self> {
+        let fastq_path = fastq_path.as_ref();
+        let sidecar = Self::sidecar_path(fastq_path);
+
+        if let Ok(index) = Self::load(&sidecar) {
+            if index.stamp.is_some() && index.verify(fastq_path).is_ok() {
+                return Ok(index);
+            }
+        }
+
+        let index = Self::build(fastq_path)?;
+        let _ = index.save(&sidecar);
+        Ok(index)
+    }
+
+    /// Entries in file order.
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// IDs in file order.
     pub fn ids(&self) -> impl Iterator<Item = &String> {
-        self.entries.keys()
+        self.entries.iter().map(|e| &e.id)
+    }
+
+    /// IDs in lexicographic order.
+    pub fn sorted_ids(&self) -> impl Iterator<Item = &String> {
+        self.sorted.iter().map(|&p| &self.entries[p].id)
+    }
+
+    /// Entries whose ID starts with `prefix`, in ID order.
+    pub fn with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a IndexEntry> {
+        let start = self
+            .sorted
+            .partition_point(|&p| self.entries[p].id.as_str() < prefix);
+        self.sorted[start..]
+            .iter()
+            .map(|&p| &self.entries[p])
+            .take_while(move |e| e.id.starts_with(prefix))
+    }
+
+    /// Adds (or replaces) the secondary index `name`, reading keys from
+    /// the FASTQ file this index was built from. Returns the number of
+    /// distinct keys.
+    pub fn add_secondary<P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        fastq_path: P,
+        extractor: &KeyExtractor,
+    ) -> Result<usize> {
+        self.verify(&fastq_path)?;
+        let file = File::open(fastq_path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        self.index_secondary(name, &mmap, extractor)
+    }
+
+    fn index_secondary(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        extractor: &KeyExtractor,
+    ) -> Result<usize> {
+        let mut keys: BTreeMap<Vec<u8>, Vec<usize>> = BTreeMap::new();
+        for (position, entry) in self.entries.iter().enumerate() {
+            let record = data
+                .get(entry.offset as usize..)
+                .and_then(|data| entry.record(data))
+                .ok_or_else(|| FastqError::InvalidIndex {
+                    msg: format!("entry for {} does not match the FASTQ file", entry.id),
+                })?;
+            let key = if entry.is_wrapped() {
+                extractor.extract(&OwnedRecord::from_wrapped(&record).as_record())
+            } else {
+                extractor.extract(&record)
+            };
+            if let Some(key) = key {
+                keys.entry(key).or_default().push(position);
+            }
+        }
+
+        let count = keys.len();
+        self.secondary.insert(name.to_string(), keys);
+        Ok(count)
+    }
+
+    pub fn remove_secondary(&mut self, name: &str) -> bool {
+        self.secondary.remove(name).is_some()
+    }
+
+    pub fn secondary_names(&self) -> impl Iterator<Item = &String> {
+        self.secondary.keys()
+    }
+
+    /// Distinct keys of a secondary index in byte order, with the number
+    /// of records for each. `None` if there is no index called `name`.
+    pub fn secondary_keys(&self, name: &str) -> Option<impl Iterator<Item = (&[u8], usize)>> {
+        let keys = self.secondary.get(name)?;
+        Some(
+            keys.iter()
+                .map(|(key, positions)| (key.as_slice(), positions.len())),
+        )
+    }
+
+    /// File positions of the records carrying `key`, in file order. `None`
+    /// if there is no index called `name`; empty if the key is absent.
+    pub fn lookup_secondary(&self, name: &str, key: &[u8]) -> Option<&[usize]> {
+        let keys = self.secondary.get(name)?;
+        Some(keys.get(key).map_or(&[], Vec::as_slice))
+    }
+}
+
+/// Indexes the records of `data[start..]`, with offsets relative to the
+/// start of `data`. With `partial_tail`, a record cut short by the end of
+/// the data ends the scan instead of failing it.
+fn index_range(data: &[u8], start: usize, partial_tail: bool) -> Result<Vec<IndexEntry>> {
+    let base = data.as_ptr() as usize;
+    let offset_of = |field: &[u8]| (field.as_ptr() as usize - base) as u64;
+    let mut parser = Parser::new(&data[start..]).allow_wrapped();
+    let mut entries = Vec::new();
+
+    loop {
+        let record = match parser.parse_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(FastqError::UnexpectedEof | FastqError::LengthMismatch { .. }) if partial_tail => {
+                break
+            }
+            Err(e) => return Err(e),
+        };
+
+        // The ID slice starts right after the '@' of the header line.
+        let offset = offset_of(record.id) - 1;
+        let seq_offset = offset_of(record.seq);
+        let (line_bases, line_width) = line_layout(data, seq_offset as usize, record.seq);
+
+        entries.push(IndexEntry {
+            id: String::from_utf8_lossy(record.id).into_owned(),
+            offset,
+            length: start + parser.pos - offset as usize,
+            seq_offset,
+            seq_length: record.seq.len(),
+            qual_offset: offset_of(record.qual),
+            qual_length: record.qual.len(),
+            bases: residue_count(record.seq),
+            line_bases,
+            line_width,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// LINEBASES and LINEWIDTH of a sequence field: residues per full line and
+/// bytes per full line including its terminator.
+fn line_layout(data: &[u8], seq_offset: usize, seq: &[u8]) -> (usize, usize) {
+    match memchr::memchr(b'\n', seq) {
+        Some(nl) => {
+            let bases = if nl > 0 && seq[nl - 1] == b'\r' {
+                nl - 1
+            } else {
+                nl
+            };
+            (bases, nl + 1)
+        }
+        None => {
+            let eol = match data.get(seq_offset + seq.len()) {
+                Some(b'\r') => 2,
+                _ => 1,
+            };
+            (seq.len(), seq.len() + eol)
+        }
     }
 }
 
+/// Bytes spanned by `bases` residues laid out `line_bases` per line.
+/// `None` if the span overflows, which only junk `.fai` numbers cause.
+fn field_span(bases: usize, line_bases: usize, line_width: usize) -> Option<usize> {
+    if bases == 0 || line_bases == 0 {
+        return Some(0);
+    }
+    let full_lines = (bases - 1) / line_bases;
+    full_lines
+        .checked_mul(line_width)?
+        .checked_add(bases - full_lines * line_bases)
+}
+
+fn parse_fai_line(line: &str, data: &[u8]) -> std::result::Result<IndexEntry, String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 6 {
+        return Err(format!("expected 6 columns, found {}", fields.len()));
+    }
+    let number = |i: usize| -> std::result::Result<usize, String> {
+        fields[i]
+            .parse()
+            .map_err(|_| format!("invalid number {:?}", fields[i]))
+    };
+    let (bases, seq_offset, line_bases, line_width, qual_offset) =
+        (number(1)?, number(2)?, number(3)?, number(4)?, number(5)?);
+    if line_width < line_bases {
+        return Err("LINEWIDTH is smaller than LINEBASES".to_string());
+    }
+
+    let outside = || "offsets lie outside the FASTQ file".to_string();
+    let seq_length = field_span(bases, line_bases, line_width).ok_or_else(outside)?;
+    let seq_end = seq_offset.checked_add(seq_length).ok_or_else(outside)?;
+    let qual_end = qual_offset.checked_add(seq_length).ok_or_else(outside)?;
+    if seq_offset == 0 || seq_offset > data.len() || qual_end > data.len() {
+        return Err(outside());
+    }
+    if qual_offset < seq_end {
+        return Err("quality starts before the sequence ends".to_string());
+    }
+
+    // The header is the line ending just before the sequence.
+    let offset = memchr::memrchr(b'\n', &data[..seq_offset - 1]).map_or(0, |p| p + 1);
+    let mut header = &data[offset..seq_offset - 1];
+    if let [rest @ .., b'\r'] = header {
+        header = rest;
+    }
+    let id = match header {
+        [b'@', rest @ ..] => Parser::parse_header(rest).map_err(|e| e.to_string())?.0,
+        _ => return Err(format!("no header line before offset {}", seq_offset)),
+    };
+    if id != fields[0].as_bytes() {
+        return Err(format!(
+            "name {} does not match read {}",
+            fields[0],
+            String::from_utf8_lossy(id)
+        ));
+    }
+
+    let mut end = qual_end;
+    if data.get(end) == Some(&b'\r') {
+        end += 1;
+    }
+    if data.get(end) == Some(&b'\n') {
+        end += 1;
+    }
+
+    Ok(IndexEntry {
+        id: fields[0].to_string(),
+        offset: offset as u64,
+        length: end - offset,
+        seq_offset: seq_offset as u64,
+        seq_length,
+        qual_offset: qual_offset as u64,
+        qual_length: seq_length,
+        bases,
+        line_bases,
+        line_width,
+    })
+}
+
 pub struct IndexedReader {
     mmap: Mmap,
     index: FastqIndex,
 }
 
 impl IndexedReader {
+    /// Fails with `StaleIndex` if `index` was built from a different
+    /// version of `fastq_path`.
     pub fn new<P: AsRef<Path>>(fastq_path: P, index: FastqIndex) -> Result<Self> {
+        index.verify(&fastq_path)?;
         let file = File::open(fastq_path)?;
         let mmap = unsafe { MmapOptions::new().map(&file)? };
 
         Ok(IndexedReader { mmap, index })
     }
 
+    /// Opens `fastq_path` with its sidecar index (`<fastq_path>.fqi`),
+    /// building and caching it when it is missing or stale.
+    pub fn open_or_build<P: AsRef<Path>>(fastq_path: P) -> Result<Self> {
+        let index = FastqIndex::load_or_build(&fastq_path)?;
+        Self::new(fastq_path, index)
+    }
+
     pub fn from_paths<P: AsRef<Path>>(fastq_path: P, index_path: P) -> Result<Self> {
         let index = FastqIndex::load(index_path)?;
         Self::new(fastq_path, index)
     }
 
+    /// Opens `fastq_path` with an existing `samtools fqidx` index.
+    pub fn from_fai<P: AsRef<Path>>(fastq_path: P, fai_path: P) -> Result<Self> {
+        let index = FastqIndex::read_fai(&fastq_path, fai_path)?;
+        Self::new(fastq_path, index)
+    }
+
+    /// Borrows the record from the mapped file. Wrapped records cannot be
+    /// borrowed without their line breaks, so they yield `None` here and
+    /// are only available through [`get_owned_record`](Self::get_owned_record).
     pub fn get_record(&self, id: &str) -> Option<Record<'_>> {
-        let entry = self.index.get(id)?;
+        self.record_at(self.index.get(id)?)
+    }
 
-        if entry.offset as usize + entry.length > self.mmap.len() {
+    /// The `n`th record in file order. `None` for wrapped records, as with
+    /// [`get_record`](Self::get_record).
+    pub fn get_by_position(&self, n: usize) -> Option<Record<'_>> {
+        self.record_at(self.index.get_by_position(n)?)
+    }
+
+    fn record_at(&self, entry: &IndexEntry) -> Option<Record<'_>> {
+        if entry.is_wrapped() {
             return None;
         }
+        entry.record(self.mmap.get(entry.offset as usize..)?)
+    }
 
-        let data = &self.mmap[entry.offset as usize..entry.offset as usize + entry.length];
-
-        let header_end = memchr::memchr(b'\n', data)?;
-        let header = &data[1..header_end];
-
-        let (id_bytes, desc) = if let Some(space_pos) = header.iter().position(|&b| b == b' ') {
-            (&header[..space_pos], Some(&header[space_pos + 1..]))
-        } else {
-            (header, None)
-        };
-
-        let seq_start = header_end + 1;
-        let seq_end = seq_start + entry.seq_length;
-        let seq = &data[seq_start..seq_end];
-
-        let qual_start = data[seq_end..].iter().position(|&b| b == b'\n')? + seq_end + 1;
-        let qual_end = qual_start + entry.seq_length;
-        let qual = &data[qual_start..qual_end];
-
-        Some(Record::new(id_bytes, desc, seq, qual))
+    fn owned_record_at(&self, entry: &IndexEntry) -> Option<OwnedRecord> {
+        let record = entry.record(self.mmap.get(entry.offset as usize..)?)?;
+        Some(OwnedRecord::from_wrapped(&record))
     }
 
+    /// Copies the record, joining the lines of wrapped records.
     pub fn get_owned_record(&self, id: &str) -> Option<OwnedRecord> {
-        self.get_record(id).map(|r| OwnedRecord::from_record(&r))
+        self.owned_record_at(self.index.get(id)?)
     }
 
     pub fn get_batch(&self, ids: &[&str]) -> Vec<Option<OwnedRecord>> {
@@ -194,43 +1007,156 @@ impl IndexedReader {
         &self.index
     }
 
+    /// Records `start..start + count` in file order.
     pub fn iter_range(&self, start: usize, count: usize) -> RangeIterator<'_> {
+        let end = start.saturating_add(count).min(self.index.len());
         RangeIterator {
             reader: self,
-            ids: self.index.ids().skip(start).take(count).cloned().collect(),
-            current: 0,
+            current: start.min(end),
+            end,
         }
     }
+
+    /// Parses `text` as a region, treating it as a plain read ID when the
+    /// whole string is indexed (Illumina IDs end in `:<number>`).
+    pub fn resolve_region(&self, text: &str) -> Result<Region> {
+        if self.index.contains(text) {
+            Ok(Region::new(text, None, None))
+        } else {
+            Region::parse(text)
+        }
+    }
+
+    /// Copies the residues of `region` and their qualities. Only the bytes
+    /// of the requested range are read, so long reads are never fully
+    /// materialized. Returns `None` when the read is not indexed.
+    pub fn fetch(&self, region: &Region, reverse_complement: bool) -> Result<Option<OwnedRecord>> {
+        let Some(entry) = self.index.get(&region.id) else {
+            return Ok(None);
+        };
+        let (start, end) = region.bounds(entry.bases);
+        let field = |offset: u64| -> Result<&[u8]> {
+            let (from, to) = entry.residue_bytes(offset, start, end);
+            self.mmap
+                .get(from as usize..to as usize)
+                .ok_or_else(|| FastqError::InvalidIndex {
+                    msg: format!("entry for {} lies outside the FASTQ file", entry.id),
+                })
+        };
+
+        Ok(Some(region_record(
+            region,
+            entry,
+            field(entry.seq_offset)?,
+            field(entry.qual_offset)?,
+            reverse_complement,
+        )))
+    }
+
+    /// Fetches each region in order, failing on the first read that is not
+    /// indexed.
+    pub fn fetch_regions<I, S>(
+        &self,
+        regions: I,
+        reverse_complement: bool,
+    ) -> Result<Vec<OwnedRecord>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        regions
+            .into_iter()
+            .map(|text| {
+                let text = text.as_ref();
+                let region = self.resolve_region(text)?;
+                self.fetch(&region, reverse_complement)?
+                    .ok_or_else(|| FastqError::RegionNotFound {
+                        region: text.to_string(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Fetches every region listed in `path`, one per line.
+    pub fn fetch_region_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        reverse_complement: bool,
+    ) -> Result<Vec<OwnedRecord>> {
+        self.fetch_regions(Region::read_list(path)?, reverse_complement)
+    }
+
+    /// Adds the secondary index `name` from the mapped file. Save it with
+    /// `index().save(..)` to keep it.
+    pub fn add_secondary(&mut self, name: &str, extractor: &KeyExtractor) -> Result<usize> {
+        self.index.index_secondary(name, &self.mmap, extractor)
+    }
+
+    /// Streams every record whose secondary key `name` equals `key`, in
+    /// file order. `None` if there is no secondary index called `name`.
+    /// Records are copied, like [`iter_range`](Self::iter_range), so that
+    /// wrapped records come back with their lines joined.
+    pub fn records_for_key<'a>(
+        &'a self,
+        name: &str,
+        key: &[u8],
+    ) -> Option<impl Iterator<Item = OwnedRecord> + 'a> {
+        let positions = self.index.lookup_secondary(name, key)?;
+        Some(
+            positions
+                .iter()
+                .filter_map(move |&p| self.owned_record_at(self.index.get_by_position(p)?)),
+        )
+    }
+
+    /// Records whose ID starts with `prefix`, in ID order. Records are
+    /// copied so that wrapped records come back with their lines joined.
+    pub fn iter_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = OwnedRecord> + 'a {
+        self.index
+            .with_prefix(prefix)
+            .filter_map(move |entry| self.owned_record_at(entry))
+    }
 }
 
 pub struct RangeIterator<'a> {
     reader: &'a IndexedReader,
-    ids: Vec<String>,
     current: usize,
+    end: usize,
 }
 
 impl<'a> Iterator for RangeIterator<'a> {
     type Item = OwnedRecord;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current >= self.ids.len() {
+        if self.current >= self.end {
             return None;
         }
 
-        let id = &self.ids[self.current];
+        let position = self.current;
         self.current += 1;
 
-        self.reader.get_owned_record(id)
+        let entry = self.reader.index.get_by_position(position)?;
+        self.reader.owned_record_at(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.current;
+        (remaining, Some(remaining))
     }
 }
 
+impl ExactSizeIterator for RangeIterator<'_> {}
+
 pub struct RandomAccessReader {
     file: BufReader<File>,
     index: FastqIndex,
 }
 
 impl RandomAccessReader {
+    /// Fails with `StaleIndex` if `index` was built from a different
+    /// version of `fastq_path`.
     pub fn new<P: AsRef<Path>>(fastq_path: P, index: FastqIndex) -> Result<Self> {
+        index.verify(&fastq_path)?;
         let file = File::open(fastq_path)?;
         let reader = BufReader::new(file);
 
@@ -245,8 +1171,22 @@ impl RandomAccessReader {
         Self::new(fastq_path, index)
     }
 
+    /// Opens `fastq_path` with an existing `samtools fqidx` index.
+    pub fn from_fai<P: AsRef<Path>>(fastq_path: P, fai_path: P) -> Result<Self> {
+        let index = FastqIndex::read_fai(&fastq_path, fai_path)?;
+        Self::new(fastq_path, index)
+    }
+
     pub fn get_record(&mut self, id: &str) -> Result<Option<OwnedRecord>> {
-        let entry = match self.index.get(id) {
+        match self.index.position(id) {
+            Some(position) => self.get_by_position(position),
+            None => Ok(None),
+        }
+    }
+
+    /// The `n`th record in file order.
+    pub fn get_by_position(&mut self, n: usize) -> Result<Option<OwnedRecord>> {
+        let entry = match self.index.get_by_position(n) {
             Some(e) => e,
             None => return Ok(None),
         };
@@ -256,33 +1196,41 @@ impl RandomAccessReader {
         let mut buffer = vec![0u8; entry.length];
         self.file.read_exact(&mut buffer)?;
 
-        let header_end = memchr::memchr(b'\n', &buffer).ok_or(FastqError::UnexpectedEof)?;
-        let header = &buffer[1..header_end];
+        let record = entry
+            .record(&buffer)
+            .ok_or_else(|| FastqError::InvalidIndex {
+                msg: format!("entry for {} does not match the FASTQ file", entry.id),
+            })?;
+        Ok(Some(OwnedRecord::from_wrapped(&record)))
+    }
 
-        let (id_bytes, desc) = if let Some(space_pos) = header.iter().position(|&b| b == b' ') {
-            (&header[..space_pos], Some(&header[space_pos + 1..]))
-        } else {
-            (header, None)
+    /// Seeks to and reads only the bytes of `region`. Returns `None` when
+    /// the read is not indexed.
+    pub fn fetch(
+        &mut self,
+        region: &Region,
+        reverse_complement: bool,
+    ) -> Result<Option<OwnedRecord>> {
+        let Some(entry) = self.index.get(&region.id) else {
+            return Ok(None);
         };
+        let (start, end) = region.bounds(entry.bases);
 
-        let seq_start = header_end + 1;
-        let seq_end = seq_start + entry.seq_length;
-        let seq = &buffer[seq_start..seq_end];
+        let mut fields = [Vec::new(), Vec::new()];
+        for (buffer, offset) in fields.iter_mut().zip([entry.seq_offset, entry.qual_offset]) {
+            let (from, to) = entry.residue_bytes(offset, start, end);
+            buffer.resize((to - from) as usize, 0);
+            self.file.seek(SeekFrom::Start(from))?;
+            self.file.read_exact(buffer)?;
+        }
 
-        let qual_start = buffer[seq_end..]
-            .iter()
-            .position(|&b| b == b'\n')
-            .ok_or(FastqError::UnexpectedEof)?
-            + seq_end
-            + 1;
-        let qual_end = qual_start + entry.seq_length;
-        let qual = &buffer[qual_start..qual_end];
-
-        Ok(Some(OwnedRecord {
-            id: id_bytes.to_vec(),
-            desc: desc.map(|d| d.to_vec()),
-            seq: seq.to_vec(),
-            qual: qual.to_vec(),
-        }))
+        let [seq, qual] = &fields;
+        Ok(Some(region_record(
+            region,
+            entry,
+            seq,
+            qual,
+            reverse_complement,
+        )))
     }
 }
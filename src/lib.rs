@@ -1,8 +1,11 @@
 pub mod barcode;
 pub mod buffer;
+pub mod compress;
 pub mod error;
 pub mod filter;
+pub mod hash_index;
 pub mod index;
+pub mod merge;
 pub mod metrics;
 pub mod paired;
 pub mod parallel;
@@ -10,22 +13,39 @@ pub mod parser;
 pub mod reader;
 pub mod record;
 pub mod simd;
+pub mod sort;
+pub mod split;
 pub mod stream;
 pub mod writer;
 
 pub use barcode::{
     BarcodeConfig, BarcodeCorrector, BarcodeExtractor, Demultiplexer, UmiDeduplicator,
 };
+#[cfg(feature = "zstd")]
+pub use compress::ZstdOptions;
+pub use compress::{GzipFormat, OutputCompression, ParallelGzEncoder, ParallelGzip};
 pub use error::{FastqError, Result};
-pub use filter::{AdapterTrimmer, AdvancedFilter, FilterStats, QualityFilter};
-pub use index::{FastqIndex, IndexedReader, RandomAccessReader};
+pub use filter::{
+    AdapterTrimmer, AdvancedFilter, FilterStats, PairPolicy, PairedAdapterTrimmer, PairedFilter,
+    QualityFilter, TrimMethod,
+};
+pub use hash_index::{HashIndex, HashIndexBuilder, HashIndexedReader};
+pub use index::{FastqIndex, IndexedReader, KeyExtractor, RandomAccessReader, Region};
+pub use merge::{InsertSizeEstimator, InsertSizeReport, MergeStats, ReadMerger};
 pub use metrics::{ErrorDetector, QualityMetrics, QualityPlotter};
-pub use paired::{InterleavedReader, PairedEndReader};
+pub use paired::{
+    InterleavedReader, PairMatching, PairRepairer, PairedEndReader, SynchronizedReader,
+};
 pub use parser::{Parser, ParserBuilder};
 pub use reader::{FastqReader, FastqReaderBuilder};
-pub use record::{OwnedRecord, QualityEncoding, Record};
-pub use stream::{AsyncStreamingReader, ChunkedStreamer, StreamingReader};
-pub use writer::{FastaWriter, FastqWriter, FormatConverter, SubsetExtractor};
+pub use record::{OwnedRecord, QualityEncoding, RawRecord, Record};
+pub use sort::{natural_cmp, ExternalSorter, SortKey, SortStats};
+pub use split::{PairedSplitWriter, PartitionSummary, PartitionedWriter, SplitBy, SplitWriter};
+pub use stream::{AsyncStreamingReader, ChunkedStreamer, RawRecords, StreamingReader};
+pub use writer::{
+    AtomicFile, FastaWriter, FastqFormat, FastqWriter, FormatConverter, InterleavedWriter,
+    SubsetExtractor,
+};
 
 #[cfg(test)]
 mod tests {
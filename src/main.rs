@@ -61,7 +61,7 @@ fn process_simple(input_path: &str) -> Result<()> {
     println!("Processing FASTQ file: {}", input_path);
 
     let reader = FastqReader::from_path(input_path)?;
-    let mut count = 0;
+    let mut count = 0usize;
     let mut total_length = 0;
 
     for result in reader.into_records() {
@@ -84,7 +84,7 @@ fn process_simple(input_path: &str) -> Result<()> {
             println!("  Mean quality: {:.2}", rec_mut.mean_quality());
         }
 
-        if count % 100_000 == 0 {
+        if count.is_multiple_of(100_000) {
             println!("Processed {} records...", count);
         }
     }
@@ -150,7 +150,7 @@ fn process_with_filter(
             }
         }
 
-        if stats.total_reads % 100_000 == 0 {
+        if stats.total_reads.is_multiple_of(100_000) {
             eprintln!("Processed {} records...", stats.total_reads);
         }
     }
@@ -213,7 +213,7 @@ fn print_statistics(input_path: &str) -> Result<()> {
 
     let reader = FastqReader::from_path(input_path)?;
 
-    let mut total_records = 0;
+    let mut total_records = 0usize;
     let mut total_bases = 0;
     let mut min_length = usize::MAX;
     let mut max_length = 0;
@@ -245,7 +245,7 @@ fn print_statistics(input_path: &str) -> Result<()> {
         let mut rec_mut = record.as_record();
         total_quality += rec_mut.mean_quality();
 
-        if total_records % 100_000 == 0 {
+        if total_records.is_multiple_of(100_000) {
             println!("Analyzed {} records...", total_records);
         }
     }
@@ -0,0 +1,553 @@
+use crate::{
+    error::Result,
+    filter::AdapterTrimmer,
+    paired::{PairMatching, PairedEndReader},
+    record::{OwnedRecord, Record},
+    writer::FastqWriter,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+/// Score subtracted for each mismatching base in [`Overlap::score`].
+const MISMATCH_PENALTY: isize = 4;
+
+/// Best placement of the reverse-complemented R2 against R1. `shift` is the
+/// position in R1 where the first base of reverse-complemented R2 lands; it
+/// is negative for staggered pairs whose insert is shorter than the reads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overlap {
+    pub shift: isize,
+    pub length: usize,
+    pub mismatches: usize,
+}
+
+impl Overlap {
+    #[inline]
+    pub fn mismatch_rate(&self) -> f64 {
+        self.mismatches as f64 / self.length as f64
+    }
+
+    /// Alignment score used to rank candidate overlaps: matching bases
+    /// minus a fixed penalty per mismatch, as FLASH and fastp do. Unlike
+    /// the mismatch rate it rewards length, so a long true overlap with a
+    /// sequencing error beats a short perfect match in low-complexity
+    /// sequence.
+    #[inline]
+    pub fn score(&self) -> isize {
+        (self.length - self.mismatches) as isize - MISMATCH_PENALTY * self.mismatches as isize
+    }
+
+    /// Length of the fragment implied by the overlap.
+    #[inline]
+    pub fn insert_size(&self, r1_len: usize, r2_len: usize) -> usize {
+        if self.shift < 0 {
+            self.length
+        } else {
+            (self.shift as usize + r2_len).max(r1_len)
+        }
+    }
+}
+
+/// Finds the best overlap between `r1` and the reverse complement of R2,
+/// shared by the merger, the paired adapter trimmer and the insert size
+/// estimator. N bases never count as mismatches. Candidates within the
+/// mismatch budget are ranked by [`Overlap::score`].
+pub fn find_overlap(
+    r1: &[u8],
+    r2_rc: &[u8],
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+    allow_staggered: bool,
+) -> Option<Overlap> {
+    find_overlaps(r1, r2_rc, min_overlap, max_mismatch_rate, allow_staggered).0
+}
+
+/// Like [`find_overlap`], but also returns how many placements fit within
+/// the mismatch budget. More than one means the best overlap is ambiguous,
+/// typically because the reads are low-complexity or repetitive.
+pub(crate) fn find_overlaps(
+    r1: &[u8],
+    r2_rc: &[u8],
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+    allow_staggered: bool,
+) -> (Option<Overlap>, usize) {
+    let (len1, len2) = (r1.len() as isize, r2_rc.len() as isize);
+    let min_overlap = min_overlap.max(1) as isize;
+    let lowest = if allow_staggered {
+        -(len2 - min_overlap)
+    } else {
+        0
+    };
+
+    let mut best: Option<Overlap> = None;
+    let mut candidates = 0;
+
+    for shift in lowest..=(len1 - min_overlap) {
+        let r1_start = shift.max(0) as usize;
+        let r2_start = (-shift).max(0) as usize;
+        let length = (r1.len() - r1_start).min(r2_rc.len() - r2_start);
+        if (length as isize) < min_overlap {
+            continue;
+        }
+
+        let max_mismatches = (length as f64 * max_mismatch_rate) as usize;
+        let mut mismatches = 0;
+        for (&a, &b) in r1[r1_start..r1_start + length]
+            .iter()
+            .zip(&r2_rc[r2_start..r2_start + length])
+        {
+            if a != b && !is_n(a) && !is_n(b) {
+                mismatches += 1;
+                if mismatches > max_mismatches {
+                    break;
+                }
+            }
+        }
+        if mismatches > max_mismatches {
+            continue;
+        }
+
+        candidates += 1;
+        let candidate = Overlap {
+            shift,
+            length,
+            mismatches,
+        };
+        let better = match best {
+            None => true,
+            Some(current) => {
+                let (score, current_score) = (candidate.score(), current.score());
+                score > current_score || (score == current_score && mismatches < current.mismatches)
+            }
+        };
+        if better {
+            best = Some(candidate);
+        }
+    }
+
+    (best, candidates)
+}
+
+#[inline]
+fn is_n(base: u8) -> bool {
+    base == b'N' || base == b'n'
+}
+
+#[inline]
+fn error_probability(q: u8) -> f64 {
+    10f64.powf(-(q as f64) / 10.0)
+}
+
+#[inline]
+fn phred(p: f64, max_quality: u8) -> u8 {
+    let q = -10.0 * p.max(1e-10).log10();
+    q.round().clamp(0.0, max_quality as f64) as u8
+}
+
+/// Posterior consensus of two aligned base calls, following the formulas
+/// used by PEAR and vsearch. Qualities are Phred scores, not ASCII.
+pub fn consensus_base(b1: u8, q1: u8, b2: u8, q2: u8, max_quality: u8) -> (u8, u8) {
+    match (is_n(b1), is_n(b2)) {
+        (true, true) => return (b'N', q1.min(q2)),
+        (true, false) => return (b2, q2),
+        (false, true) => return (b1, q1),
+        (false, false) => {}
+    }
+
+    let (p1, p2) = (error_probability(q1), error_probability(q2));
+
+    if b1.eq_ignore_ascii_case(&b2) {
+        let p = (p1 * p2 / 3.0) / (1.0 - p1 - p2 + 4.0 * p1 * p2 / 3.0);
+        (b1, phred(p, max_quality))
+    } else {
+        let (base, p_hi, p_lo) = if q1 >= q2 { (b1, p1, p2) } else { (b2, p2, p1) };
+        let p = p_hi * (1.0 - p_lo / 3.0) / (p_hi + p_lo - 4.0 * p_hi * p_lo / 3.0);
+        (base, phred(p, max_quality))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MergeStats {
+    pub pairs: usize,
+    pub merged: usize,
+    pub unmerged: usize,
+    pub length_histogram: BTreeMap<usize, usize>,
+}
+
+impl MergeStats {
+    pub fn merge_rate(&self) -> f64 {
+        if self.pairs == 0 {
+            0.0
+        } else {
+            self.merged as f64 / self.pairs as f64
+        }
+    }
+
+    pub fn print_summary(&self) {
+        println!("Merging Statistics:");
+        println!("  Total pairs: {}", self.pairs);
+        println!("  Merged: {}", self.merged);
+        println!("  Not merged: {}", self.unmerged);
+        println!("  Merge rate: {:.2}%", self.merge_rate() * 100.0);
+    }
+}
+
+/// FLASH/PEAR-style merger for overlapping paired-end reads.
+pub struct ReadMerger {
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+    allow_staggered: bool,
+    quality_offset: u8,
+    max_quality: u8,
+}
+
+impl Default for ReadMerger {
+    fn default() -> Self {
+        ReadMerger {
+            min_overlap: 10,
+            max_mismatch_rate: 0.1,
+            allow_staggered: false,
+            quality_offset: 33,
+            max_quality: 41,
+        }
+    }
+}
+
+impl ReadMerger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_overlap(mut self, overlap: usize) -> Self {
+        self.min_overlap = overlap;
+        self
+    }
+
+    pub fn max_mismatch_rate(mut self, rate: f64) -> Self {
+        self.max_mismatch_rate = rate;
+        self
+    }
+
+    /// Accept overlaps where R2 extends past the start of R1, trimming both
+    /// overhangs from the merged read.
+    pub fn allow_staggered(mut self, allow: bool) -> Self {
+        self.allow_staggered = allow;
+        self
+    }
+
+    pub fn quality_offset(mut self, offset: u8) -> Self {
+        self.quality_offset = offset;
+        self
+    }
+
+    pub fn max_quality(mut self, quality: u8) -> Self {
+        self.max_quality = quality;
+        self
+    }
+
+    pub fn merge(&self, r1: &Record, r2: &Record) -> Option<OwnedRecord> {
+        let r2_rc = r2.reverse_complement();
+        let overlap = find_overlap(
+            r1.seq,
+            &r2_rc.seq,
+            self.min_overlap,
+            self.max_mismatch_rate,
+            self.allow_staggered,
+        )?;
+
+        let offset = self.quality_offset;
+        let r1_start = overlap.shift.max(0) as usize;
+        let r2_start = (-overlap.shift).max(0) as usize;
+
+        let (prefix_seq, prefix_qual) = (&r1.seq[..r1_start], &r1.qual[..r1_start]);
+        let mut seq = prefix_seq.to_vec();
+        let mut qual = prefix_qual.to_vec();
+
+        for i in 0..overlap.length {
+            let (b1, q1) = (r1.seq[r1_start + i], r1.qual[r1_start + i]);
+            let (b2, q2) = (r2_rc.seq[r2_start + i], r2_rc.qual[r2_start + i]);
+            let (base, q) = consensus_base(
+                b1,
+                q1.saturating_sub(offset),
+                b2,
+                q2.saturating_sub(offset),
+                self.max_quality,
+            );
+            seq.push(base);
+            qual.push(q + offset);
+        }
+
+        if overlap.shift >= 0 {
+            let r1_end = r1_start + overlap.length;
+            let r2_end = r2_start + overlap.length;
+            if r1_end < r1.seq.len() {
+                seq.extend_from_slice(&r1.seq[r1_end..]);
+                qual.extend_from_slice(&r1.qual[r1_end..]);
+            } else {
+                seq.extend_from_slice(&r2_rc.seq[r2_end..]);
+                qual.extend_from_slice(&r2_rc.qual[r2_end..]);
+            }
+        }
+
+        Some(OwnedRecord {
+            id: PairMatching::StripSuffix.pair_key(r1).to_vec(),
+            desc: r1.desc.map(|d| d.to_vec()),
+            seq,
+            qual,
+        })
+    }
+
+    pub fn merge_pairs<I, W>(
+        &self,
+        pairs: I,
+        merged: &mut FastqWriter<W>,
+        unmerged_r1: &mut FastqWriter<W>,
+        unmerged_r2: &mut FastqWriter<W>,
+    ) -> Result<MergeStats>
+    where
+        I: Iterator<Item = Result<(OwnedRecord, OwnedRecord)>>,
+        W: Write,
+    {
+        let mut stats = MergeStats::default();
+
+        for pair in pairs {
+            let (r1, r2) = pair?;
+            stats.pairs += 1;
+
+            match self.merge(&r1.as_record(), &r2.as_record()) {
+                Some(record) => {
+                    *stats.length_histogram.entry(record.seq.len()).or_insert(0) += 1;
+                    merged.write_owned_record(&record)?;
+                    stats.merged += 1;
+                }
+                None => {
+                    unmerged_r1.write_owned_record(&r1)?;
+                    unmerged_r2.write_owned_record(&r2)?;
+                    stats.unmerged += 1;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    pub fn merge_files<P: AsRef<Path>>(
+        &self,
+        r1: P,
+        r2: P,
+        merged: P,
+        unmerged_r1: P,
+        unmerged_r2: P,
+    ) -> Result<MergeStats> {
+        let reader = PairedEndReader::from_paths(r1, r2)?;
+        let mut merged = FastqWriter::to_file(merged)?;
+        let mut unmerged_r1 = FastqWriter::to_file(unmerged_r1)?;
+        let mut unmerged_r2 = FastqWriter::to_file(unmerged_r2)?;
+
+        let stats = self.merge_pairs(
+            reader.into_paired_records(),
+            &mut merged,
+            &mut unmerged_r1,
+            &mut unmerged_r2,
+        )?;
+
+        merged.finish()?;
+        unmerged_r1.finish()?;
+        unmerged_r2.finish()?;
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InsertSizeReport {
+    pub pairs_sampled: usize,
+    pub overlapping_pairs: usize,
+    pub too_long_to_overlap: usize,
+    pub adapter_dimers: usize,
+    pub histogram: BTreeMap<usize, usize>,
+    pub mean: f64,
+    pub median: usize,
+    pub mode: usize,
+}
+
+impl InsertSizeReport {
+    pub fn too_long_fraction(&self) -> f64 {
+        if self.pairs_sampled == 0 {
+            0.0
+        } else {
+            self.too_long_to_overlap as f64 / self.pairs_sampled as f64
+        }
+    }
+
+    fn finalize(&mut self) {
+        let total: usize = self.histogram.values().sum();
+        if total == 0 {
+            return;
+        }
+
+        let sum: usize = self.histogram.iter().map(|(size, n)| size * n).sum();
+        self.mean = sum as f64 / total as f64;
+
+        let middle = (total - 1) / 2;
+        let mut seen = 0;
+        for (&size, &n) in &self.histogram {
+            seen += n;
+            if seen > middle {
+                self.median = size;
+                break;
+            }
+        }
+
+        self.mode = self
+            .histogram
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map(|(&size, _)| size)
+            .unwrap_or(0);
+    }
+
+    pub fn print_summary(&self) {
+        println!("Insert Size Statistics:");
+        println!("  Pairs sampled: {}", self.pairs_sampled);
+        println!("  Overlapping pairs: {}", self.overlapping_pairs);
+        println!(
+            "  Too long to overlap: {} ({:.2}%)",
+            self.too_long_to_overlap,
+            self.too_long_fraction() * 100.0
+        );
+        println!("  Adapter dimers: {}", self.adapter_dimers);
+        println!("  Mean: {:.2}", self.mean);
+        println!("  Median: {}", self.median);
+        println!("  Mode: {}", self.mode);
+    }
+}
+
+/// Estimates the library insert size distribution by overlapping mates,
+/// without alignment. Pairs that do not overlap are counted as too long.
+pub struct InsertSizeEstimator {
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+    sample_size: usize,
+    dimer_max_insert: usize,
+    dimer_min_overlap: usize,
+    adapter_trimmer: AdapterTrimmer,
+}
+
+impl Default for InsertSizeEstimator {
+    fn default() -> Self {
+        InsertSizeEstimator {
+            min_overlap: 20,
+            max_mismatch_rate: 0.1,
+            sample_size: 100_000,
+            dimer_max_insert: 10,
+            dimer_min_overlap: 5,
+            adapter_trimmer: AdapterTrimmer::default(),
+        }
+    }
+}
+
+impl InsertSizeEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_overlap(mut self, overlap: usize) -> Self {
+        self.min_overlap = overlap;
+        self
+    }
+
+    pub fn max_mismatch_rate(mut self, rate: f64) -> Self {
+        self.max_mismatch_rate = rate;
+        self
+    }
+
+    pub fn sample_size(mut self, pairs: usize) -> Self {
+        self.sample_size = pairs;
+        self
+    }
+
+    /// Pairs whose insert is at most this long are counted as adapter
+    /// dimers rather than added to the histogram.
+    pub fn dimer_max_insert(mut self, length: usize) -> Self {
+        self.dimer_max_insert = length;
+        self
+    }
+
+    /// Minimum overlap for detecting adapter dimers. Their inserts are
+    /// shorter than `min_overlap`, so they are searched for separately
+    /// among placements with an insert of at most `dimer_max_insert`.
+    pub fn dimer_min_overlap(mut self, overlap: usize) -> Self {
+        self.dimer_min_overlap = overlap;
+        self
+    }
+
+    pub fn adapter_trimmer(mut self, trimmer: AdapterTrimmer) -> Self {
+        self.adapter_trimmer = trimmer;
+        self
+    }
+
+    pub fn insert_size(&self, r1: &Record, r2: &Record) -> Option<usize> {
+        let r2_rc = crate::simd::reverse_complement(r2.seq);
+        find_overlap(
+            r1.seq,
+            &r2_rc,
+            self.min_overlap,
+            self.max_mismatch_rate,
+            true,
+        )
+        .map(|overlap| overlap.insert_size(r1.len(), r2.len()))
+        .or_else(|| self.dimer_insert(r1.seq, &r2_rc))
+    }
+
+    /// Overlaps only the first `dimer_max_insert` bases of R1 with the last
+    /// ones of reverse-complemented R2, where a dimer's insert would be.
+    fn dimer_insert(&self, r1: &[u8], r2_rc: &[u8]) -> Option<usize> {
+        let r1 = &r1[..self.dimer_max_insert.min(r1.len())];
+        let r2_rc = &r2_rc[r2_rc.len().saturating_sub(self.dimer_max_insert)..];
+        find_overlap(
+            r1,
+            r2_rc,
+            self.dimer_min_overlap,
+            self.max_mismatch_rate,
+            true,
+        )
+        .filter(|overlap| overlap.shift <= 0)
+        .map(|overlap| overlap.length)
+    }
+
+    pub fn estimate<I>(&self, pairs: I) -> Result<InsertSizeReport>
+    where
+        I: Iterator<Item = Result<(OwnedRecord, OwnedRecord)>>,
+    {
+        let mut report = InsertSizeReport::default();
+
+        for pair in pairs.take(self.sample_size) {
+            let (r1, r2) = pair?;
+            let (r1, r2) = (r1.as_record(), r2.as_record());
+            report.pairs_sampled += 1;
+
+            match self.insert_size(&r1, &r2) {
+                Some(insert) if insert <= self.dimer_max_insert => report.adapter_dimers += 1,
+                Some(insert) => {
+                    report.overlapping_pairs += 1;
+                    *report.histogram.entry(insert).or_insert(0) += 1;
+                }
+                None if self.adapter_trimmer.trim(&r1).len() <= self.dimer_max_insert => {
+                    report.adapter_dimers += 1
+                }
+                None => report.too_long_to_overlap += 1,
+            }
+        }
+
+        report.finalize();
+        Ok(report)
+    }
+
+    pub fn estimate_files<P: AsRef<Path>>(&self, r1: P, r2: P) -> Result<InsertSizeReport> {
+        let reader = PairedEndReader::from_paths(r1, r2)?;
+        self.estimate(reader.into_paired_records())
+    }
+}
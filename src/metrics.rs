@@ -56,19 +56,11 @@ impl QualityMetrics {
             self.position_qualities[pos].push(score);
         }
 
-        let gc_count = record
-            .seq()
-            .iter()
-            .filter(|&&b| b == b'G' || b == b'C' || b == b'g' || b == b'c')
-            .count();
-        let gc_percent = (gc_count as f64 / record.len() as f64) * 100.0;
+        let counts = crate::simd::count_bases(record.seq());
+        let gc_percent = (counts.gc() as f64 / record.len() as f64) * 100.0;
         self.gc_content.push(gc_percent);
 
-        self.n_bases += record
-            .seq()
-            .iter()
-            .filter(|&&b| b == b'N' || b == b'n')
-            .count();
+        self.n_bases += counts.n;
 
         self.duplicate_tracker.add(record.seq());
 
@@ -394,8 +386,8 @@ impl QualityPlotter {
             row[4] = '|';
         }
 
-        for x in 5..width {
-            plot[height - 1][x] = '-';
+        for cell in plot[height - 1].iter_mut().skip(5) {
+            *cell = '-';
         }
 
         let positions_per_column = stats.len().max(1) / (width - 6).max(1);
@@ -2,12 +2,95 @@ use crate::{
     error::{FastqError, Result},
     reader::FastqReader,
     record::{OwnedRecord, Record},
+    writer::FastqWriter,
 };
-use std::path::Path;
+use regex::bytes::Regex;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Rule used to decide whether two reads are mates.
+#[derive(Debug, Clone, Default)]
+pub enum PairMatching {
+    /// IDs must be byte-for-byte identical.
+    Exact,
+    /// A trailing `/1` or `/2` is removed before comparing.
+    #[default]
+    StripSuffix,
+    /// IDs must be identical and the read number is taken from a CASAVA 1.8
+    /// comment such as `1:N:0:ATCACG`.
+    Casava,
+    /// The first capture group (or the whole match) is the pair key. A named
+    /// group `read` supplies the read number.
+    Regex(Regex),
+}
+
+impl PairMatching {
+    pub fn regex(pattern: &str) -> std::result::Result<Self, regex::Error> {
+        Ok(PairMatching::Regex(Regex::new(pattern)?))
+    }
+
+    pub fn pair_key<'a>(&self, record: &Record<'a>) -> &'a [u8] {
+        let id = record.id;
+        match self {
+            PairMatching::Exact | PairMatching::Casava => id,
+            PairMatching::StripSuffix => match id {
+                [base @ .., b'/', b'1' | b'2'] => base,
+                _ => id,
+            },
+            PairMatching::Regex(re) => match re.captures(id) {
+                Some(caps) => caps
+                    .get(1)
+                    .or_else(|| caps.get(0))
+                    .map_or(id, |m| m.as_bytes()),
+                None => id,
+            },
+        }
+    }
+
+    pub fn read_number(&self, record: &Record) -> Option<u8> {
+        match self {
+            PairMatching::Exact => None,
+            PairMatching::StripSuffix => match record.id {
+                [.., b'/', n @ (b'1' | b'2')] => Some(n - b'0'),
+                _ => None,
+            },
+            PairMatching::Casava => match record.desc {
+                Some([n @ (b'1' | b'2'), b':', ..]) => Some(n - b'0'),
+                _ => None,
+            },
+            PairMatching::Regex(re) => {
+                let caps = re.captures(record.id)?;
+                match caps.name("read")?.as_bytes() {
+                    [n @ (b'1' | b'2')] => Some(n - b'0'),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    pub fn is_pair(&self, r1: &Record, r2: &Record) -> bool {
+        self.pair_key(r1) == self.pair_key(r2)
+    }
+
+    /// True when the reads carry read numbers and they are the wrong way
+    /// round, i.e. the R1 and R2 inputs were swapped.
+    pub fn is_swapped(&self, r1: &Record, r2: &Record) -> bool {
+        matches!(
+            (self.read_number(r1), self.read_number(r2)),
+            (Some(2), Some(1))
+        )
+    }
+}
 
 pub struct PairedEndReader {
     r1_reader: FastqReader,
     r2_reader: FastqReader,
+    matching: PairMatching,
 }
 
 impl PairedEndReader {
@@ -18,14 +101,42 @@ impl PairedEndReader {
         Ok(PairedEndReader {
             r1_reader,
             r2_reader,
+            matching: PairMatching::default(),
         })
     }
 
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
     pub fn into_paired_records(self) -> PairedRecordIterator {
         PairedRecordIterator {
             r1_iter: self.r1_reader.into_records(),
             r2_iter: self.r2_reader.into_records(),
             strict_pairing: true,
+            matching: self.matching,
+            checked_order: false,
+        }
+    }
+
+    /// Reads the first record of each file and reports whether the files
+    /// appear to be given in R2, R1 order.
+    pub fn detect_swapped<P: AsRef<Path>>(
+        r1_path: P,
+        r2_path: P,
+        matching: &PairMatching,
+    ) -> Result<bool> {
+        let first = |path: P| -> Result<Option<OwnedRecord>> {
+            FastqReader::from_path(path)?
+                .into_records()
+                .next()
+                .transpose()
+        };
+
+        match (first(r1_path)?, first(r2_path)?) {
+            (Some(r1), Some(r2)) => Ok(matching.is_swapped(&r1.as_record(), &r2.as_record())),
+            _ => Ok(false),
         }
     }
 
@@ -39,7 +150,7 @@ impl PairedEndReader {
         while count < SAMPLE_SIZE {
             match (r1_iter.next(), r2_iter.next()) {
                 (Some(Ok(r1)), Some(Ok(r2))) => {
-                    if !Self::ids_match(&r1.as_record(), &r2.as_record()) {
+                    if !self.matching.is_pair(&r1.as_record(), &r2.as_record()) {
                         return Ok(false);
                     }
                     count += 1;
@@ -52,28 +163,14 @@ impl PairedEndReader {
 
         Ok(true)
     }
-
-    fn ids_match(r1: &Record, r2: &Record) -> bool {
-        let id1 = Self::extract_base_id(r1.id());
-        let id2 = Self::extract_base_id(r2.id());
-        id1 == id2
-    }
-
-    fn extract_base_id(id: &[u8]) -> &[u8] {
-        if let Some(space_pos) = id.iter().position(|&b| b == b' ') {
-            &id[..space_pos]
-        } else if let Some(slash_pos) = id.iter().position(|&b| b == b'/') {
-            &id[..slash_pos]
-        } else {
-            id
-        }
-    }
 }
 
 pub struct PairedRecordIterator {
     r1_iter: Box<dyn Iterator<Item = Result<OwnedRecord>> + Send>,
     r2_iter: Box<dyn Iterator<Item = Result<OwnedRecord>> + Send>,
     strict_pairing: bool,
+    matching: PairMatching,
+    checked_order: bool,
 }
 
 impl PairedRecordIterator {
@@ -81,6 +178,11 @@ impl PairedRecordIterator {
         self.strict_pairing = strict;
         self
     }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
 }
 
 impl Iterator for PairedRecordIterator {
@@ -89,17 +191,21 @@ impl Iterator for PairedRecordIterator {
     fn next(&mut self) -> Option<Self::Item> {
         match (self.r1_iter.next(), self.r2_iter.next()) {
             (Some(Ok(r1)), Some(Ok(r2))) => {
-                if self.strict_pairing {
-                    let id1 = PairedEndReader::extract_base_id(&r1.id);
-                    let id2 = PairedEndReader::extract_base_id(&r2.id);
-
-                    if id1 != id2 {
-                        return Some(Err(FastqError::PairedEndMismatch {
-                            r1_id: String::from_utf8_lossy(&r1.id).into_owned(),
-                            r2_id: String::from_utf8_lossy(&r2.id).into_owned(),
-                        }));
+                let (rec1, rec2) = (r1.as_record(), r2.as_record());
+
+                if self.strict_pairing && !self.checked_order {
+                    self.checked_order = true;
+                    if self.matching.is_swapped(&rec1, &rec2) {
+                        return Some(Err(FastqError::SwappedPairedFiles));
                     }
                 }
+
+                if self.strict_pairing && !self.matching.is_pair(&rec1, &rec2) {
+                    return Some(Err(FastqError::PairedEndMismatch {
+                        r1_id: String::from_utf8_lossy(&r1.id).into_owned(),
+                        r2_id: String::from_utf8_lossy(&r2.id).into_owned(),
+                    }));
+                }
                 Some(Ok((r1, r2)))
             }
             (Some(Err(e)), _) | (_, Some(Err(e))) => Some(Err(e)),
@@ -111,34 +217,624 @@ impl Iterator for PairedRecordIterator {
 
 pub struct InterleavedReader {
     reader: FastqReader,
+    matching: PairMatching,
 }
 
 impl InterleavedReader {
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let reader = FastqReader::from_path(path)?;
-        Ok(InterleavedReader { reader })
+        Ok(InterleavedReader {
+            reader,
+            matching: PairMatching::default(),
+        })
+    }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
     }
 
     pub fn into_paired_records(self) -> InterleavedPairedIterator {
         InterleavedPairedIterator {
             iter: self.reader.into_records(),
+            strict_pairing: true,
+            matching: self.matching,
+            records_read: 0,
         }
     }
 }
 
 pub struct InterleavedPairedIterator {
     iter: Box<dyn Iterator<Item = Result<OwnedRecord>> + Send>,
+    strict_pairing: bool,
+    matching: PairMatching,
+    records_read: usize,
+}
+
+impl InterleavedPairedIterator {
+    pub fn strict_pairing(mut self, strict: bool) -> Self {
+        self.strict_pairing = strict;
+        self
+    }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
 }
 
 impl Iterator for InterleavedPairedIterator {
     type Item = Result<(OwnedRecord, OwnedRecord)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match (self.iter.next(), self.iter.next()) {
-            (Some(Ok(r1)), Some(Ok(r2))) => Some(Ok((r1, r2))),
-            (Some(Err(e)), _) | (_, Some(Err(e))) => Some(Err(e)),
-            (None, None) | (Some(_), None) => None,
-            (None, Some(_)) => Some(Err(FastqError::InterleavedOddCount)),
+        let first = match self.iter.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        self.records_read += 1;
+
+        let second = match self.iter.next() {
+            Some(Ok(record)) => record,
+            Some(Err(e)) => return Some(Err(e)),
+            None => {
+                return Some(Err(FastqError::InterleavedOddCount {
+                    record: self.records_read,
+                }))
+            }
+        };
+        self.records_read += 1;
+
+        if self.strict_pairing
+            && !self
+                .matching
+                .is_pair(&first.as_record(), &second.as_record())
+        {
+            return Some(Err(FastqError::PairedEndMismatch {
+                r1_id: String::from_utf8_lossy(&first.id).into_owned(),
+                r2_id: String::from_utf8_lossy(&second.id).into_owned(),
+            }));
+        }
+
+        Some(Ok((first, second)))
+    }
+}
+
+/// Reads any number of lane-matched files in lockstep, e.g. R1, R2, I1 and
+/// I2 from an Illumina run. Each step yields one record per file, in the
+/// order the paths were given.
+pub struct SynchronizedReader {
+    readers: Vec<FastqReader>,
+    matching: PairMatching,
+}
+
+impl SynchronizedReader {
+    pub fn from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self> {
+        let readers = paths
+            .iter()
+            .map(FastqReader::from_path)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SynchronizedReader {
+            readers,
+            matching: PairMatching::default(),
+        })
+    }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.readers.len()
+    }
+
+    pub fn into_records(self) -> SynchronizedRecordIterator {
+        SynchronizedRecordIterator {
+            iters: self
+                .readers
+                .into_iter()
+                .map(FastqReader::into_records)
+                .collect(),
+            strict_pairing: true,
+            matching: self.matching,
+            records_read: 0,
+            finished: false,
+        }
+    }
+}
+
+pub struct SynchronizedRecordIterator {
+    iters: Vec<Box<dyn Iterator<Item = Result<OwnedRecord>> + Send>>,
+    strict_pairing: bool,
+    matching: PairMatching,
+    records_read: usize,
+    finished: bool,
+}
+
+impl SynchronizedRecordIterator {
+    pub fn strict_pairing(mut self, strict: bool) -> Self {
+        self.strict_pairing = strict;
+        self
+    }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    fn check_ids(&self, records: &[OwnedRecord]) -> Result<()> {
+        let Some((first, rest)) = records.split_first() else {
+            return Ok(());
+        };
+        let first = first.as_record();
+
+        for (i, record) in rest.iter().enumerate() {
+            if !self.matching.is_pair(&first, &record.as_record()) {
+                return Err(FastqError::SynchronizedIdMismatch {
+                    file: i + 1,
+                    record: self.records_read,
+                    expected: String::from_utf8_lossy(first.id).into_owned(),
+                    found: String::from_utf8_lossy(&record.id).into_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for SynchronizedRecordIterator {
+    type Item = Result<Vec<OwnedRecord>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let mut records = Vec::with_capacity(self.iters.len());
+        let mut exhausted = None;
+
+        for (i, iter) in self.iters.iter_mut().enumerate() {
+            match iter.next() {
+                Some(Ok(record)) => records.push(record),
+                Some(Err(e)) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    exhausted.get_or_insert(i);
+                }
+            }
+        }
+
+        if let Some(file) = exhausted {
+            self.finished = true;
+            if records.is_empty() {
+                return None;
+            }
+            return Some(Err(FastqError::SynchronizedLengthMismatch {
+                file,
+                records: self.records_read,
+            }));
+        }
+
+        self.records_read += 1;
+        if self.strict_pairing {
+            if let Err(e) = self.check_ids(&records) {
+                return Some(Err(e));
+            }
+        }
+
+        Some(Ok(records))
+    }
+}
+
+const SPILL_BUCKETS: usize = 16;
+/// Partitioning rounds before an oversized bucket is loaded anyway, e.g.
+/// when it holds many copies of one key.
+const MAX_SPILL_LEVEL: u32 = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepairStats {
+    pub pairs: usize,
+    pub singletons: usize,
+    pub spilled: usize,
+}
+
+/// Re-pairs mates that appear out of order, in the spirit of bbmap's
+/// `repair.sh`. Unmatched reads are buffered in memory up to `max_buffered`
+/// records; beyond that they are hash-partitioned to disk and matched bucket
+/// by bucket once the input is exhausted. Buckets that would hold more than
+/// `max_buffered` reads in memory are partitioned again.
+pub struct PairRepairer {
+    matching: PairMatching,
+    max_buffered: usize,
+    spill_dir: Option<PathBuf>,
+}
+
+impl Default for PairRepairer {
+    fn default() -> Self {
+        PairRepairer {
+            matching: PairMatching::default(),
+            max_buffered: 1_000_000,
+            spill_dir: None,
+        }
+    }
+}
+
+impl PairRepairer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn max_buffered(mut self, records: usize) -> Self {
+        self.max_buffered = records.max(1);
+        self
+    }
+
+    pub fn spill_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.spill_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn repair<I1, I2, W>(
+        &self,
+        r1: I1,
+        r2: I2,
+        out_r1: &mut FastqWriter<W>,
+        out_r2: &mut FastqWriter<W>,
+        singletons: &mut FastqWriter<W>,
+    ) -> Result<RepairStats>
+    where
+        I1: Iterator<Item = Result<OwnedRecord>>,
+        I2: Iterator<Item = Result<OwnedRecord>>,
+        W: Write,
+    {
+        let mut state = RepairState::new(self, out_r1, out_r2, singletons);
+        let mut r1 = r1.fuse();
+        let mut r2 = r2.fuse();
+
+        loop {
+            let mut progressed = false;
+            if let Some(record) = r1.next() {
+                state.accept(record?, 0)?;
+                progressed = true;
+            }
+            if let Some(record) = r2.next() {
+                state.accept(record?, 1)?;
+                progressed = true;
+            }
+            if !progressed {
+                break;
+            }
+        }
+
+        state.finish()
+    }
+
+    /// Repairs a single stream holding both mates, such as a shuffled
+    /// interleaved file. The read number decides the mate when the matching
+    /// rule provides one; otherwise the first read seen for a key is R1.
+    pub fn repair_interleaved<I, W>(
+        &self,
+        records: I,
+        out_r1: &mut FastqWriter<W>,
+        out_r2: &mut FastqWriter<W>,
+        singletons: &mut FastqWriter<W>,
+    ) -> Result<RepairStats>
+    where
+        I: Iterator<Item = Result<OwnedRecord>>,
+        W: Write,
+    {
+        let mut state = RepairState::new(self, out_r1, out_r2, singletons);
+
+        for record in records {
+            let record = record?;
+            let side = match self.matching.read_number(&record.as_record()) {
+                Some(n) => (n - 1) as usize,
+                None => {
+                    let key = self.matching.pair_key(&record.as_record());
+                    usize::from(state.pending[0].contains_key(key))
+                }
+            };
+            state.accept(record, side)?;
+        }
+
+        state.finish()
+    }
+
+    pub fn repair_files<P: AsRef<Path>>(
+        &self,
+        r1_in: P,
+        r2_in: P,
+        r1_out: P,
+        r2_out: P,
+        singletons_out: P,
+    ) -> Result<RepairStats> {
+        let r1 = FastqReader::from_path(r1_in)?;
+        let r2 = FastqReader::from_path(r2_in)?;
+        let mut out_r1 = FastqWriter::to_file(r1_out)?;
+        let mut out_r2 = FastqWriter::to_file(r2_out)?;
+        let mut singletons = FastqWriter::to_file(singletons_out)?;
+
+        let stats = self.repair(
+            r1.into_records(),
+            r2.into_records(),
+            &mut out_r1,
+            &mut out_r2,
+            &mut singletons,
+        )?;
+
+        out_r1.finish()?;
+        out_r2.finish()?;
+        singletons.finish()?;
+        Ok(stats)
+    }
+}
+
+struct RepairState<'a, W: Write> {
+    repairer: &'a PairRepairer,
+    pending: [HashMap<Vec<u8>, OwnedRecord>; 2],
+    spill: Option<SpillBuckets>,
+    outputs: [&'a mut FastqWriter<W>; 2],
+    singletons: &'a mut FastqWriter<W>,
+    stats: RepairStats,
+}
+
+impl<'a, W: Write> RepairState<'a, W> {
+    fn new(
+        repairer: &'a PairRepairer,
+        out_r1: &'a mut FastqWriter<W>,
+        out_r2: &'a mut FastqWriter<W>,
+        singletons: &'a mut FastqWriter<W>,
+    ) -> Self {
+        RepairState {
+            repairer,
+            pending: [HashMap::new(), HashMap::new()],
+            spill: None,
+            outputs: [out_r1, out_r2],
+            singletons,
+            stats: RepairStats::default(),
+        }
+    }
+
+    fn accept(&mut self, record: OwnedRecord, side: usize) -> Result<()> {
+        let key = self
+            .repairer
+            .matching
+            .pair_key(&record.as_record())
+            .to_vec();
+
+        match self.pending[1 - side].remove(&key) {
+            Some(mate) => {
+                if side == 0 {
+                    self.write_pair(&record, &mate)?;
+                } else {
+                    self.write_pair(&mate, &record)?;
+                }
+            }
+            None => {
+                if let Some(duplicate) = self.pending[side].insert(key, record) {
+                    self.write_singleton(&duplicate)?;
+                }
+                if self.pending[0].len() + self.pending[1].len() > self.repairer.max_buffered {
+                    self.spill_pending()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_pair(&mut self, r1: &OwnedRecord, r2: &OwnedRecord) -> Result<()> {
+        self.outputs[0].write_owned_record(r1)?;
+        self.outputs[1].write_owned_record(r2)?;
+        self.stats.pairs += 1;
+        Ok(())
+    }
+
+    fn write_singleton(&mut self, record: &OwnedRecord) -> Result<()> {
+        self.singletons.write_owned_record(record)?;
+        self.stats.singletons += 1;
+        Ok(())
+    }
+
+    fn spill_pending(&mut self) -> Result<()> {
+        if self.spill.is_none() {
+            self.spill = Some(SpillBuckets::create(self.repairer.spill_dir.as_deref(), 0)?);
+        }
+        let spill = self.spill.as_mut().unwrap();
+
+        for (side, pending) in self.pending.iter_mut().enumerate() {
+            for (key, record) in pending.drain() {
+                spill.write(side, &key, &record)?;
+                self.stats.spilled += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<RepairStats> {
+        if self.spill.is_none() {
+            self.flush_leftovers()?;
+            return Ok(self.stats);
         }
+
+        self.spill_pending()?;
+        let spill = self.spill.take().unwrap();
+        self.join_spilled(spill.close()?)?;
+
+        Ok(self.stats)
+    }
+
+    /// Pairs up spilled reads bucket by bucket. The R1 side of a bucket is
+    /// held in memory, so a bucket with more than `max_buffered` R1 reads
+    /// is first split again with a different hash.
+    fn join_spilled(&mut self, runs: SpilledRuns) -> Result<()> {
+        for bucket in 0..SPILL_BUCKETS {
+            let r1_path = &runs.paths[bucket];
+            let r2_path = &runs.paths[SPILL_BUCKETS + bucket];
+
+            if runs.counts[bucket] > self.repairer.max_buffered && runs.level < MAX_SPILL_LEVEL {
+                let mut split =
+                    SpillBuckets::create(self.repairer.spill_dir.as_deref(), runs.level + 1)?;
+                for (side, path) in [r1_path, r2_path].into_iter().enumerate() {
+                    for record in SpillBuckets::read(path)? {
+                        let record = record?;
+                        let key = self.repairer.matching.pair_key(&record.as_record());
+                        split.write(side, key, &record)?;
+                    }
+                }
+                self.join_spilled(split.close()?)?;
+                continue;
+            }
+
+            for record in SpillBuckets::read(r1_path)? {
+                let record = record?;
+                let key = self
+                    .repairer
+                    .matching
+                    .pair_key(&record.as_record())
+                    .to_vec();
+                if let Some(duplicate) = self.pending[0].insert(key, record) {
+                    self.write_singleton(&duplicate)?;
+                }
+            }
+
+            for record in SpillBuckets::read(r2_path)? {
+                let record = record?;
+                let key = self.repairer.matching.pair_key(&record.as_record());
+                match self.pending[0].remove(key) {
+                    Some(mate) => self.write_pair(&mate, &record)?,
+                    None => self.write_singleton(&record)?,
+                }
+            }
+
+            self.flush_leftovers()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush_leftovers(&mut self) -> Result<()> {
+        let [r1_pending, r2_pending] = &mut self.pending;
+        let mut leftovers: Vec<_> = r1_pending
+            .drain()
+            .chain(r2_pending.drain())
+            .map(|(_, record)| record)
+            .collect();
+        leftovers.sort_by(|a, b| a.id.cmp(&b.id));
+
+        for record in &leftovers {
+            self.write_singleton(record)?;
+        }
+        Ok(())
+    }
+}
+
+struct SpillBuckets {
+    dir: SpillDir,
+    level: u32,
+    paths: Vec<PathBuf>,
+    writers: Vec<FastqWriter<File>>,
+    counts: Vec<usize>,
+}
+
+impl SpillBuckets {
+    /// Buckets for one round of partitioning. `level` seeds the hash, so a
+    /// bucket split again spreads over new buckets instead of one.
+    fn create(parent: Option<&Path>, level: u32) -> Result<Self> {
+        let dir = SpillDir::create(parent)?;
+        let mut paths = Vec::with_capacity(2 * SPILL_BUCKETS);
+        let mut writers = Vec::with_capacity(2 * SPILL_BUCKETS);
+
+        for side in 0..2 {
+            for bucket in 0..SPILL_BUCKETS {
+                let path = dir.0.join(format!("r{}_{:02}.fastq", side + 1, bucket));
+                writers.push(FastqWriter::to_file(&path)?);
+                paths.push(path);
+            }
+        }
+
+        Ok(SpillBuckets {
+            dir,
+            level,
+            paths,
+            writers,
+            counts: vec![0; 2 * SPILL_BUCKETS],
+        })
+    }
+
+    fn write(&mut self, side: usize, key: &[u8], record: &OwnedRecord) -> Result<()> {
+        let mut hasher = DefaultHasher::new();
+        self.level.hash(&mut hasher);
+        key.hash(&mut hasher);
+        let bucket = side * SPILL_BUCKETS + (hasher.finish() % SPILL_BUCKETS as u64) as usize;
+        self.counts[bucket] += 1;
+        self.writers[bucket].write_owned_record(record)
+    }
+
+    fn close(self) -> Result<SpilledRuns> {
+        for writer in self.writers {
+            writer.finish()?;
+        }
+        Ok(SpilledRuns {
+            _dir: self.dir,
+            level: self.level,
+            paths: self.paths,
+            counts: self.counts,
+        })
+    }
+
+    fn read(path: &Path) -> Result<Box<dyn Iterator<Item = Result<OwnedRecord>> + Send>> {
+        if fs::metadata(path)?.len() == 0 {
+            return Ok(Box::new(std::iter::empty()));
+        }
+        Ok(FastqReader::from_path(path)?.into_records())
+    }
+}
+
+struct SpilledRuns {
+    _dir: SpillDir,
+    level: u32,
+    paths: Vec<PathBuf>,
+    counts: Vec<usize>,
+}
+
+/// Scratch directory for spill files, removed with its contents on drop.
+pub(crate) struct SpillDir(pub(crate) PathBuf);
+
+static SPILL_DIR_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl SpillDir {
+    /// Creates a fresh directory under `parent` (default: the system temp
+    /// dir). The name is never reused, so concurrent sorts get their own.
+    pub(crate) fn create(parent: Option<&Path>) -> Result<Self> {
+        let parent = parent.map_or_else(std::env::temp_dir, Path::to_path_buf);
+        fs::create_dir_all(&parent)?;
+        loop {
+            let dir = parent.join(format!(
+                "fastq-spill-{}-{}",
+                std::process::id(),
+                SPILL_DIR_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(SpillDir(dir)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
     }
 }
@@ -1,8 +1,11 @@
 use crate::{
-    error::Result,
-    filter::QualityFilter,
+    compress::OutputCompression,
+    error::{FastqError, Result},
+    filter::{PairDecision, PairedFilter, PairedFilterStats, QualityFilter},
+    paired::PairMatching,
     parser::Parser,
-    record::{OwnedRecord, Record},
+    record::{OwnedRecord, RawRecord, Record},
+    writer::{FastqFormat, FastqWriter},
 };
 use crossbeam_channel::{bounded, Sender};
 use rayon::prelude::*;
@@ -41,14 +44,10 @@ impl ParallelParser {
             .par_iter()
             .map(|&(start, end)| {
                 let slice = &self.data[start..end];
-                let parser = Parser::new(slice);
-                let mut records = Vec::new();
-
-                for record in parser {
-                    records.push(OwnedRecord::from_record(&record));
-                }
-
-                Ok(records)
+                Ok(chunk_records(slice)
+                    .iter()
+                    .map(OwnedRecord::from_record)
+                    .collect::<Vec<_>>())
             })
             .try_fold(Vec::new, |mut acc, chunk_result| {
                 chunk_result.map(|chunk| {
@@ -70,9 +69,8 @@ impl ParallelParser {
 
         chunks.par_iter().try_for_each(|&(start, end)| {
             let slice = &self.data[start..end];
-            let parser = Parser::new(slice);
 
-            for record in parser {
+            for record in chunk_records(slice) {
                 callback(OwnedRecord::from_record(&record));
             }
 
@@ -88,9 +86,8 @@ impl ParallelParser {
         thread::spawn(move || {
             chunks.par_iter().for_each(|&(start, end)| {
                 let slice = &data[start..end];
-                let parser = Parser::new(slice);
 
-                for record in parser {
+                for record in chunk_records(slice) {
                     let owned = OwnedRecord::from_record(&record);
                     if sender.send(Ok(owned)).is_err() {
                         break;
@@ -103,15 +100,21 @@ impl ParallelParser {
     }
 
     fn find_record_boundaries(&self) -> Vec<(usize, usize)> {
-        let mut boundaries = Vec::new();
         let data = &*self.data;
-        let len = data.len();
+        if data.is_empty() {
+            return Vec::new();
+        }
 
-        if len == 0 {
-            return boundaries;
+        match exact_record_chunks(data, self.num_threads) {
+            Some(boundaries) => boundaries,
+            None => self.scan_record_boundaries((data.len() / self.num_threads).max(CHUNK_SIZE)),
         }
+    }
 
-        let chunk_size = (len / self.num_threads).max(CHUNK_SIZE);
+    fn scan_record_boundaries(&self, chunk_size: usize) -> Vec<(usize, usize)> {
+        let mut boundaries = Vec::new();
+        let data = &*self.data;
+        let len = data.len();
         let mut start = 0;
 
         while start < len {
@@ -145,6 +148,88 @@ impl ParallelParser {
     }
 }
 
+/// Splits `data` into record-aligned `(start, end)` chunks of at least
+/// 1 MiB, about one per thread. Each cut is moved forward to the next
+/// record start found by a local resync scan, so nothing before the cut
+/// is read. Returns `None` when a cut lands in input that is not strict
+/// four-line FASTQ, since the boundaries are only guaranteed to fall
+/// between records there; callers parse or validate each chunk on its own.
+pub(crate) fn exact_record_chunks(data: &[u8], num_threads: usize) -> Option<Vec<(usize, usize)>> {
+    if data.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let chunk_size = (data.len() / num_threads.max(1)).max(CHUNK_SIZE);
+    let mut boundaries = Vec::new();
+    let mut start = 0;
+
+    while start + chunk_size < data.len() {
+        let cut = next_record_start(data, start + chunk_size)?;
+        if cut >= data.len() {
+            break;
+        }
+        boundaries.push((start, cut));
+        start = cut;
+    }
+    boundaries.push((start, data.len()));
+
+    Some(boundaries)
+}
+
+/// First record start at or after `from`: a line beginning with `@`
+/// whose third line begins with `+`, whose sequence and quality lines
+/// have the same length, and which is followed by another `@` line or the
+/// end of the data. One of any four consecutive lines of strict FASTQ is
+/// such a header, and quality lines starting with `@` never qualify
+/// because the line two below them is a sequence line.
+fn next_record_start(data: &[u8], from: usize) -> Option<usize> {
+    let next_line =
+        |pos: usize| memchr::memchr(b'\n', &data[pos..]).map_or(data.len(), |nl| pos + nl + 1);
+    let mut line = if from == 0 { 0 } else { next_line(from - 1) };
+
+    for _ in 0..4 {
+        if line >= data.len() {
+            return Some(data.len());
+        }
+        if is_record_start(data, line, next_line) {
+            return Some(line);
+        }
+        line = next_line(line);
+    }
+
+    None
+}
+
+fn is_record_start(data: &[u8], pos: usize, next_line: impl Fn(usize) -> usize) -> bool {
+    let mut starts = [pos; 5];
+    for i in 1..5 {
+        if starts[i - 1] >= data.len() {
+            return false;
+        }
+        starts[i] = next_line(starts[i - 1]);
+    }
+    let line_len = |i: usize| {
+        let line = &data[starts[i]..starts[i + 1]];
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        line.strip_suffix(b"\r").unwrap_or(line).len()
+    };
+
+    data[starts[0]] == b'@'
+        && data.get(starts[2]) == Some(&b'+')
+        && line_len(1) == line_len(3)
+        && (starts[4] >= data.len() || data[starts[4]] == b'@')
+}
+
+/// Records of one chunk. Strict four-line chunks are resolved from their
+/// structural index; anything else goes through the line parser.
+fn chunk_records(chunk: &[u8]) -> Vec<Record<'_>> {
+    let index = crate::simd::StructuralIndex::build(chunk);
+    match crate::parser::record_spans(chunk, &index) {
+        Ok(spans) => spans.iter().map(|span| span.record(chunk)).collect(),
+        Err(_) => Parser::new(chunk).collect(),
+    }
+}
+
 pub struct ChunkedProcessor {
     chunk_size: usize,
     buffer_size: usize,
@@ -421,6 +506,9 @@ impl ProcessingStats {
 pub struct ParallelFilterProcessor {
     filter: Arc<QualityFilter>,
     num_workers: usize,
+    compression: OutputCompression,
+    format: FastqFormat,
+    raw: bool,
 }
 
 impl ParallelFilterProcessor {
@@ -428,23 +516,47 @@ impl ParallelFilterProcessor {
         ParallelFilterProcessor {
             filter: Arc::new(filter),
             num_workers: rayon::current_num_threads(),
+            compression: OutputCompression::None,
+            format: FastqFormat::default(),
+            raw: false,
         }
     }
 
+    /// Copy kept records that were not trimmed to the output verbatim
+    /// instead of re-serializing them. This preserves their original
+    /// layout, so `format` only applies to trimmed records.
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Compression for the output. `OutputCompression::ParallelGzip` moves
+    /// compression off the writer thread onto its own pool.
+    pub fn compression(mut self, compression: OutputCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     pub fn process<R, W>(&self, input: R, output: W) -> Result<ProcessingStats>
     where
         R: std::io::Read + Send + 'static,
         W: std::io::Write + Send + 'static,
     {
-        let (input_sender, input_receiver) = bounded::<OwnedRecord>(QUEUE_SIZE);
-        let (output_sender, output_receiver) = bounded::<OwnedRecord>(QUEUE_SIZE);
+        let (input_sender, input_receiver) = bounded::<RawRecord>(QUEUE_SIZE);
+        let (output_sender, output_receiver) = bounded::<RawRecord>(QUEUE_SIZE);
 
         let filter = Arc::clone(&self.filter);
         let stats = Arc::new(Mutex::new(ProcessingStats::new()));
 
+        let keep_raw = self.raw;
         let reader_thread = thread::spawn(move || {
-            let reader = crate::stream::StreamingReader::new(input);
-            for result in reader {
+            let reader = crate::stream::StreamingReader::new(input).keep_raw(keep_raw);
+            for result in reader.raw_records() {
                 match result {
                     Ok(record) => {
                         if input_sender.send(record).is_err() {
@@ -465,18 +577,18 @@ impl ParallelFilterProcessor {
 
                 thread::spawn(move || {
                     while let Ok(record) = input_rx.recv() {
-                        let record_ref = record.as_record();
-                        let mut record_mut = Record::new(
-                            record_ref.id,
-                            record_ref.desc,
-                            record_ref.seq,
-                            record_ref.qual,
-                        );
+                        let mut record_mut = record.as_record();
 
                         if filter.filter(&mut record_mut) {
                             if let Some(trimmed) = filter.trim(&record_mut) {
-                                let owned = OwnedRecord::from_record(&trimmed);
-                                if output_tx.send(owned).is_err() {
+                                // Untrimmed records still carry their raw
+                                // bytes and can be passed on as they are.
+                                let owned = if trimmed.raw().is_some() {
+                                    None
+                                } else {
+                                    Some(RawRecord::from(OwnedRecord::from_record(&trimmed)))
+                                };
+                                if output_tx.send(owned.unwrap_or(record)).is_err() {
                                     break;
                                 }
                                 stats.lock().unwrap().processed += 1;
@@ -492,22 +604,198 @@ impl ParallelFilterProcessor {
         drop(input_receiver);
         drop(output_sender);
 
-        let writer_thread = thread::spawn(move || {
-            let mut output = output;
+        let compression = self.compression;
+        let format = self.format.clone();
+        let writer_thread = thread::spawn(move || -> Result<()> {
+            let mut writer = FastqWriter::with_compression(output, compression)?.format(format);
             while let Ok(record) = output_receiver.recv() {
-                let record_ref = record.as_record();
-                let formatted = format!("{}", record_ref);
-                if output.write_all(formatted.as_bytes()).is_err() {
-                    break;
-                }
+                writer.write_owned_raw(&record)?;
             }
+            writer.finish()?;
+            Ok(())
         });
 
         reader_thread.join().unwrap();
         for worker in filter_workers {
             worker.join().unwrap();
         }
-        writer_thread.join().unwrap();
+        writer_thread.join().unwrap()?;
+
+        let final_stats = stats.lock().unwrap().clone();
+        Ok(final_stats)
+    }
+}
+
+enum PairedOutput {
+    Pair(OwnedRecord, OwnedRecord),
+    Single(u8, OwnedRecord),
+}
+
+pub struct ParallelPairedFilterProcessor {
+    filter: Arc<PairedFilter>,
+    matching: PairMatching,
+    num_workers: usize,
+    compression: OutputCompression,
+    format: FastqFormat,
+}
+
+impl ParallelPairedFilterProcessor {
+    pub fn new(filter: PairedFilter) -> Self {
+        ParallelPairedFilterProcessor {
+            filter: Arc::new(filter),
+            matching: PairMatching::default(),
+            num_workers: rayon::current_num_threads(),
+            compression: OutputCompression::None,
+            format: FastqFormat::default(),
+        }
+    }
+
+    /// Output layout for every stream. A mate suffix becomes `/1` or `/2`
+    /// according to the mate, singletons included.
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Compression applied to every output stream.
+    pub fn compression(mut self, compression: OutputCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    pub fn num_workers(mut self, workers: usize) -> Self {
+        self.num_workers = workers.max(1);
+        self
+    }
+
+    /// Filters R1/R2 streams in parallel. Mates travel through the pipeline
+    /// together, so the paired outputs stay synchronized even though pair
+    /// order is not preserved.
+    pub fn process<R, W>(
+        &self,
+        r1_input: R,
+        r2_input: R,
+        r1_output: W,
+        r2_output: W,
+        singleton_output: Option<W>,
+    ) -> Result<PairedFilterStats>
+    where
+        R: std::io::Read + Send + 'static,
+        W: std::io::Write + Send + 'static,
+    {
+        let (input_sender, input_receiver) = bounded::<(OwnedRecord, OwnedRecord)>(QUEUE_SIZE);
+        let (output_sender, output_receiver) = bounded::<PairedOutput>(QUEUE_SIZE);
+
+        let stats = Arc::new(Mutex::new(PairedFilterStats::new()));
+        let matching = self.matching.clone();
+
+        let reader_thread = thread::spawn(move || -> Result<()> {
+            let r1_reader = crate::stream::StreamingReader::new(r1_input);
+            let r2_reader = crate::stream::StreamingReader::new(r2_input);
+            let mut r2_reader = r2_reader.fuse();
+
+            for r1 in r1_reader {
+                let r1 = r1?;
+                let r2 = r2_reader
+                    .next()
+                    .ok_or(FastqError::PairedEndLengthMismatch)??;
+                if !matching.is_pair(&r1.as_record(), &r2.as_record()) {
+                    return Err(FastqError::PairedEndMismatch {
+                        r1_id: String::from_utf8_lossy(&r1.id).into_owned(),
+                        r2_id: String::from_utf8_lossy(&r2.id).into_owned(),
+                    });
+                }
+                if input_sender.send((r1, r2)).is_err() {
+                    return Ok(());
+                }
+            }
+
+            match r2_reader.next() {
+                Some(_) => Err(FastqError::PairedEndLengthMismatch),
+                None => Ok(()),
+            }
+        });
+
+        let filter_workers: Vec<_> = (0..self.num_workers)
+            .map(|_| {
+                let input_rx = input_receiver.clone();
+                let output_tx = output_sender.clone();
+                let filter = Arc::clone(&self.filter);
+                let stats = Arc::clone(&stats);
+
+                thread::spawn(move || {
+                    while let Ok((r1, r2)) = input_rx.recv() {
+                        let decision = filter.apply(&r1.as_record(), &r2.as_record());
+                        stats.lock().unwrap().record(&decision);
+
+                        let output = match decision {
+                            PairDecision::Pair(r1, r2) => PairedOutput::Pair(
+                                OwnedRecord::from_record(&r1),
+                                OwnedRecord::from_record(&r2),
+                            ),
+                            PairDecision::SingleR1(record) => {
+                                PairedOutput::Single(1, OwnedRecord::from_record(&record))
+                            }
+                            PairDecision::SingleR2(record) => {
+                                PairedOutput::Single(2, OwnedRecord::from_record(&record))
+                            }
+                            PairDecision::Discard => continue,
+                        };
+
+                        if output_tx.send(output).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        drop(input_receiver);
+        drop(output_sender);
+
+        let compression = self.compression;
+        let format = self.format.clone();
+        let writer_thread = thread::spawn(move || -> Result<()> {
+            let writer = |output| {
+                FastqWriter::with_compression(output, compression).map(|w| w.format(format.clone()))
+            };
+            let mut r1_writer = writer(r1_output)?;
+            let mut r2_writer = writer(r2_output)?;
+            let mut singleton_writer = singleton_output.map(writer).transpose()?;
+
+            while let Ok(output) = output_receiver.recv() {
+                match output {
+                    PairedOutput::Pair(r1, r2) => {
+                        r1_writer.write_mate(&r1.as_record(), 1)?;
+                        r2_writer.write_mate(&r2.as_record(), 2)?;
+                    }
+                    PairedOutput::Single(mate, record) => {
+                        if let Some(writer) = singleton_writer.as_mut() {
+                            writer.write_mate(&record.as_record(), mate)?;
+                        }
+                    }
+                }
+            }
+
+            r1_writer.finish()?;
+            r2_writer.finish()?;
+            if let Some(writer) = singleton_writer {
+                writer.finish()?;
+            }
+            Ok(())
+        });
+
+        let read_result = reader_thread.join().unwrap();
+        for worker in filter_workers {
+            worker.join().unwrap();
+        }
+        writer_thread.join().unwrap()?;
+        read_result?;
 
         let final_stats = stats.lock().unwrap().clone();
         Ok(final_stats)
@@ -8,6 +8,7 @@ pub struct Parser<'a> {
     pub(crate) data: &'a [u8],
     pub(crate) pos: usize,
     line: usize,
+    wrapped: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -17,9 +18,20 @@ impl<'a> Parser<'a> {
             data,
             pos: 0,
             line: 1,
+            wrapped: false,
         }
     }
 
+    /// Accepts wrapped (multi-line) records. Their `seq` and `qual` slices
+    /// keep the embedded line breaks, so this is only for callers that need
+    /// the raw field spans, such as the index, or that copy the residues
+    /// out with [`OwnedRecord::from_wrapped`](crate::record::OwnedRecord::from_wrapped).
+    #[inline]
+    pub(crate) fn allow_wrapped(mut self) -> Self {
+        self.wrapped = true;
+        self
+    }
+
     #[inline]
     fn _peek(&self) -> Option<u8> {
         if self.pos < self.data.len() {
@@ -78,6 +90,7 @@ impl<'a> Parser<'a> {
             return Ok(None);
         }
 
+        let start = self.pos;
         let header_line = self.read_line()?;
         if header_line.is_empty() {
             return Ok(None);
@@ -100,16 +113,24 @@ impl<'a> Parser<'a> {
             });
         }
 
-        let qual = self.read_quality(seq.len())?;
-
-        if seq.len() != qual.len() {
-            return Err(FastqError::LengthMismatch {
-                seq_len: seq.len(),
-                qual_len: qual.len(),
-            });
-        }
+        let qual = if self.wrapped {
+            // Wrapped records keep their line breaks in the returned slices,
+            // so the quality is matched on residues rather than bytes.
+            self.read_quality(residue_count(seq))?
+        } else {
+            let qual = self.read_quality(seq.len())?;
+            if seq.len() != qual.len() {
+                return Err(FastqError::LengthMismatch {
+                    seq_len: seq.len(),
+                    qual_len: qual.len(),
+                });
+            }
+            qual
+        };
 
-        Ok(Some(Record::new(id, desc, seq, qual)))
+        Ok(Some(
+            Record::new(id, desc, seq, qual).with_raw(&self.data[start..self.pos]),
+        ))
     }
 
     fn read_sequence(&mut self) -> Result<&'a [u8]> {
@@ -193,7 +214,7 @@ impl<'a> Parser<'a> {
     }
 
     #[inline]
-    fn parse_header(header: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    pub(crate) fn parse_header(header: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
         // Use SIMD-accelerated character search
         if let Some(space_pos) = crate::simd::find_char(header, b' ', 0) {
             Ok((&header[..space_pos], Some(&header[space_pos + 1..])))
@@ -205,6 +226,29 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Number of non-whitespace bytes, i.e. bases or quality characters, in a
+/// field that may span several lines.
+#[inline]
+pub(crate) fn residue_count(field: &[u8]) -> usize {
+    if memchr::memchr(b'\n', field).is_none() {
+        return field.len();
+    }
+    field.iter().filter(|b| !b.is_ascii_whitespace()).count()
+}
+
+/// Copies the residues of a field that may span several lines, dropping
+/// the line breaks.
+pub(crate) fn residues(field: &[u8]) -> Vec<u8> {
+    if memchr::memchr(b'\n', field).is_none() {
+        return field.to_vec();
+    }
+    field
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect()
+}
+
 impl<'a> Iterator for Parser<'a> {
     type Item = Record<'a>;
 
@@ -217,6 +261,140 @@ impl<'a> Iterator for Parser<'a> {
     }
 }
 
+/// Byte offsets of the four lines of a record, resolved from a
+/// [`StructuralIndex`](crate::simd::StructuralIndex).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordSpan {
+    pub start: usize,
+    pub seq_start: usize,
+    pub sep_start: usize,
+    pub qual_start: usize,
+    pub end: usize,
+}
+
+impl RecordSpan {
+    #[inline]
+    pub fn record<'a>(&self, data: &'a [u8]) -> Record<'a> {
+        let header = trim_line(&data[self.start + 1..self.seq_start]);
+        let (id, desc) = match Parser::parse_header(header) {
+            Ok(parts) => parts,
+            Err(_) => (header, None),
+        };
+        let seq = trim_line(&data[self.seq_start..self.sep_start]);
+        let qual = trim_line(&data[self.qual_start..self.end]);
+        Record::new(id, desc, seq, qual).with_raw(&data[self.start..self.end])
+    }
+}
+
+#[inline]
+fn trim_line(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    if end > 0 && line[end - 1] == b'\n' {
+        end -= 1;
+    }
+    if end > 0 && line[end - 1] == b'\r' {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Resolves every record of a strict four-line FASTQ buffer from its
+/// structural index. Multi-line records are rejected, so callers that need
+/// to accept them should fall back to [`Parser`].
+pub fn record_spans(data: &[u8], index: &crate::simd::StructuralIndex) -> Result<Vec<RecordSpan>> {
+    let mut spans = Vec::with_capacity(index.newline_count() / 4 + 1);
+    let mut newlines = index.newlines();
+    let mut lines = [0usize; 4];
+    let mut field = 0;
+    let mut line = 1;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        if field == 0 && data[pos] != b'@' {
+            if data[pos..].iter().all(|b| b.is_ascii_whitespace()) {
+                break;
+            }
+            return Err(FastqError::InvalidHeader { line });
+        }
+        if field == 2 && data[pos] != b'+' {
+            return Err(FastqError::InvalidSeparator { line });
+        }
+
+        lines[field] = pos;
+        pos = newlines.next().map_or(data.len(), |nl| nl + 1);
+        field += 1;
+        line += 1;
+
+        if field == 4 {
+            let span = RecordSpan {
+                start: lines[0],
+                seq_start: lines[1],
+                sep_start: lines[2],
+                qual_start: lines[3],
+                end: pos,
+            };
+            let seq_len = trim_line(&data[span.seq_start..span.sep_start]).len();
+            let qual_len = trim_line(&data[span.qual_start..span.end]).len();
+            if seq_len != qual_len {
+                return Err(FastqError::LengthMismatch { seq_len, qual_len });
+            }
+            spans.push(span);
+            field = 0;
+        }
+    }
+
+    if field != 0 {
+        return Err(FastqError::UnexpectedEof);
+    }
+
+    Ok(spans)
+}
+
+/// Parser that indexes the whole buffer in one SIMD pass and then slices
+/// record fields directly out of the offset table.
+pub struct IndexedParser<'a> {
+    data: &'a [u8],
+    spans: Vec<RecordSpan>,
+    pos: usize,
+}
+
+impl<'a> IndexedParser<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let index = crate::simd::StructuralIndex::build(data);
+        let spans = record_spans(data, &index)?;
+        Ok(IndexedParser {
+            data,
+            spans,
+            pos: 0,
+        })
+    }
+
+    pub fn spans(&self) -> &[RecordSpan] {
+        &self.spans
+    }
+
+    pub fn get(&self, i: usize) -> Option<Record<'a>> {
+        self.spans.get(i).map(|span| span.record(self.data))
+    }
+}
+
+impl<'a> Iterator for IndexedParser<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.get(self.pos)?;
+        self.pos += 1;
+        Some(record)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.spans.len() - self.pos;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for IndexedParser<'a> {}
+
 pub struct ParserBuilder {
     validate: bool,
     parallel: bool,
@@ -283,9 +461,9 @@ impl<R: Read> StreamingParser<R> {
             return Ok(None);
         }
 
-        let mut parser = Parser::new(buffer);
+        let mut parser = Parser::new(buffer).allow_wrapped();
         if let Some(record) = parser.parse_record()? {
-            let owned = crate::record::OwnedRecord::from_record(&record);
+            let owned = crate::record::OwnedRecord::from_wrapped(&record);
             self.reader.consume(parser.pos);
             Ok(Some(owned))
         } else {
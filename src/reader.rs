@@ -1,6 +1,6 @@
 use crate::{
     error::Result,
-    parser::{Parser, StreamingParser},
+    parser::{IndexedParser, Parser, StreamingParser},
     record::{OwnedRecord, Record},
 };
 use flate2::read::MultiGzDecoder;
@@ -18,10 +18,37 @@ impl FastqReader {
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
 
-        if path.extension().and_then(|s| s.to_str()) == Some("gz") {
-            Self::from_gzip_file(path)
-        } else {
-            Self::from_file(path)
+        match path.extension().and_then(|s| s.to_str()) {
+            // BGZF is a series of gzip members, so the gzip decoder reads it.
+            Some("gz" | "bgz" | "bgzf") => Self::from_gzip_file(path),
+            #[cfg(feature = "zstd")]
+            Some("zst" | "zstd") => Ok(Self::from_reader(zstd::stream::read::Decoder::new(
+                File::open(path)?,
+            )?)),
+            #[cfg(feature = "bzip2")]
+            Some("bz2") => Ok(Self::from_reader(bzip2::read::MultiBzDecoder::new(
+                BufReader::new(File::open(path)?),
+            ))),
+            #[cfg(feature = "xz")]
+            Some("xz") => Ok(Self::from_reader(xz2::read::XzDecoder::new_multi_decoder(
+                BufReader::new(File::open(path)?),
+            ))),
+            #[cfg(not(feature = "zstd"))]
+            Some(ext @ ("zst" | "zstd")) => Err(crate::error::FastqError::UnsupportedCompression {
+                extension: ext.to_string(),
+                feature: "zstd",
+            }),
+            #[cfg(not(feature = "bzip2"))]
+            Some(ext @ "bz2") => Err(crate::error::FastqError::UnsupportedCompression {
+                extension: ext.to_string(),
+                feature: "bzip2",
+            }),
+            #[cfg(not(feature = "xz"))]
+            Some(ext @ "xz") => Err(crate::error::FastqError::UnsupportedCompression {
+                extension: ext.to_string(),
+                feature: "xz",
+            }),
+            _ => Self::from_file(path),
         }
     }
 
@@ -75,6 +102,10 @@ impl MmapReader {
         RecordIterator::new(&self.mmap)
     }
 
+    pub fn indexed_records(&self) -> Result<IndexedParser<'_>> {
+        IndexedParser::new(&self.mmap)
+    }
+
     pub fn into_records(self) -> impl Iterator<Item = Result<OwnedRecord>> {
         OwnedRecordIterator::new(self.mmap)
     }
@@ -119,9 +150,9 @@ struct OwnedRecordIterator {
 impl OwnedRecordIterator {
     fn new(mmap: Mmap) -> Self {
         let data = unsafe { std::slice::from_raw_parts(mmap.as_ptr(), mmap.len()) };
-        let parser = Box::new(Parser::new(unsafe {
-            std::mem::transmute::<&[u8], &[u8]>(data)
-        }));
+        let parser = Box::new(
+            Parser::new(unsafe { std::mem::transmute::<&[u8], &[u8]>(data) }).allow_wrapped(),
+        );
         OwnedRecordIterator {
             _mmap: mmap,
             parser: Box::into_raw(parser),
@@ -136,7 +167,7 @@ impl Iterator for OwnedRecordIterator {
         unsafe {
             (*self.parser)
                 .parse_record()
-                .map(|opt| opt.map(|r| OwnedRecord::from_record(&r)))
+                .map(|opt| opt.map(|r| OwnedRecord::from_wrapped(&r)))
                 .transpose()
         }
     }
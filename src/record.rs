@@ -27,8 +27,8 @@ impl QualityEncoding {
 
     pub fn to_phred_scores(&self, qual_string: &[u8]) -> Vec<u8> {
         match self {
-            QualityEncoding::Phred33 => qual_string.iter().map(|&q| q.saturating_sub(33)).collect(),
-            QualityEncoding::Phred64 => qual_string.iter().map(|&q| q.saturating_sub(64)).collect(),
+            QualityEncoding::Phred33 => crate::simd::phred_scores(qual_string, 33),
+            QualityEncoding::Phred64 => crate::simd::phred_scores(qual_string, 64),
             QualityEncoding::Unknown => {
                 vec![0; qual_string.len()]
             }
@@ -44,15 +44,49 @@ impl QualityEncoding {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 pub struct Record<'a> {
     pub id: &'a [u8],
     pub desc: Option<&'a [u8]>,
     pub seq: &'a [u8],
     pub qual: &'a [u8],
     quality_encoding: Option<QualityEncoding>,
+    raw: Option<RawSpan<'a>>,
 }
 
+/// The input bytes a record was parsed from, together with the fields as
+/// they were at that point. The span only counts as valid while the public
+/// fields still point at the same slices, so a record whose fields were
+/// reassigned (for example by trimming) falls back to formatted output.
+#[derive(Clone, Copy)]
+struct RawSpan<'a> {
+    bytes: &'a [u8],
+    id: &'a [u8],
+    desc: Option<&'a [u8]>,
+    seq: &'a [u8],
+    qual: &'a [u8],
+}
+
+impl fmt::Debug for RawSpan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RawSpan({} bytes)", self.bytes.len())
+    }
+}
+
+/// Equality compares the fields and the cached quality encoding; where the
+/// record was parsed from does not matter.
+impl PartialEq for Record<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.desc == other.desc
+            && self.seq == other.seq
+            && self.qual == other.qual
+            && self.quality_encoding == other.quality_encoding
+    }
+}
+
+impl Eq for Record<'_> {}
+
 impl<'a> Record<'a> {
     #[inline]
     pub fn new(id: &'a [u8], desc: Option<&'a [u8]>, seq: &'a [u8], qual: &'a [u8]) -> Self {
@@ -62,9 +96,41 @@ impl<'a> Record<'a> {
             seq,
             qual,
             quality_encoding: None,
+            raw: None,
         }
     }
 
+    /// Attaches the bytes the record was parsed from, header line through
+    /// quality line terminator. Parsers call this so that
+    /// [`FastqWriter::write_raw`](crate::writer::FastqWriter::write_raw) can
+    /// copy the record verbatim.
+    #[inline]
+    pub fn with_raw(mut self, bytes: &'a [u8]) -> Self {
+        self.raw = Some(RawSpan {
+            bytes,
+            id: self.id,
+            desc: self.desc,
+            seq: self.seq,
+            qual: self.qual,
+        });
+        self
+    }
+
+    /// The original bytes of the record, if it came from a parser and none
+    /// of its fields have been replaced since.
+    #[inline]
+    pub fn raw(&self) -> Option<&'a [u8]> {
+        let raw = self.raw.as_ref()?;
+        let same = |a: &[u8], b: &[u8]| std::ptr::eq(a, b);
+        let desc_same = match (self.desc, raw.desc) {
+            (Some(a), Some(b)) => same(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        (same(self.id, raw.id) && desc_same && same(self.seq, raw.seq) && same(self.qual, raw.qual))
+            .then_some(raw.bytes)
+    }
+
     #[inline]
     pub fn id(&self) -> &[u8] {
         self.id
@@ -124,12 +190,14 @@ impl<'a> Record<'a> {
             });
         }
 
-        for &base in self.seq {
-            if !matches!(
-                base,
-                b'A' | b'C' | b'G' | b'T' | b'N' | b'a' | b'c' | b'g' | b't' | b'n'
-            ) {
-                return Err(crate::error::FastqError::InvalidBase { base });
+        if !crate::simd::validate_alphabet(self.seq) {
+            for &base in self.seq {
+                if !matches!(
+                    base,
+                    b'A' | b'C' | b'G' | b'T' | b'N' | b'a' | b'c' | b'g' | b't' | b'n'
+                ) {
+                    return Err(crate::error::FastqError::InvalidBase { base });
+                }
             }
         }
 
@@ -161,20 +229,26 @@ impl<'a> Record<'a> {
         }
         scores.iter().map(|&q| q as f64).sum::<f64>() / scores.len() as f64
     }
+
+    pub fn reverse_complement(&self) -> OwnedRecord {
+        let mut qual = self.qual.to_vec();
+        qual.reverse();
+        OwnedRecord {
+            id: self.id.to_vec(),
+            desc: self.desc.map(|d| d.to_vec()),
+            seq: crate::simd::reverse_complement(self.seq),
+            qual,
+        }
+    }
 }
 
+/// Formats the record with the default
+/// [`FastqFormat`](crate::writer::FastqFormat), without the final newline.
+/// Use [`FastqFormat::display`](crate::writer::FastqFormat::display) for
+/// other layouts.
 impl<'a> fmt::Display for Record<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "@")?;
-        f.write_str(self.id_str().map_err(|_| fmt::Error)?)?;
-        if let Some(desc) = self.desc_str() {
-            write!(f, " {}", desc.map_err(|_| fmt::Error)?)?;
-        }
-        writeln!(f)?;
-        f.write_str(self.seq_str().map_err(|_| fmt::Error)?)?;
-        write!(f, "\n+\n")?;
-        f.write_str(self.qual_str().map_err(|_| fmt::Error)?)?;
-        Ok(())
+        crate::writer::FastqFormat::default().display(self).fmt(f)
     }
 }
 
@@ -195,6 +269,17 @@ impl OwnedRecord {
         }
     }
 
+    /// Like [`from_record`](Self::from_record), but drops the line breaks
+    /// of a wrapped record parsed with `Parser::allow_wrapped`.
+    pub(crate) fn from_wrapped(record: &Record) -> Self {
+        OwnedRecord {
+            id: record.id.to_vec(),
+            desc: record.desc.map(|d| d.to_vec()),
+            seq: crate::parser::residues(record.seq),
+            qual: crate::parser::residues(record.qual),
+        }
+    }
+
     pub fn as_record(&self) -> Record<'_> {
         Record {
             id: &self.id,
@@ -202,6 +287,55 @@ impl OwnedRecord {
             seq: &self.seq,
             qual: &self.qual,
             quality_encoding: None,
+            raw: None,
+        }
+    }
+}
+
+/// An [`OwnedRecord`] together with a copy of the bytes it was parsed
+/// from, for verbatim output with
+/// [`FastqWriter::write_owned_raw`](crate::writer::FastqWriter::write_owned_raw).
+/// The record is only reachable immutably, so the bytes always match it;
+/// [`into_record`](Self::into_record) takes it out for editing and drops
+/// them.
+pub struct RawRecord {
+    record: OwnedRecord,
+    raw: Option<Vec<u8>>,
+}
+
+impl RawRecord {
+    /// Copies `record`, and its original bytes if it still has them.
+    pub fn from_record(record: &Record) -> Self {
+        RawRecord {
+            record: OwnedRecord::from_record(record),
+            raw: record.raw().map(<[u8]>::to_vec),
+        }
+    }
+
+    pub fn record(&self) -> &OwnedRecord {
+        &self.record
+    }
+
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    pub fn into_record(self) -> OwnedRecord {
+        self.record
+    }
+
+    /// Borrows the record with its original bytes attached.
+    pub fn as_record(&self) -> Record<'_> {
+        let record = self.record.as_record();
+        match &self.raw {
+            Some(raw) => record.with_raw(raw),
+            None => record,
         }
     }
 }
+
+impl From<OwnedRecord> for RawRecord {
+    fn from(record: OwnedRecord) -> Self {
+        RawRecord { record, raw: None }
+    }
+}
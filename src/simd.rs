@@ -1,21 +1,50 @@
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::OnceLock;
 
 static HAS_AVX2: OnceLock<bool> = OnceLock::new();
+static HAS_SSE41: OnceLock<bool> = OnceLock::new();
+static FORCE_SCALAR: AtomicBool = AtomicBool::new(false);
 
 #[inline]
 fn has_avx2() -> bool {
-    *HAS_AVX2.get_or_init(|| {
-        #[cfg(target_arch = "x86_64")]
-        {
-            is_x86_feature_detected!("avx2")
-        }
-        #[cfg(not(target_arch = "x86_64"))]
-        {
-            false
-        }
-    })
+    !FORCE_SCALAR.load(Ordering::Relaxed)
+        && *HAS_AVX2.get_or_init(|| {
+            #[cfg(target_arch = "x86_64")]
+            {
+                is_x86_feature_detected!("avx2")
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            {
+                false
+            }
+        })
+}
+
+#[inline]
+fn has_sse41() -> bool {
+    !FORCE_SCALAR.load(Ordering::Relaxed)
+        && *HAS_SSE41.get_or_init(|| {
+            #[cfg(target_arch = "x86_64")]
+            {
+                is_x86_feature_detected!("sse4.1")
+            }
+            #[cfg(not(target_arch = "x86_64"))]
+            {
+                false
+            }
+        })
+}
+
+/// Routes every dispatched function in this module to its scalar
+/// implementation, regardless of the CPU features detected at runtime.
+pub fn force_scalar(enabled: bool) {
+    FORCE_SCALAR.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_scalar_forced() -> bool {
+    FORCE_SCALAR.load(Ordering::Relaxed)
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -193,3 +222,678 @@ pub mod bytecount {
         memchr::memchr_iter(byte, data).count()
     }
 }
+
+const BLOCK_SIZE: usize = 64;
+
+/// Stage-1 structural index: one bit per input byte, set where the byte is a
+/// newline, packed into 64-byte blocks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StructuralIndex {
+    blocks: Vec<u64>,
+    len: usize,
+}
+
+impl StructuralIndex {
+    pub fn build(data: &[u8]) -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if has_avx2() {
+                return unsafe { Self::build_avx2(data) };
+            }
+        }
+
+        Self::build_scalar(data)
+    }
+
+    pub fn build_scalar(data: &[u8]) -> Self {
+        let blocks = data.chunks(BLOCK_SIZE).map(scalar_block_mask).collect();
+        StructuralIndex {
+            blocks,
+            len: data.len(),
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    /// # Safety
+    /// This function requires AVX2 instruction set to be available on the CPU.
+    /// The caller must ensure that AVX2 is supported before calling this function.
+    pub unsafe fn build_avx2(data: &[u8]) -> Self {
+        let mut blocks = Vec::with_capacity(data.len().div_ceil(BLOCK_SIZE));
+        let newline = _mm256_set1_epi8(b'\n' as i8);
+
+        let chunks = data.chunks_exact(BLOCK_SIZE);
+        let remainder = chunks.remainder();
+
+        for chunk in chunks {
+            let lo = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            let hi = _mm256_loadu_si256(chunk.as_ptr().add(32) as *const __m256i);
+            let lo_mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(lo, newline)) as u32 as u64;
+            let hi_mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(hi, newline)) as u32 as u64;
+            blocks.push(lo_mask | (hi_mask << 32));
+        }
+
+        if !remainder.is_empty() {
+            blocks.push(scalar_block_mask(remainder));
+        }
+
+        StructuralIndex {
+            blocks,
+            len: data.len(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn blocks(&self) -> &[u64] {
+        &self.blocks
+    }
+
+    pub fn newline_count(&self) -> usize {
+        self.blocks.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    pub fn newlines(&self) -> NewlineIter<'_> {
+        NewlineIter {
+            blocks: &self.blocks,
+            block_idx: 0,
+            current: self.blocks.first().copied().unwrap_or(0),
+        }
+    }
+
+    /// Start offset of every line, including a final line that is not
+    /// terminated by a newline.
+    pub fn line_starts(&self) -> Vec<usize> {
+        let mut starts = Vec::with_capacity(self.newline_count() + 1);
+        if self.len > 0 {
+            starts.push(0);
+        }
+        for pos in self.newlines() {
+            if pos + 1 < self.len {
+                starts.push(pos + 1);
+            }
+        }
+        starts
+    }
+}
+
+#[inline]
+fn scalar_block_mask(block: &[u8]) -> u64 {
+    block
+        .iter()
+        .enumerate()
+        .fold(0u64, |mask, (i, &b)| mask | (((b == b'\n') as u64) << i))
+}
+
+pub struct NewlineIter<'a> {
+    blocks: &'a [u64],
+    block_idx: usize,
+    current: u64,
+}
+
+impl<'a> Iterator for NewlineIter<'a> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.block_idx += 1;
+            if self.block_idx >= self.blocks.len() {
+                return None;
+            }
+            self.current = self.blocks[self.block_idx];
+        }
+
+        let bit = self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+        Some(self.block_idx * BLOCK_SIZE + bit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BaseCounts {
+    pub a: usize,
+    pub c: usize,
+    pub g: usize,
+    pub t: usize,
+    pub n: usize,
+    pub other: usize,
+}
+
+impl BaseCounts {
+    #[inline]
+    pub fn gc(&self) -> usize {
+        self.g + self.c
+    }
+
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.a + self.c + self.g + self.t + self.n + self.other
+    }
+
+    #[inline]
+    pub fn gc_fraction(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            0.0
+        } else {
+            self.gc() as f64 / total as f64
+        }
+    }
+
+    fn add(&mut self, other: BaseCounts) {
+        self.a += other.a;
+        self.c += other.c;
+        self.g += other.g;
+        self.t += other.t;
+        self.n += other.n;
+        self.other += other.other;
+    }
+}
+
+/// Scalar reference implementations of the sequence kernels. The dispatched
+/// functions must produce identical results for every input.
+pub mod scalar {
+    use super::BaseCounts;
+
+    pub fn count_bases(seq: &[u8]) -> BaseCounts {
+        let mut counts = BaseCounts::default();
+        for &b in seq {
+            match b | 0x20 {
+                b'a' => counts.a += 1,
+                b'c' => counts.c += 1,
+                b'g' => counts.g += 1,
+                b't' => counts.t += 1,
+                b'n' => counts.n += 1,
+                _ => counts.other += 1,
+            }
+        }
+        counts
+    }
+
+    #[inline]
+    pub fn complement(base: u8) -> u8 {
+        match base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            b'N' => b'N',
+            b'a' => b't',
+            b'c' => b'g',
+            b'g' => b'c',
+            b't' => b'a',
+            b'n' => b'n',
+            other => other,
+        }
+    }
+
+    pub fn reverse_complement_into(seq: &[u8], out: &mut [u8]) {
+        for (dst, &src) in out.iter_mut().zip(seq.iter().rev()) {
+            *dst = complement(src);
+        }
+    }
+
+    pub fn to_uppercase(seq: &mut [u8]) {
+        seq.make_ascii_uppercase();
+    }
+
+    pub fn validate_alphabet(seq: &[u8]) -> bool {
+        seq.iter()
+            .all(|&b| matches!(b | 0x20, b'a' | b'c' | b'g' | b't' | b'n'))
+    }
+
+    pub fn phred_scores_into(qual: &[u8], offset: u8, out: &mut [u8]) {
+        for (dst, &q) in out.iter_mut().zip(qual) {
+            *dst = q.saturating_sub(offset);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+/// # Safety
+/// This function requires AVX2 instruction set to be available on the CPU.
+/// The caller must ensure that AVX2 is supported before calling this function.
+pub unsafe fn count_bases_avx2(seq: &[u8]) -> BaseCounts {
+    let case = _mm256_set1_epi8(0x20);
+    let targets = [
+        _mm256_set1_epi8(b'a' as i8),
+        _mm256_set1_epi8(b'c' as i8),
+        _mm256_set1_epi8(b'g' as i8),
+        _mm256_set1_epi8(b't' as i8),
+        _mm256_set1_epi8(b'n' as i8),
+    ];
+    let mut totals = [0usize; 5];
+
+    let chunks = seq.chunks_exact(32);
+    let remainder = chunks.remainder();
+
+    for chunk in chunks {
+        let vector = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+        let lower = _mm256_or_si256(vector, case);
+        for (total, target) in totals.iter_mut().zip(targets.iter()) {
+            let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(lower, *target));
+            *total += mask.count_ones() as usize;
+        }
+    }
+
+    let counted = seq.len() - remainder.len();
+    let mut counts = BaseCounts {
+        a: totals[0],
+        c: totals[1],
+        g: totals[2],
+        t: totals[3],
+        n: totals[4],
+        other: 0,
+    };
+    counts.other = counted - counts.total();
+    counts.add(scalar::count_bases(remainder));
+    counts
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+/// # Safety
+/// This function requires SSE4.1 instruction set to be available on the CPU.
+/// The caller must ensure that SSE4.1 is supported before calling this function.
+pub unsafe fn count_bases_sse41(seq: &[u8]) -> BaseCounts {
+    let case = _mm_set1_epi8(0x20);
+    let targets = [
+        _mm_set1_epi8(b'a' as i8),
+        _mm_set1_epi8(b'c' as i8),
+        _mm_set1_epi8(b'g' as i8),
+        _mm_set1_epi8(b't' as i8),
+        _mm_set1_epi8(b'n' as i8),
+    ];
+    let mut totals = [0usize; 5];
+
+    let chunks = seq.chunks_exact(16);
+    let remainder = chunks.remainder();
+
+    for chunk in chunks {
+        let vector = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+        let lower = _mm_or_si128(vector, case);
+        for (total, target) in totals.iter_mut().zip(targets.iter()) {
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(lower, *target));
+            *total += mask.count_ones() as usize;
+        }
+    }
+
+    let counted = seq.len() - remainder.len();
+    let mut counts = BaseCounts {
+        a: totals[0],
+        c: totals[1],
+        g: totals[2],
+        t: totals[3],
+        n: totals[4],
+        other: 0,
+    };
+    counts.other = counted - counts.total();
+    counts.add(scalar::count_bases(remainder));
+    counts
+}
+
+#[inline]
+pub fn count_bases(seq: &[u8]) -> BaseCounts {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            return unsafe { count_bases_avx2(seq) };
+        }
+        if has_sse41() {
+            return unsafe { count_bases_sse41(seq) };
+        }
+    }
+
+    scalar::count_bases(seq)
+}
+
+#[inline]
+pub fn gc_count(seq: &[u8]) -> usize {
+    count_bases(seq).gc()
+}
+
+#[inline]
+pub fn n_count(seq: &[u8]) -> usize {
+    count_bases(seq).n
+}
+
+// Complement lookup keyed by the low nibble of an ACGTN letter, in either
+// case: A=1, C=3, G=7, N=14, T=4.
+#[cfg(target_arch = "x86_64")]
+const COMPLEMENT_TABLE: [i8; 16] = [
+    0, b'T' as i8, 0, b'G' as i8, b'A' as i8, 0, 0, b'C' as i8, 0, 0, 0, 0, 0, 0, b'N' as i8, 0,
+];
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+/// # Safety
+/// This function requires AVX2 instruction set to be available on the CPU.
+/// The caller must ensure that AVX2 is supported before calling this function.
+/// `out` must be at least as long as `seq`.
+pub unsafe fn reverse_complement_avx2(seq: &[u8], out: &mut [u8]) {
+    assert!(out.len() >= seq.len());
+
+    let t = COMPLEMENT_TABLE;
+    let table = _mm256_setr_epi8(
+        t[0], t[1], t[2], t[3], t[4], t[5], t[6], t[7], t[8], t[9], t[10], t[11], t[12], t[13],
+        t[14], t[15], t[0], t[1], t[2], t[3], t[4], t[5], t[6], t[7], t[8], t[9], t[10], t[11],
+        t[12], t[13], t[14], t[15],
+    );
+    let reverse = _mm256_setr_epi8(
+        15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 15, 14, 13, 12, 11, 10, 9, 8, 7, 6,
+        5, 4, 3, 2, 1, 0,
+    );
+    let case = _mm256_set1_epi8(0x20);
+    let nibble = _mm256_set1_epi8(0x0f);
+    let letters = [b'a', b'c', b'g', b't', b'n'].map(|b| _mm256_set1_epi8(b as i8));
+
+    let len = seq.len();
+    let chunks = seq.chunks_exact(32);
+    let remainder = chunks.remainder();
+
+    for (i, chunk) in chunks.enumerate() {
+        let vector = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+        let lower = _mm256_or_si256(vector, case);
+        let mut valid = _mm256_setzero_si256();
+        for letter in &letters {
+            valid = _mm256_or_si256(valid, _mm256_cmpeq_epi8(lower, *letter));
+        }
+
+        let index = _mm256_and_si256(vector, nibble);
+        let complemented = _mm256_or_si256(
+            _mm256_shuffle_epi8(table, index),
+            _mm256_and_si256(vector, case),
+        );
+        let blended = _mm256_blendv_epi8(vector, complemented, valid);
+
+        let reversed = _mm256_shuffle_epi8(blended, reverse);
+        let reversed = _mm256_permute2x128_si256(reversed, reversed, 0x01);
+
+        let dst = len - (i + 1) * 32;
+        _mm256_storeu_si256(out.as_mut_ptr().add(dst) as *mut __m256i, reversed);
+    }
+
+    scalar::reverse_complement_into(remainder, &mut out[..remainder.len()]);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+/// # Safety
+/// This function requires SSE4.1 instruction set to be available on the CPU.
+/// The caller must ensure that SSE4.1 is supported before calling this function.
+/// `out` must be at least as long as `seq`.
+pub unsafe fn reverse_complement_sse41(seq: &[u8], out: &mut [u8]) {
+    assert!(out.len() >= seq.len());
+
+    let t = COMPLEMENT_TABLE;
+    let table = _mm_setr_epi8(
+        t[0], t[1], t[2], t[3], t[4], t[5], t[6], t[7], t[8], t[9], t[10], t[11], t[12], t[13],
+        t[14], t[15],
+    );
+    let reverse = _mm_setr_epi8(15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0);
+    let case = _mm_set1_epi8(0x20);
+    let nibble = _mm_set1_epi8(0x0f);
+    let letters = [b'a', b'c', b'g', b't', b'n'].map(|b| _mm_set1_epi8(b as i8));
+
+    let len = seq.len();
+    let chunks = seq.chunks_exact(16);
+    let remainder = chunks.remainder();
+
+    for (i, chunk) in chunks.enumerate() {
+        let vector = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+        let lower = _mm_or_si128(vector, case);
+        let mut valid = _mm_setzero_si128();
+        for letter in &letters {
+            valid = _mm_or_si128(valid, _mm_cmpeq_epi8(lower, *letter));
+        }
+
+        let index = _mm_and_si128(vector, nibble);
+        let complemented =
+            _mm_or_si128(_mm_shuffle_epi8(table, index), _mm_and_si128(vector, case));
+        let blended = _mm_blendv_epi8(vector, complemented, valid);
+        let reversed = _mm_shuffle_epi8(blended, reverse);
+
+        let dst = len - (i + 1) * 16;
+        _mm_storeu_si128(out.as_mut_ptr().add(dst) as *mut __m128i, reversed);
+    }
+
+    scalar::reverse_complement_into(remainder, &mut out[..remainder.len()]);
+}
+
+/// Reverse complement of an ACGTN sequence. Case is preserved and any other
+/// byte is copied through unchanged.
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; seq.len()];
+    reverse_complement_into(seq, &mut out);
+    out
+}
+
+#[inline]
+pub fn reverse_complement_into(seq: &[u8], out: &mut [u8]) {
+    let out = &mut out[..seq.len()];
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            return unsafe { reverse_complement_avx2(seq, out) };
+        }
+        if has_sse41() {
+            return unsafe { reverse_complement_sse41(seq, out) };
+        }
+    }
+
+    scalar::reverse_complement_into(seq, out)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+/// # Safety
+/// This function requires AVX2 instruction set to be available on the CPU.
+/// The caller must ensure that AVX2 is supported before calling this function.
+pub unsafe fn to_uppercase_avx2(seq: &mut [u8]) {
+    let below = _mm256_set1_epi8((b'a' - 1) as i8);
+    let above = _mm256_set1_epi8((b'z' + 1) as i8);
+    let case = _mm256_set1_epi8(0x20);
+
+    let mut chunks = seq.chunks_exact_mut(32);
+    for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m256i;
+        let vector = _mm256_loadu_si256(ptr);
+        let is_lower = _mm256_and_si256(
+            _mm256_cmpgt_epi8(vector, below),
+            _mm256_cmpgt_epi8(above, vector),
+        );
+        let upper = _mm256_sub_epi8(vector, _mm256_and_si256(is_lower, case));
+        _mm256_storeu_si256(ptr, upper);
+    }
+
+    scalar::to_uppercase(chunks.into_remainder());
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+/// # Safety
+/// This function requires SSE4.1 instruction set to be available on the CPU.
+/// The caller must ensure that SSE4.1 is supported before calling this function.
+pub unsafe fn to_uppercase_sse41(seq: &mut [u8]) {
+    let below = _mm_set1_epi8((b'a' - 1) as i8);
+    let above = _mm_set1_epi8((b'z' + 1) as i8);
+    let case = _mm_set1_epi8(0x20);
+
+    let mut chunks = seq.chunks_exact_mut(16);
+    for chunk in &mut chunks {
+        let ptr = chunk.as_mut_ptr() as *mut __m128i;
+        let vector = _mm_loadu_si128(ptr);
+        let is_lower = _mm_and_si128(_mm_cmpgt_epi8(vector, below), _mm_cmplt_epi8(vector, above));
+        let upper = _mm_sub_epi8(vector, _mm_and_si128(is_lower, case));
+        _mm_storeu_si128(ptr, upper);
+    }
+
+    scalar::to_uppercase(chunks.into_remainder());
+}
+
+#[inline]
+pub fn to_uppercase(seq: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            return unsafe { to_uppercase_avx2(seq) };
+        }
+        if has_sse41() {
+            return unsafe { to_uppercase_sse41(seq) };
+        }
+    }
+
+    scalar::to_uppercase(seq)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+/// # Safety
+/// This function requires AVX2 instruction set to be available on the CPU.
+/// The caller must ensure that AVX2 is supported before calling this function.
+pub unsafe fn validate_alphabet_avx2(seq: &[u8]) -> bool {
+    let case = _mm256_set1_epi8(0x20);
+    let letters = [b'a', b'c', b'g', b't', b'n'].map(|b| _mm256_set1_epi8(b as i8));
+
+    let chunks = seq.chunks_exact(32);
+    let remainder = chunks.remainder();
+
+    for chunk in chunks {
+        let vector = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+        let lower = _mm256_or_si256(vector, case);
+        let mut valid = _mm256_setzero_si256();
+        for letter in &letters {
+            valid = _mm256_or_si256(valid, _mm256_cmpeq_epi8(lower, *letter));
+        }
+        if _mm256_movemask_epi8(valid) != -1 {
+            return false;
+        }
+    }
+
+    scalar::validate_alphabet(remainder)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+/// # Safety
+/// This function requires SSE4.1 instruction set to be available on the CPU.
+/// The caller must ensure that SSE4.1 is supported before calling this function.
+pub unsafe fn validate_alphabet_sse41(seq: &[u8]) -> bool {
+    let case = _mm_set1_epi8(0x20);
+    let letters = [b'a', b'c', b'g', b't', b'n'].map(|b| _mm_set1_epi8(b as i8));
+
+    let chunks = seq.chunks_exact(16);
+    let remainder = chunks.remainder();
+
+    for chunk in chunks {
+        let vector = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+        let lower = _mm_or_si128(vector, case);
+        let mut valid = _mm_setzero_si128();
+        for letter in &letters {
+            valid = _mm_or_si128(valid, _mm_cmpeq_epi8(lower, *letter));
+        }
+        if _mm_movemask_epi8(valid) != 0xffff {
+            return false;
+        }
+    }
+
+    scalar::validate_alphabet(remainder)
+}
+
+/// Returns true when every byte is one of `ACGTN`, in either case.
+#[inline]
+pub fn validate_alphabet(seq: &[u8]) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            return unsafe { validate_alphabet_avx2(seq) };
+        }
+        if has_sse41() {
+            return unsafe { validate_alphabet_sse41(seq) };
+        }
+    }
+
+    scalar::validate_alphabet(seq)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+/// # Safety
+/// This function requires AVX2 instruction set to be available on the CPU.
+/// The caller must ensure that AVX2 is supported before calling this function.
+/// `out` must be at least as long as `qual`.
+pub unsafe fn phred_scores_avx2(qual: &[u8], offset: u8, out: &mut [u8]) {
+    assert!(out.len() >= qual.len());
+    let offset_vec = _mm256_set1_epi8(offset as i8);
+
+    let chunks = qual.chunks_exact(32);
+    let remainder = chunks.remainder();
+
+    for (i, chunk) in chunks.enumerate() {
+        let vector = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+        let scores = _mm256_subs_epu8(vector, offset_vec);
+        _mm256_storeu_si256(out.as_mut_ptr().add(i * 32) as *mut __m256i, scores);
+    }
+
+    let done = qual.len() - remainder.len();
+    scalar::phred_scores_into(remainder, offset, &mut out[done..]);
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+/// # Safety
+/// This function requires SSE4.1 instruction set to be available on the CPU.
+/// The caller must ensure that SSE4.1 is supported before calling this function.
+/// `out` must be at least as long as `qual`.
+pub unsafe fn phred_scores_sse41(qual: &[u8], offset: u8, out: &mut [u8]) {
+    assert!(out.len() >= qual.len());
+    let offset_vec = _mm_set1_epi8(offset as i8);
+
+    let chunks = qual.chunks_exact(16);
+    let remainder = chunks.remainder();
+
+    for (i, chunk) in chunks.enumerate() {
+        let vector = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+        let scores = _mm_subs_epu8(vector, offset_vec);
+        _mm_storeu_si128(out.as_mut_ptr().add(i * 16) as *mut __m128i, scores);
+    }
+
+    let done = qual.len() - remainder.len();
+    scalar::phred_scores_into(remainder, offset, &mut out[done..]);
+}
+
+/// Subtracts the Phred offset from every quality character, saturating at 0.
+pub fn phred_scores(qual: &[u8], offset: u8) -> Vec<u8> {
+    let mut out = vec![0u8; qual.len()];
+    phred_scores_into(qual, offset, &mut out);
+    out
+}
+
+#[inline]
+pub fn phred_scores_into(qual: &[u8], offset: u8, out: &mut [u8]) {
+    let out = &mut out[..qual.len()];
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2() {
+            return unsafe { phred_scores_avx2(qual, offset, out) };
+        }
+        if has_sse41() {
+            return unsafe { phred_scores_sse41(qual, offset, out) };
+        }
+    }
+
+    scalar::phred_scores_into(qual, offset, out)
+}
@@ -0,0 +1,490 @@
+use crate::{
+    error::{FastqError, Result},
+    index::KeyExtractor,
+    paired::{PairMatching, SpillDir},
+    parser::residue_count,
+    reader::FastqReader,
+    record::OwnedRecord,
+    writer::FastqWriter,
+};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Most runs merged at once. Larger run counts are merged in several
+/// passes so the number of open spill files stays bounded.
+const MERGE_WIDTH: usize = 64;
+
+/// What [`ExternalSorter`] orders records by. Records with equal keys keep
+/// their input order.
+pub enum SortKey {
+    /// The read ID. In paired mode the pair key of the
+    /// [`PairMatching`] rule is used, so `/1` and `/2` suffixes are ignored.
+    Id,
+    /// The bases, which groups similar reads and helps compression.
+    Sequence,
+    /// The number of bases, shortest first.
+    Length,
+    /// Any key from a [`KeyExtractor`]. Records without a key sort first.
+    Key(KeyExtractor),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SortStats {
+    pub records: usize,
+    /// Sorted runs written to disk; 0 when everything fit in memory.
+    pub runs: usize,
+}
+
+/// Sorts FASTQ input of any size under a memory budget. Records are
+/// buffered until `memory_limit` bytes, sorted and spilled to a temporary
+/// run, and the runs are k-way merged into the output. Paired input is
+/// co-sorted: mates are kept together and ordered by the R1 read.
+pub struct ExternalSorter {
+    key: SortKey,
+    numeric: bool,
+    matching: PairMatching,
+    memory_limit: usize,
+    spill_dir: Option<PathBuf>,
+}
+
+impl Default for ExternalSorter {
+    fn default() -> Self {
+        ExternalSorter {
+            key: SortKey::Id,
+            numeric: false,
+            matching: PairMatching::default(),
+            memory_limit: 512 * 1024 * 1024,
+            spill_dir: None,
+        }
+    }
+}
+
+impl ExternalSorter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key(mut self, key: SortKey) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Compare runs of digits in IDs and extracted keys by value, so
+    /// `read2` sorts before `read10`. See [`natural_cmp`].
+    pub fn numeric(mut self, numeric: bool) -> Self {
+        self.numeric = numeric;
+        self
+    }
+
+    /// Rule used to check that paired inputs are in step and, for
+    /// [`SortKey::Id`], to derive the pair key.
+    pub fn matching(mut self, matching: PairMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
+    /// Approximate bytes of records held in memory before a run is
+    /// spilled.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes.max(1);
+        self
+    }
+
+    pub fn spill_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.spill_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sorts `records` into `output`, which keeps its own
+    /// [`FastqFormat`](crate::writer::FastqFormat).
+    pub fn sort<I, W>(&self, records: I, output: &mut FastqWriter<W>) -> Result<SortStats>
+    where
+        I: Iterator<Item = Result<OwnedRecord>>,
+        W: Write,
+    {
+        self.sort_groups(records.map(|r| r.map(|r| [r])), [output])
+    }
+
+    /// Co-sorts two mate files. Both inputs must hold the same reads in
+    /// the same order; mates are checked with the matching rule.
+    pub fn sort_paired<I1, I2, W>(
+        &self,
+        r1: I1,
+        r2: I2,
+        out_r1: &mut FastqWriter<W>,
+        out_r2: &mut FastqWriter<W>,
+    ) -> Result<SortStats>
+    where
+        I1: Iterator<Item = Result<OwnedRecord>>,
+        I2: Iterator<Item = Result<OwnedRecord>>,
+        W: Write,
+    {
+        let mut r1 = r1.fuse();
+        let mut r2 = r2.fuse();
+        let pairs = std::iter::from_fn(|| match (r1.next(), r2.next()) {
+            (None, None) => None,
+            (Some(a), Some(b)) => Some(a.and_then(|a| b.and_then(|b| self.check_pair(a, b)))),
+            _ => Some(Err(FastqError::PairedEndLengthMismatch)),
+        });
+        self.sort_groups(pairs, [out_r1, out_r2])
+    }
+
+    /// Sorts `input` into `output`. The output is written to a temporary
+    /// file and renamed into place at the end, so it may be the input
+    /// itself: the mapped input is never truncated while it is read.
+    pub fn sort_file<P: AsRef<Path>>(&self, input: P, output: P) -> Result<SortStats> {
+        let reader = FastqReader::from_path(input)?;
+        let mut writer = FastqWriter::to_file_atomic(output)?;
+        let stats = self.sort(reader.into_records(), &mut writer)?;
+        writer.commit()?;
+        Ok(stats)
+    }
+
+    /// Co-sorts two mate files. As with [`sort_file`](Self::sort_file),
+    /// the outputs may be the inputs.
+    pub fn sort_paired_files<P: AsRef<Path>>(
+        &self,
+        r1_in: P,
+        r2_in: P,
+        r1_out: P,
+        r2_out: P,
+    ) -> Result<SortStats> {
+        let r1 = FastqReader::from_path(r1_in)?;
+        let r2 = FastqReader::from_path(r2_in)?;
+        let mut out_r1 = FastqWriter::to_file_atomic(r1_out)?;
+        let mut out_r2 = FastqWriter::to_file_atomic(r2_out)?;
+        let stats = self.sort_paired(
+            r1.into_records(),
+            r2.into_records(),
+            &mut out_r1,
+            &mut out_r2,
+        )?;
+        out_r1.commit()?;
+        out_r2.commit()?;
+        Ok(stats)
+    }
+
+    fn check_pair(&self, r1: OwnedRecord, r2: OwnedRecord) -> Result<[OwnedRecord; 2]> {
+        if !self.matching.is_pair(&r1.as_record(), &r2.as_record()) {
+            return Err(FastqError::PairedEndMismatch {
+                r1_id: String::from_utf8_lossy(&r1.id).into_owned(),
+                r2_id: String::from_utf8_lossy(&r2.id).into_owned(),
+            });
+        }
+        Ok([r1, r2])
+    }
+
+    fn sort_groups<I, W, const N: usize>(
+        &self,
+        groups: I,
+        mut outputs: [&mut FastqWriter<W>; N],
+    ) -> Result<SortStats>
+    where
+        I: Iterator<Item = Result<[OwnedRecord; N]>>,
+        W: Write,
+    {
+        let mut stats = SortStats::default();
+        let mut buffer = Vec::new();
+        let mut buffered = 0;
+        let mut spill: Option<(SpillDir, Vec<Run<N>>)> = None;
+
+        for group in groups {
+            let entry = self.entry(group?);
+            buffered += entry.footprint();
+            buffer.push(entry);
+            stats.records += 1;
+
+            if buffered >= self.memory_limit {
+                let (dir, runs) = match &mut spill {
+                    Some(spill) => spill,
+                    None => {
+                        spill.insert((SpillDir::create(self.spill_dir.as_deref())?, Vec::new()))
+                    }
+                };
+                let run = Run::create(&dir.0, runs.len())?;
+                self.sort_entries(&mut buffer);
+                run.write(buffer.drain(..).map(Ok))?;
+                runs.push(run);
+                buffered = 0;
+            }
+        }
+
+        self.sort_entries(&mut buffer);
+        let Some((dir, mut runs)) = spill else {
+            return write_entries(buffer.into_iter().map(Ok), &mut outputs).map(|()| stats);
+        };
+        stats.runs = runs.len();
+
+        // The last, partial run stays in memory and is merged after every
+        // spilled run, which keeps equal keys in input order.
+        let mut next_run = runs.len();
+        while runs.len() + 1 > MERGE_WIDTH {
+            let merged = Run::create(&dir.0, next_run)?;
+            next_run += 1;
+            let batch: Vec<Run<N>> = runs.drain(..MERGE_WIDTH).collect();
+            merged.write(self.merge(batch, Vec::new())?)?;
+            runs.insert(0, merged);
+        }
+        let merged = self.merge(runs, buffer)?;
+        write_entries(merged, &mut outputs)?;
+        Ok(stats)
+    }
+
+    fn entry<const N: usize>(&self, records: [OwnedRecord; N]) -> Entry<N> {
+        let key = match &self.key {
+            SortKey::Key(extractor) => extractor
+                .extract(&records[0].as_record())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        Entry { key, records }
+    }
+
+    fn sort_entries<const N: usize>(&self, entries: &mut [Entry<N>]) {
+        entries.sort_by(|a, b| self.compare(a, b));
+    }
+
+    fn compare<const N: usize>(&self, a: &Entry<N>, b: &Entry<N>) -> Ordering {
+        let bytes = |a: &[u8], b: &[u8]| {
+            if self.numeric {
+                natural_cmp(a, b)
+            } else {
+                a.cmp(b)
+            }
+        };
+        let (r1, r2) = (&a.records[0], &b.records[0]);
+        match &self.key {
+            SortKey::Id if N > 1 => bytes(
+                self.matching.pair_key(&r1.as_record()),
+                self.matching.pair_key(&r2.as_record()),
+            ),
+            SortKey::Id => bytes(&r1.id, &r2.id),
+            SortKey::Sequence => r1.seq.cmp(&r2.seq),
+            SortKey::Length => residue_count(&r1.seq).cmp(&residue_count(&r2.seq)),
+            SortKey::Key(_) => bytes(&a.key, &b.key),
+        }
+    }
+
+    /// Merges `runs` in order, followed by the already sorted `tail`.
+    /// Ties go to the earlier source, which keeps the sort stable.
+    fn merge<const N: usize>(
+        &self,
+        runs: Vec<Run<N>>,
+        tail: Vec<Entry<N>>,
+    ) -> Result<impl Iterator<Item = Result<Entry<N>>> + '_> {
+        let mut sources: Vec<Source<N>> = Vec::with_capacity(runs.len() + 1);
+        for run in &runs {
+            sources.push(Source::Run(run.open()?));
+        }
+        sources.push(Source::Memory(tail.into_iter()));
+
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (index, source) in sources.iter_mut().enumerate() {
+            if let Some(entry) = self.next_entry(source)? {
+                heap.push(Head {
+                    sorter: self,
+                    entry,
+                    source: index,
+                });
+            }
+        }
+
+        let mut failed = false;
+        Ok(std::iter::from_fn(move || {
+            // Run files are removed when the merge is dropped.
+            let _ = &runs;
+            if failed {
+                return None;
+            }
+            let head = heap.pop()?;
+            match self.next_entry(&mut sources[head.source]) {
+                Ok(Some(entry)) => heap.push(Head {
+                    sorter: self,
+                    entry,
+                    source: head.source,
+                }),
+                Ok(None) => {}
+                Err(e) => {
+                    failed = true;
+                    return Some(Err(e));
+                }
+            }
+            Some(Ok(head.entry))
+        }))
+    }
+
+    fn next_entry<const N: usize>(&self, source: &mut Source<N>) -> Result<Option<Entry<N>>> {
+        match source {
+            Source::Memory(entries) => Ok(entries.next()),
+            Source::Run(readers) => {
+                let mut records = Vec::with_capacity(N);
+                for reader in readers.iter_mut() {
+                    match reader.next() {
+                        Some(record) => records.push(record?),
+                        None => break,
+                    }
+                }
+                if records.is_empty() {
+                    return Ok(None);
+                }
+                match records.try_into() {
+                    Ok(records) => Ok(Some(self.entry(records))),
+                    Err(_) => Err(FastqError::PairedEndLengthMismatch),
+                }
+            }
+        }
+    }
+}
+
+/// Compares byte strings with runs of ASCII digits ordered by numeric
+/// value, so `read2` < `read10` and `lane1:9` < `lane1:10`. Leading zeros
+/// are ignored, and strings that only differ in them fall back to plain
+/// byte order.
+pub fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let (start_a, start_b) = (i, j);
+            while i < a.len() && a[i].is_ascii_digit() {
+                i += 1;
+            }
+            while j < b.len() && b[j].is_ascii_digit() {
+                j += 1;
+            }
+            let x = trim_zeros(&a[start_a..i]);
+            let y = trim_zeros(&b[start_b..j]);
+            let ord = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        } else if a[i] != b[j] {
+            return a[i].cmp(&b[j]);
+        } else {
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j)).then_with(|| a.cmp(b))
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&d| d == b'0').count();
+    &digits[zeros..]
+}
+
+struct Entry<const N: usize> {
+    key: Vec<u8>,
+    records: [OwnedRecord; N],
+}
+
+impl<const N: usize> Entry<N> {
+    /// Rough heap size, counting the allocations of every field.
+    fn footprint(&self) -> usize {
+        let fields: usize = self
+            .records
+            .iter()
+            .map(|r| r.id.len() + r.desc.as_ref().map_or(0, Vec::len) + r.seq.len() + r.qual.len())
+            .sum();
+        std::mem::size_of::<Self>() + self.key.len() + fields
+    }
+}
+
+fn write_entries<E, W, const N: usize>(
+    entries: E,
+    outputs: &mut [&mut FastqWriter<W>],
+) -> Result<()>
+where
+    E: IntoIterator<Item = Result<Entry<N>>>,
+    W: Write,
+{
+    for entry in entries {
+        for (record, output) in entry?.records.iter().zip(outputs.iter_mut()) {
+            output.write_owned_record(record)?;
+        }
+    }
+    Ok(())
+}
+
+/// A sorted run on disk, one plain FASTQ file per mate.
+struct Run<const N: usize> {
+    paths: [PathBuf; N],
+}
+
+impl<const N: usize> Run<N> {
+    fn create(dir: &Path, index: usize) -> Result<Self> {
+        Ok(Run {
+            paths: std::array::from_fn(|mate| {
+                dir.join(format!("run{:05}_{}.fastq", index, mate + 1))
+            }),
+        })
+    }
+
+    fn write<E>(&self, entries: E) -> Result<()>
+    where
+        E: IntoIterator<Item = Result<Entry<N>>>,
+    {
+        let mut writers = Vec::with_capacity(N);
+        for path in &self.paths {
+            writers.push(FastqWriter::to_file(path)?);
+        }
+        write_entries(entries, &mut writers.iter_mut().collect::<Vec<_>>())?;
+        for writer in writers {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Vec<RunReader>> {
+        self.paths
+            .iter()
+            .map(|path| Ok(FastqReader::from_path(path)?.into_records()))
+            .collect()
+    }
+}
+
+impl<const N: usize> Drop for Run<N> {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+type RunReader = Box<dyn Iterator<Item = Result<OwnedRecord>> + Send>;
+
+enum Source<const N: usize> {
+    Run(Vec<RunReader>),
+    Memory(std::vec::IntoIter<Entry<N>>),
+}
+
+/// Merge heap entry. `BinaryHeap` is a max-heap, so the ordering is
+/// reversed to pop the smallest key, and the earliest source on ties.
+struct Head<'s, const N: usize> {
+    sorter: &'s ExternalSorter,
+    entry: Entry<N>,
+    source: usize,
+}
+
+impl<const N: usize> Ord for Head<'_, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sorter
+            .compare(&other.entry, &self.entry)
+            .then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl<const N: usize> PartialOrd for Head<'_, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const N: usize> PartialEq for Head<'_, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<const N: usize> Eq for Head<'_, N> {}
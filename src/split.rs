@@ -0,0 +1,528 @@
+use crate::{
+    compress::OutputCompression,
+    error::{FastqError, Result},
+    index::KeyExtractor,
+    record::{OwnedRecord, Record},
+    writer::{FastqFormat, FastqWriter},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_MAX_OPEN: usize = 256;
+
+/// When a [`SplitWriter`] moves on to the next shard.
+///
+/// With the size limits a shard never exceeds the limit unless a single
+/// record (or pair) does. Bytes are counted before compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    Records(u64),
+    Bases(u64),
+    Bytes(u64),
+    /// Deals records to `K` files in turn, all kept open.
+    RoundRobin(usize),
+}
+
+/// Decides which shard each record or pair goes to.
+struct Sharder {
+    split: SplitBy,
+    shard: usize,
+    records: u64,
+    bases: u64,
+    bytes: u64,
+}
+
+impl Sharder {
+    fn new(split: SplitBy) -> Self {
+        let split = match split {
+            SplitBy::Records(n) => SplitBy::Records(n.max(1)),
+            SplitBy::RoundRobin(k) => SplitBy::RoundRobin(k.max(1)),
+            other => other,
+        };
+        Sharder {
+            split,
+            shard: 0,
+            records: 0,
+            bases: 0,
+            bytes: 0,
+        }
+    }
+
+    fn assign(&mut self, bases: u64, bytes: u64) -> usize {
+        let full = match self.split {
+            SplitBy::RoundRobin(k) => {
+                let shard = (self.records % k as u64) as usize;
+                self.records += 1;
+                return shard;
+            }
+            SplitBy::Records(n) => self.records >= n,
+            SplitBy::Bases(n) => self.bases + bases > n,
+            SplitBy::Bytes(n) => self.bytes + bytes > n,
+        };
+
+        if full && self.records > 0 {
+            self.shard += 1;
+            self.records = 0;
+            self.bases = 0;
+            self.bytes = 0;
+        }
+        self.records += 1;
+        self.bases += bases;
+        self.bytes += bytes;
+        self.shard
+    }
+
+    fn keeps_shards_open(&self) -> bool {
+        matches!(self.split, SplitBy::RoundRobin(_))
+    }
+}
+
+/// One output stream of a split: a path template and its open shards.
+struct ShardFiles {
+    template: String,
+    pad: usize,
+    compression: OutputCompression,
+    format: FastqFormat,
+    writers: Vec<Option<FastqWriter<File>>>,
+    paths: Vec<PathBuf>,
+}
+
+impl ShardFiles {
+    fn new(template: String) -> Result<Self> {
+        if !template.contains("{}") {
+            return Err(FastqError::InvalidOutputTemplate { template });
+        }
+        let compression = OutputCompression::from_path(template.replace("{}", ""))?;
+        Ok(ShardFiles {
+            template,
+            pad: 3,
+            compression,
+            format: FastqFormat::default(),
+            writers: Vec::new(),
+            paths: Vec::new(),
+        })
+    }
+
+    fn writer(&mut self, shard: usize, keep_open: bool) -> Result<&mut FastqWriter<File>> {
+        while self.writers.len() <= shard {
+            if !keep_open {
+                if let Some(previous) = self.writers.last_mut().and_then(Option::take) {
+                    previous.finish()?;
+                }
+            }
+            let number = format!("{:0width$}", self.writers.len() + 1, width = self.pad);
+            let path = PathBuf::from(self.template.replace("{}", &number));
+            let writer =
+                FastqWriter::to_file_with(&path, self.compression)?.format(self.format.clone());
+            self.writers.push(Some(writer));
+            self.paths.push(path);
+        }
+        Ok(self.writers[shard]
+            .as_mut()
+            .expect("shard already finished"))
+    }
+
+    fn finish(self) -> Result<Vec<PathBuf>> {
+        for writer in self.writers.into_iter().flatten() {
+            writer.finish()?;
+        }
+        Ok(self.paths)
+    }
+}
+
+/// Writes records across numbered files, like `seqkit split2`.
+///
+/// The template names each shard by replacing `{}` with its 1-based number,
+/// zero-padded to three digits: `"out/part_{}.fastq.gz"` gives
+/// `out/part_001.fastq.gz`, `out/part_002.fastq.gz`, and so on. Its
+/// extension selects compression as in [`FastqWriter::to_file`]. Files are
+/// created as the first record reaches them, so no empty shards are left.
+pub struct SplitWriter {
+    sharder: Sharder,
+    files: ShardFiles,
+}
+
+impl SplitWriter {
+    pub fn new<S: Into<String>>(template: S, split: SplitBy) -> Result<Self> {
+        Ok(SplitWriter {
+            sharder: Sharder::new(split),
+            files: ShardFiles::new(template.into())?,
+        })
+    }
+
+    /// Digits in the shard number (default 3).
+    pub fn pad(mut self, width: usize) -> Self {
+        self.files.pad = width;
+        self
+    }
+
+    pub fn compression(mut self, compression: OutputCompression) -> Self {
+        self.files.compression = compression;
+        self
+    }
+
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        self.files.format = format;
+        self
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        let bytes = self.files.format.encoded_len(record, None);
+        let shard = self.sharder.assign(record.len() as u64, bytes as u64);
+        let keep_open = self.sharder.keeps_shards_open();
+        self.files.writer(shard, keep_open)?.write_record(record)
+    }
+
+    pub fn write_owned_record(&mut self, record: &OwnedRecord) -> Result<()> {
+        self.write_record(&record.as_record())
+    }
+
+    /// Paths of the shards created so far.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.files.paths
+    }
+
+    /// Finishes every open shard and returns all shard paths in order.
+    pub fn finish(self) -> Result<Vec<PathBuf>> {
+        self.files.finish()
+    }
+}
+
+/// Splits paired reads into R1 and R2 shards that stay in step: pair `i`
+/// lands in shard `n` of both outputs, so `R1_003` and `R2_003` hold the
+/// same pairs in the same order. Size limits apply to each pair as a whole
+/// (R1 plus R2).
+pub struct PairedSplitWriter {
+    sharder: Sharder,
+    files: [ShardFiles; 2],
+}
+
+impl PairedSplitWriter {
+    pub fn new<S: Into<String>>(r1_template: S, r2_template: S, split: SplitBy) -> Result<Self> {
+        Ok(PairedSplitWriter {
+            sharder: Sharder::new(split),
+            files: [
+                ShardFiles::new(r1_template.into())?,
+                ShardFiles::new(r2_template.into())?,
+            ],
+        })
+    }
+
+    pub fn pad(mut self, width: usize) -> Self {
+        for files in &mut self.files {
+            files.pad = width;
+        }
+        self
+    }
+
+    pub fn compression(mut self, compression: OutputCompression) -> Self {
+        for files in &mut self.files {
+            files.compression = compression;
+        }
+        self
+    }
+
+    /// Output layout for both mates. A mate suffix becomes `/1` or `/2`.
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        for files in &mut self.files {
+            files.format = format.clone();
+        }
+        self
+    }
+
+    pub fn write_pair(&mut self, r1: &Record, r2: &Record) -> Result<()> {
+        let bases = (r1.len() + r2.len()) as u64;
+        let bytes = self.files[0].format.encoded_len(r1, Some(1))
+            + self.files[1].format.encoded_len(r2, Some(2));
+        let shard = self.sharder.assign(bases, bytes as u64);
+        let keep_open = self.sharder.keeps_shards_open();
+
+        self.files[0].writer(shard, keep_open)?.write_mate(r1, 1)?;
+        self.files[1].writer(shard, keep_open)?.write_mate(r2, 2)
+    }
+
+    pub fn write_owned_pair(&mut self, r1: &OwnedRecord, r2: &OwnedRecord) -> Result<()> {
+        self.write_pair(&r1.as_record(), &r2.as_record())
+    }
+
+    /// Paths of the R1 and R2 shards created so far.
+    pub fn paths(&self) -> (&[PathBuf], &[PathBuf]) {
+        (&self.files[0].paths, &self.files[1].paths)
+    }
+
+    /// Finishes every open shard and returns the R1/R2 path pairs in order.
+    pub fn finish(self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let [r1, r2] = self.files;
+        let r1 = r1.finish()?;
+        let r2 = r2.finish()?;
+        Ok(r1.into_iter().zip(r2).collect())
+    }
+}
+
+/// Records and bases written to one partition of a [`PartitionedWriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionSummary {
+    pub key: Vec<u8>,
+    pub path: PathBuf,
+    pub records: u64,
+    pub bases: u64,
+}
+
+struct Partition {
+    path: PathBuf,
+    writer: Option<FastqWriter<File>>,
+    created: bool,
+    last_used: u64,
+    records: u64,
+    bases: u64,
+}
+
+/// Routes records to one file per key, such as lane, tile, sample or
+/// barcode.
+///
+/// The template names each partition by replacing `{}` with its key.
+/// Path separators, control characters, `%` and invalid UTF-8 in the key
+/// are percent-encoded, so distinct keys always get distinct files. Files
+/// are opened when their first record arrives. At most `max_open` files are
+/// open at once. When another is needed, the least recently used one is
+/// finished and later reopened in append mode. Compressed outputs then hold
+/// several concatenated streams, which gzip, zstd, bzip2 and xz readers
+/// all accept.
+pub struct PartitionedWriter {
+    prefix: OsString,
+    suffix: OsString,
+    extractor: Option<KeyExtractor>,
+    unassigned_key: Option<Vec<u8>>,
+    compression: OutputCompression,
+    format: FastqFormat,
+    max_open: usize,
+    partitions: HashMap<Vec<u8>, Partition>,
+    lru: BTreeMap<u64, Vec<u8>>,
+    tick: u64,
+    unassigned: u64,
+}
+
+impl PartitionedWriter {
+    pub fn new<S: Into<String>>(template: S) -> Result<Self> {
+        let template = template.into();
+        match template.split_once("{}") {
+            Some((prefix, suffix)) => Self::with_affixes(prefix, suffix),
+            None => Err(FastqError::InvalidOutputTemplate { template }),
+        }
+    }
+
+    /// Names each partition `prefix`, then its key, then `suffix`. Unlike
+    /// [`new`](Self::new), the parts are used verbatim, so they may hold
+    /// non-UTF-8 paths or a literal `{}`.
+    pub fn with_affixes<P: Into<OsString>, S: Into<OsString>>(
+        prefix: P,
+        suffix: S,
+    ) -> Result<Self> {
+        let (prefix, suffix) = (prefix.into(), suffix.into());
+        let mut bare = prefix.clone();
+        bare.push(&suffix);
+        let compression = OutputCompression::from_path(bare)?;
+        Ok(PartitionedWriter {
+            prefix,
+            suffix,
+            extractor: None,
+            unassigned_key: None,
+            compression,
+            format: FastqFormat::default(),
+            max_open: DEFAULT_MAX_OPEN,
+            partitions: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            unassigned: 0,
+        })
+    }
+
+    /// How [`write_record`](Self::write_record) finds each record's key.
+    pub fn key(mut self, extractor: KeyExtractor) -> Self {
+        self.extractor = Some(extractor);
+        self
+    }
+
+    /// Sends records without a key to this partition instead of dropping
+    /// them.
+    pub fn unassigned<K: Into<Vec<u8>>>(mut self, key: K) -> Self {
+        self.unassigned_key = Some(key.into());
+        self
+    }
+
+    /// Most files kept open at once (default 256).
+    pub fn max_open(mut self, max_open: usize) -> Self {
+        self.max_open = max_open.max(1);
+        self
+    }
+
+    pub fn compression(mut self, compression: OutputCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Writes `record` to the partition named by the key extractor. Returns
+    /// `false` if it had no key and no `unassigned` partition is set, in
+    /// which case it is only counted.
+    pub fn write_record(&mut self, record: &Record) -> Result<bool> {
+        let key = self.extractor.as_ref().and_then(|e| e.extract(record));
+        match key.or_else(|| self.unassigned_key.clone()) {
+            Some(key) => {
+                self.write_keyed(&key, record)?;
+                Ok(true)
+            }
+            None => {
+                self.unassigned += 1;
+                Ok(false)
+            }
+        }
+    }
+
+    pub fn write_owned_record(&mut self, record: &OwnedRecord) -> Result<bool> {
+        self.write_record(&record.as_record())
+    }
+
+    /// Writes `record` to the partition for `key`, bypassing the extractor.
+    pub fn write_keyed(&mut self, key: &[u8], record: &Record) -> Result<()> {
+        let partition = self.partition(key)?;
+        partition.records += 1;
+        partition.bases += record.len() as u64;
+        partition.writer.as_mut().unwrap().write_record(record)
+    }
+
+    /// Creates the file for `key` now, so it exists even if no record is
+    /// ever routed to it.
+    pub fn open_partition(&mut self, key: &[u8]) -> Result<()> {
+        self.partition(key).map(|_| ())
+    }
+
+    /// Records without a key that were dropped.
+    pub fn unassigned_count(&self) -> u64 {
+        self.unassigned
+    }
+
+    /// Number of files currently open.
+    pub fn open_count(&self) -> usize {
+        self.lru.len()
+    }
+
+    /// Per-partition counts, sorted by key.
+    pub fn summary(&self) -> Vec<PartitionSummary> {
+        let mut summary: Vec<_> = self
+            .partitions
+            .iter()
+            .map(|(key, partition)| PartitionSummary {
+                key: key.clone(),
+                path: partition.path.clone(),
+                records: partition.records,
+                bases: partition.bases,
+            })
+            .collect();
+        summary.sort_by(|a, b| a.key.cmp(&b.key));
+        summary
+    }
+
+    /// Finishes every open file and returns the per-partition counts.
+    pub fn finish(mut self) -> Result<Vec<PartitionSummary>> {
+        let summary = self.summary();
+        for partition in self.partitions.values_mut() {
+            if let Some(writer) = partition.writer.take() {
+                writer.finish()?;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Returns the open partition for `key`, opening it (and closing the
+    /// least recently used file if at the limit) as needed.
+    fn partition(&mut self, key: &[u8]) -> Result<&mut Partition> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        let is_open = match self.partitions.get(key) {
+            Some(partition) => {
+                self.lru.remove(&partition.last_used);
+                partition.writer.is_some()
+            }
+            None => {
+                let mut path = self.prefix.clone();
+                path.push(file_key(key));
+                path.push(&self.suffix);
+                let path = PathBuf::from(path);
+                self.partitions.insert(
+                    key.to_vec(),
+                    Partition {
+                        path,
+                        writer: None,
+                        created: false,
+                        last_used: 0,
+                        records: 0,
+                        bases: 0,
+                    },
+                );
+                false
+            }
+        };
+
+        if !is_open {
+            while self.lru.len() >= self.max_open {
+                let (_, evicted) = self.lru.pop_first().unwrap();
+                let writer = self.partitions.get_mut(&evicted).unwrap().writer.take();
+                writer.unwrap().finish()?;
+            }
+        }
+
+        let partition = self.partitions.get_mut(key).unwrap();
+        if !is_open {
+            let file = open_partition_file(&partition.path, partition.created)?;
+            partition.created = true;
+            partition.writer = Some(
+                FastqWriter::with_compression(file, self.compression)?.format(self.format.clone()),
+            );
+        }
+        partition.last_used = tick;
+        self.lru.insert(tick, key.to_vec());
+        Ok(partition)
+    }
+}
+
+fn open_partition_file(path: &Path, reopen: bool) -> Result<File> {
+    if reopen {
+        Ok(OpenOptions::new().append(true).open(path)?)
+    } else {
+        Ok(File::create(path)?)
+    }
+}
+
+/// Makes a key safe to use as part of a file name by percent-encoding
+/// path separators, control characters, `%` itself, invalid UTF-8 and a
+/// leading `.`, so that `.` and `..` never name a directory. The encoding
+/// is reversible, so distinct keys never share a file.
+fn file_key(key: &[u8]) -> String {
+    use std::fmt::Write;
+
+    let mut name = String::with_capacity(key.len());
+    for chunk in key.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            let leading_dot = c == '.' && name.is_empty();
+            if c == '/' || c == '\\' || c == '%' || c.is_control() || leading_dot {
+                for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                    write!(name, "%{:02X}", byte).unwrap();
+                }
+            } else {
+                name.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(name, "%{:02X}", byte).unwrap();
+        }
+    }
+    name
+}
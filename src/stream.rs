@@ -1,4 +1,8 @@
-use crate::{error::Result, parser::Parser, record::OwnedRecord};
+use crate::{
+    error::Result,
+    parser::Parser,
+    record::{OwnedRecord, RawRecord},
+};
 use std::collections::VecDeque;
 use std::io::{BufRead, BufReader, Read};
 
@@ -8,9 +12,10 @@ const MIN_BUFFER_RESERVE: usize = 1024 * 1024;
 pub struct StreamingReader<R: Read> {
     reader: BufReader<R>,
     buffer: Vec<u8>,
-    records_buffer: VecDeque<OwnedRecord>,
+    records_buffer: VecDeque<RawRecord>,
     position: usize,
     eof: bool,
+    keep_raw: bool,
 }
 
 impl<R: Read> StreamingReader<R> {
@@ -25,22 +30,40 @@ impl<R: Read> StreamingReader<R> {
             records_buffer: VecDeque::with_capacity(100),
             position: 0,
             eof: false,
+            keep_raw: false,
         }
     }
 
+    /// Also copy each record's original bytes, for verbatim output with
+    /// [`FastqWriter::write_owned_raw`](crate::writer::FastqWriter::write_owned_raw).
+    /// They are returned by [`next_raw_record`](Self::next_raw_record) and
+    /// [`raw_records`](Self::raw_records); plain iteration drops them.
+    pub fn keep_raw(mut self, keep: bool) -> Self {
+        self.keep_raw = keep;
+        self
+    }
+
     pub fn next_record(&mut self) -> Result<Option<OwnedRecord>> {
-        if !self.records_buffer.is_empty() {
-            return Ok(self.records_buffer.pop_front());
-        }
+        Ok(self.next_raw_record()?.map(RawRecord::into_record))
+    }
 
-        if self.eof && self.position >= self.buffer.len() {
-            return Ok(None);
-        }
+    /// Like [`next_record`](Self::next_record), but keeps the original
+    /// bytes when [`keep_raw`](Self::keep_raw) is set.
+    pub fn next_raw_record(&mut self) -> Result<Option<RawRecord>> {
+        loop {
+            if let Some(record) = self.records_buffer.pop_front() {
+                return Ok(Some(record));
+            }
 
-        self.fill_buffer()?;
-        self.parse_buffer()?;
+            if self.eof && self.position >= self.buffer.len() {
+                return Ok(None);
+            }
 
-        Ok(self.records_buffer.pop_front())
+            // A read may end mid-record; keep filling until a complete
+            // record is available or the input is exhausted.
+            self.fill_buffer()?;
+            self.parse_buffer()?;
+        }
     }
 
     fn fill_buffer(&mut self) -> Result<()> {
@@ -88,8 +111,11 @@ impl<R: Read> StreamingReader<R> {
         let mut parser = Parser::new(parse_slice);
 
         while let Some(record) = parser.parse_record()? {
-            self.records_buffer
-                .push_back(OwnedRecord::from_record(&record));
+            self.records_buffer.push_back(if self.keep_raw {
+                RawRecord::from_record(&record)
+            } else {
+                RawRecord::from(OwnedRecord::from_record(&record))
+            });
         }
 
         self.position = last_complete;
@@ -125,6 +151,26 @@ impl<R: Read> Iterator for StreamingReader<R> {
     }
 }
 
+impl<R: Read> StreamingReader<R> {
+    /// Iterates over the records with their original bytes, which are only
+    /// present when [`keep_raw`](Self::keep_raw) is set.
+    pub fn raw_records(self) -> RawRecords<R> {
+        RawRecords { reader: self }
+    }
+}
+
+pub struct RawRecords<R: Read> {
+    reader: StreamingReader<R>,
+}
+
+impl<R: Read> Iterator for RawRecords<R> {
+    type Item = Result<RawRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_raw_record().transpose()
+    }
+}
+
 pub struct AsyncStreamingReader<R: Read + Send> {
     reader: R,
     buffer_size: usize,
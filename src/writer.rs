@@ -1,61 +1,251 @@
 use crate::{
+    compress::{Encoder, OutputCompression, ParallelGzip},
     error::Result,
-    record::{OwnedRecord, Record},
+    paired::{InterleavedReader, PairMatching, PairedEndReader},
+    record::{OwnedRecord, RawRecord, Record},
 };
-use flate2::write::GzEncoder;
 use flate2::Compression;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Layout of FASTQ output. The default is the usual four-line record:
+/// `@id desc`, sequence, `+`, quality, each ended by `\n`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FastqFormat {
+    repeat_id: bool,
+    line_width: Option<usize>,
+    crlf: bool,
+    strip_comments: bool,
+    mate_suffix: Option<u8>,
+}
+
+impl FastqFormat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Repeats the header after `+`, as older Illumina pipelines did.
+    pub fn repeat_id(mut self, repeat: bool) -> Self {
+        self.repeat_id = repeat;
+        self
+    }
+
+    /// Wraps sequence and quality lines at `width` characters. `None` or
+    /// `Some(0)` writes each on a single line.
+    pub fn line_width(mut self, width: Option<usize>) -> Self {
+        self.line_width = width.filter(|&w| w > 0);
+        self
+    }
+
+    /// Ends lines with `\r\n` instead of `\n`.
+    pub fn crlf(mut self, crlf: bool) -> Self {
+        self.crlf = crlf;
+        self
+    }
+
+    /// Drops the description after the first space of the header.
+    pub fn strip_comments(mut self, strip: bool) -> Self {
+        self.strip_comments = strip;
+        self
+    }
+
+    /// Appends `/1` or `/2` to each ID unless it already ends that way.
+    /// Paired writers use the number of the mate being written instead.
+    pub fn mate_suffix(mut self, mate: Option<u8>) -> Self {
+        self.mate_suffix = mate;
+        self
+    }
+
+    /// Formats `record` with these options for `{}`, without the final
+    /// line ending.
+    pub fn display<'a>(&'a self, record: &'a Record<'a>) -> impl fmt::Display + 'a {
+        struct Formatted<'a>(&'a FastqFormat, &'a Record<'a>);
+
+        impl fmt::Display for Formatted<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.emit(self.1, None, false, |bytes| {
+                    f.write_str(std::str::from_utf8(bytes).map_err(|_| fmt::Error)?)
+                })
+            }
+        }
+
+        Formatted(self, record)
+    }
 
-pub enum FastqWriter<W: Write> {
-    Plain(BufWriter<W>),
-    Gzip(GzEncoder<BufWriter<W>>),
+    /// Feeds the formatted record to `put` piece by piece, so the same
+    /// code serves `io::Write` and `fmt::Formatter` targets. `mate`
+    /// replaces the number of a configured mate suffix.
+    pub(crate) fn emit<E>(
+        &self,
+        record: &Record,
+        mate: Option<u8>,
+        terminate: bool,
+        mut put: impl FnMut(&[u8]) -> std::result::Result<(), E>,
+    ) -> std::result::Result<(), E> {
+        let eol: &[u8] = if self.crlf { b"\r\n" } else { b"\n" };
+        let suffix = self
+            .mate_suffix
+            .map(|default| [b'/', b'0' + mate.unwrap_or(default) % 10])
+            .filter(|suffix| !record.id().ends_with(suffix));
+        let desc = record.desc().filter(|_| !self.strip_comments);
+
+        let header = |put: &mut dyn FnMut(&[u8]) -> std::result::Result<(), E>| {
+            put(record.id())?;
+            if let Some(suffix) = &suffix {
+                put(suffix)?;
+            }
+            if let Some(desc) = desc {
+                put(b" ")?;
+                put(desc)?;
+            }
+            Ok(())
+        };
+
+        put(b"@")?;
+        header(&mut put)?;
+        put(eol)?;
+        self.emit_wrapped(record.seq(), eol, &mut put)?;
+        put(eol)?;
+        put(b"+")?;
+        if self.repeat_id {
+            header(&mut put)?;
+        }
+        put(eol)?;
+        self.emit_wrapped(record.qual(), eol, &mut put)?;
+        if terminate {
+            put(eol)?;
+        }
+        Ok(())
+    }
+
+    /// Number of bytes `emit` produces for a terminated record.
+    pub(crate) fn encoded_len(&self, record: &Record, mate: Option<u8>) -> usize {
+        let mut len = 0;
+        let _ = self.emit(record, mate, true, |bytes| {
+            len += bytes.len();
+            Ok::<(), std::convert::Infallible>(())
+        });
+        len
+    }
+
+    fn emit_wrapped<E>(
+        &self,
+        field: &[u8],
+        eol: &[u8],
+        put: &mut impl FnMut(&[u8]) -> std::result::Result<(), E>,
+    ) -> std::result::Result<(), E> {
+        match self.line_width {
+            Some(width) if field.len() > width => {
+                for (i, line) in field.chunks(width).enumerate() {
+                    if i > 0 {
+                        put(eol)?;
+                    }
+                    put(line)?;
+                }
+                Ok(())
+            }
+            _ => put(field),
+        }
+    }
+}
+
+/// Dropping a writer flushes it on a best-effort basis and discards any
+/// error. Call [`FastqWriter::finish`] to complete the output and see
+/// whether it was written in full.
+pub struct FastqWriter<W: Write> {
+    writer: Encoder<W>,
+    format: FastqFormat,
 }
 
 impl FastqWriter<File> {
+    /// Creates `path`, choosing compression from its extension with
+    /// [`OutputCompression::from_path`].
     pub fn to_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref();
+        Self::to_file_with(path, OutputCompression::from_path(path)?)
+    }
+
+    pub fn to_file_with<P: AsRef<Path>>(path: P, compression: OutputCompression) -> Result<Self> {
         let file = File::create(path)?;
+        FastqWriter::with_compression(file, compression)
+    }
 
-        if path.extension().and_then(|s| s.to_str()) == Some("gz") {
-            Ok(FastqWriter::Gzip(GzEncoder::new(
-                BufWriter::new(file),
-                Compression::default(),
-            )))
-        } else {
-            Ok(FastqWriter::Plain(BufWriter::new(file)))
-        }
+    /// Finishes the output and fsyncs the file before returning it.
+    pub fn finish_sync(self) -> Result<File> {
+        let file = self.finish()?;
+        file.sync_all()?;
+        Ok(file)
+    }
+}
+
+impl FastqWriter<AtomicFile> {
+    /// Writes to a temporary file next to `path`, which replaces `path`
+    /// only when [`commit`](Self::commit) succeeds. The extension selects
+    /// compression as in [`FastqWriter::to_file`].
+    pub fn to_file_atomic<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let compression = OutputCompression::from_path(path)?;
+        FastqWriter::with_compression(AtomicFile::create(path)?, compression)
+    }
+
+    /// Finishes the output, fsyncs it and renames it into place.
+    pub fn commit(self) -> Result<()> {
+        self.finish()?.commit()
     }
 }
 
 impl<W: Write> FastqWriter<W> {
     pub fn new(writer: W) -> Self {
-        FastqWriter::Plain(BufWriter::new(writer))
+        FastqWriter {
+            writer: Encoder::new(writer, OutputCompression::None).unwrap(),
+            format: FastqFormat::default(),
+        }
     }
 
     pub fn new_gzip(writer: W, compression: Compression) -> Self {
-        FastqWriter::Gzip(GzEncoder::new(BufWriter::new(writer), compression))
+        FastqWriter {
+            writer: Encoder::new(writer, OutputCompression::Gzip(compression)).unwrap(),
+            format: FastqFormat::default(),
+        }
     }
 
-    pub fn write_record(&mut self, record: &Record) -> Result<()> {
-        let writer: &mut dyn Write = match self {
-            FastqWriter::Plain(w) => w,
-            FastqWriter::Gzip(w) => w,
-        };
-
-        writer.write_all(b"@")?;
-        writer.write_all(record.id())?;
-        if let Some(desc) = record.desc() {
-            writer.write_all(b" ")?;
-            writer.write_all(desc)?;
+    /// Compresses blocks on a thread pool; see
+    /// [`ParallelGzEncoder`](crate::compress::ParallelGzEncoder).
+    pub fn new_parallel_gzip(writer: W, options: ParallelGzip) -> Self {
+        FastqWriter {
+            writer: Encoder::new(writer, OutputCompression::ParallelGzip(options)).unwrap(),
+            format: FastqFormat::default(),
         }
-        writer.write_all(b"\n")?;
-        writer.write_all(record.seq())?;
-        writer.write_all(b"\n+\n")?;
-        writer.write_all(record.qual())?;
-        writer.write_all(b"\n")?;
+    }
+
+    pub fn with_compression(writer: W, compression: OutputCompression) -> Result<Self> {
+        Ok(FastqWriter {
+            writer: Encoder::new(writer, compression)?,
+            format: FastqFormat::default(),
+        })
+    }
+
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        let writer = self.writer.as_write();
+        self.format
+            .emit(record, None, true, |bytes| writer.write_all(bytes))?;
+        Ok(())
+    }
 
+    /// Writes `record` as mate 1 or 2, so a configured mate suffix gets
+    /// that number.
+    pub(crate) fn write_mate(&mut self, record: &Record, mate: u8) -> Result<()> {
+        let writer = self.writer.as_write();
+        self.format
+            .emit(record, Some(mate), true, |bytes| writer.write_all(bytes))?;
         Ok(())
     }
 
@@ -63,58 +253,161 @@ impl<W: Write> FastqWriter<W> {
         self.write_record(&record.as_record())
     }
 
-    pub fn flush(&mut self) -> Result<()> {
-        match self {
-            FastqWriter::Plain(w) => w.flush()?,
-            FastqWriter::Gzip(w) => w.flush()?,
+    /// Copies the bytes `record` was parsed from, keeping its original
+    /// line wrapping and `+` line, and ignoring the configured format.
+    /// Records without raw bytes, such as trimmed ones, are written with
+    /// [`write_record`](Self::write_record) instead.
+    pub fn write_raw(&mut self, record: &Record) -> Result<()> {
+        let Some(raw) = record.raw() else {
+            return self.write_record(record);
+        };
+        let writer = self.writer.as_write();
+        writer.write_all(raw)?;
+        // The last record of a file may have no line terminator.
+        if !raw.ends_with(b"\n") {
+            let eol: &[u8] = if self.format.crlf { b"\r\n" } else { b"\n" };
+            writer.write_all(eol)?;
         }
         Ok(())
     }
+
+    /// [`write_raw`](Self::write_raw) for a record read with
+    /// [`StreamingReader::keep_raw`](crate::stream::StreamingReader::keep_raw).
+    pub fn write_owned_raw(&mut self, record: &RawRecord) -> Result<()> {
+        self.write_raw(&record.as_record())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.as_write().flush()?;
+        Ok(())
+    }
+
+    /// Flushes buffered data, writes the compression trailer if any, and
+    /// returns the underlying writer.
+    pub fn finish(self) -> Result<W> {
+        self.writer.finish()
+    }
 }
 
-impl<W: Write> Drop for FastqWriter<W> {
-    fn drop(&mut self) {
-        let _ = self.flush();
+pub struct InterleavedWriter<W: Write> {
+    writer: FastqWriter<W>,
+}
+
+impl InterleavedWriter<File> {
+    pub fn to_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(InterleavedWriter {
+            writer: FastqWriter::to_file(path)?,
+        })
+    }
+}
+
+impl<W: Write> InterleavedWriter<W> {
+    pub fn new(writer: W) -> Self {
+        InterleavedWriter {
+            writer: FastqWriter::new(writer),
+        }
+    }
+
+    pub fn from_writer(writer: FastqWriter<W>) -> Self {
+        InterleavedWriter { writer }
+    }
+
+    /// Sets the output layout. A mate suffix becomes `/1` on R1 and `/2`
+    /// on R2.
+    pub fn format(mut self, format: FastqFormat) -> Self {
+        self.writer.format = format;
+        self
+    }
+
+    pub fn write_pair(&mut self, r1: &Record, r2: &Record) -> Result<()> {
+        self.writer.write_mate(r1, 1)?;
+        self.writer.write_mate(r2, 2)
+    }
+
+    pub fn write_owned_pair(&mut self, r1: &OwnedRecord, r2: &OwnedRecord) -> Result<()> {
+        self.write_pair(&r1.as_record(), &r2.as_record())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn finish(self) -> Result<W> {
+        self.writer.finish()
     }
 }
 
 pub struct FastaWriter<W: Write> {
-    writer: BufWriter<W>,
+    writer: Encoder<W>,
     line_width: usize,
 }
 
 impl FastaWriter<File> {
+    /// Creates `path`, choosing compression from its extension with
+    /// [`OutputCompression::from_path`].
     pub fn to_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::to_file_with(path, OutputCompression::from_path(path)?)
+    }
+
+    pub fn to_file_with<P: AsRef<Path>>(path: P, compression: OutputCompression) -> Result<Self> {
         let file = File::create(path)?;
-        Ok(FastaWriter::new(file))
+        FastaWriter::with_compression(file, compression)
+    }
+
+    pub fn finish_sync(self) -> Result<File> {
+        let file = self.finish()?;
+        file.sync_all()?;
+        Ok(file)
+    }
+}
+
+impl FastaWriter<AtomicFile> {
+    pub fn to_file_atomic<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let compression = OutputCompression::from_path(path)?;
+        FastaWriter::with_compression(AtomicFile::create(path)?, compression)
+    }
+
+    pub fn commit(self) -> Result<()> {
+        self.finish()?.commit()
     }
 }
 
 impl<W: Write> FastaWriter<W> {
     pub fn new(writer: W) -> Self {
         FastaWriter {
-            writer: BufWriter::new(writer),
+            writer: Encoder::new(writer, OutputCompression::None).unwrap(),
             line_width: 80,
         }
     }
 
+    pub fn with_compression(writer: W, compression: OutputCompression) -> Result<Self> {
+        Ok(FastaWriter {
+            writer: Encoder::new(writer, compression)?,
+            line_width: 80,
+        })
+    }
+
     pub fn line_width(mut self, width: usize) -> Self {
         self.line_width = width;
         self
     }
 
     pub fn write_record(&mut self, record: &Record) -> Result<()> {
-        self.writer.write_all(b">")?;
-        self.writer.write_all(record.id())?;
+        let writer = self.writer.as_write();
+
+        writer.write_all(b">")?;
+        writer.write_all(record.id())?;
         if let Some(desc) = record.desc() {
-            self.writer.write_all(b" ")?;
-            self.writer.write_all(desc)?;
+            writer.write_all(b" ")?;
+            writer.write_all(desc)?;
         }
-        self.writer.write_all(b"\n")?;
+        writer.write_all(b"\n")?;
 
         for chunk in record.seq().chunks(self.line_width) {
-            self.writer.write_all(chunk)?;
-            self.writer.write_all(b"\n")?;
+            writer.write_all(chunk)?;
+            writer.write_all(b"\n")?;
         }
 
         Ok(())
@@ -125,20 +418,142 @@ impl<W: Write> FastaWriter<W> {
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        self.writer.flush()?;
+        self.writer.as_write().flush()?;
         Ok(())
     }
+
+    pub fn finish(self) -> Result<W> {
+        self.writer.finish()
+    }
+}
+
+static ATOMIC_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A file written under a temporary name in the destination directory and
+/// renamed over the destination by [`commit`](AtomicFile::commit).
+///
+/// Dropping an uncommitted `AtomicFile`, including on an error path,
+/// removes the temporary file and leaves any existing destination intact.
+pub struct AtomicFile {
+    file: Option<File>,
+    temp_path: PathBuf,
+    path: PathBuf,
 }
 
-impl<W: Write> Drop for FastaWriter<W> {
+impl AtomicFile {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+            .to_string_lossy()
+            .into_owned();
+
+        loop {
+            let temp_path = dir.join(format!(
+                ".{}.{}-{}.tmp",
+                name,
+                std::process::id(),
+                ATOMIC_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&temp_path)
+            {
+                Ok(file) => {
+                    return Ok(AtomicFile {
+                        file: Some(file),
+                        temp_path,
+                        path,
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// The final destination.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the data is written until the file is committed.
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Fsyncs the temporary file and renames it to the destination.
+    pub fn commit(mut self) -> Result<()> {
+        if let Some(file) = self.file.take() {
+            file.sync_all()?;
+        }
+        fs::rename(&self.temp_path, &self.path)?;
+        self.temp_path = PathBuf::new();
+
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            let _ = File::open(dir).and_then(|d| d.sync_all());
+        }
+        Ok(())
+    }
+
+    fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("AtomicFile used after commit")
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.file().write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file().flush()
+    }
+}
+
+impl Drop for AtomicFile {
     fn drop(&mut self) {
-        let _ = self.flush();
+        if !self.temp_path.as_os_str().is_empty() {
+            drop(self.file.take());
+            let _ = fs::remove_file(&self.temp_path);
+        }
     }
 }
 
 pub struct FormatConverter;
 
 impl FormatConverter {
+    /// Rewrites a FASTQ file with the layout given by `format`, e.g. to
+    /// feed a tool that needs the ID after `+` or wrapped lines. Returns
+    /// the number of records written.
+    pub fn reformat<P: AsRef<Path>>(input: P, output: P, format: &FastqFormat) -> Result<usize> {
+        use crate::reader::FastqReader;
+
+        let reader = FastqReader::from_path(input)?;
+        let mut writer = FastqWriter::to_file(output)?.format(format.clone());
+        let mut count = 0;
+
+        for record in reader.into_records() {
+            let record = record?;
+            writer.write_owned_record(&record)?;
+            count += 1;
+        }
+
+        writer.finish()?;
+        Ok(count)
+    }
+
     pub fn fastq_to_fasta<P: AsRef<Path>>(input: P, output: P) -> Result<usize> {
         use crate::reader::FastqReader;
 
@@ -152,7 +567,7 @@ impl FormatConverter {
             count += 1;
         }
 
-        writer.flush()?;
+        writer.finish()?;
         Ok(count)
     }
 
@@ -181,9 +596,56 @@ impl FormatConverter {
             }
         }
 
-        writer.flush()?;
+        writer.finish()?;
         Ok((total, passed))
     }
+
+    /// Merges split R1/R2 files into one interleaved file, checking mates
+    /// with `matching`. Returns the number of pairs written.
+    pub fn interleave<P: AsRef<Path>>(
+        r1: P,
+        r2: P,
+        output: P,
+        matching: PairMatching,
+    ) -> Result<usize> {
+        let reader = PairedEndReader::from_paths(r1, r2)?.matching(matching);
+        let mut writer = InterleavedWriter::to_file(output)?;
+        let mut count = 0;
+
+        for pair in reader.into_paired_records() {
+            let (r1, r2) = pair?;
+            writer.write_owned_pair(&r1, &r2)?;
+            count += 1;
+        }
+
+        writer.finish()?;
+        Ok(count)
+    }
+
+    /// Splits an interleaved file into R1/R2 files, checking mates with
+    /// `matching`. Returns the number of pairs written.
+    pub fn deinterleave<P: AsRef<Path>>(
+        input: P,
+        r1: P,
+        r2: P,
+        matching: PairMatching,
+    ) -> Result<usize> {
+        let reader = InterleavedReader::from_path(input)?.matching(matching);
+        let mut r1_writer = FastqWriter::to_file(r1)?;
+        let mut r2_writer = FastqWriter::to_file(r2)?;
+        let mut count = 0;
+
+        for pair in reader.into_paired_records() {
+            let (r1, r2) = pair?;
+            r1_writer.write_owned_record(&r1)?;
+            r2_writer.write_owned_record(&r2)?;
+            count += 1;
+        }
+
+        r1_writer.finish()?;
+        r2_writer.finish()?;
+        Ok(count)
+    }
 }
 
 pub struct SubsetExtractor;
@@ -206,7 +668,7 @@ impl SubsetExtractor {
             }
         }
 
-        writer.flush()?;
+        writer.finish()?;
         Ok(count)
     }
 
@@ -235,7 +697,7 @@ impl SubsetExtractor {
             written += 1;
         }
 
-        writer.flush()?;
+        writer.finish()?;
         Ok(written)
     }
 }
@@ -0,0 +1,745 @@
+use fastq_parser::*;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+fn write_temp(data: &[u8]) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(data).unwrap();
+    file
+}
+
+fn numbered_fastq(n: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in (0..n).rev() {
+        writeln!(data, "@READ_{:03} sample\nACGT\n+\nIIII", i).unwrap();
+    }
+    data
+}
+
+#[test]
+fn test_index_keeps_file_order() {
+    let file = write_temp(&numbered_fastq(50));
+    let index = FastqIndex::build(file.path()).unwrap();
+
+    assert_eq!(index.len(), 50);
+    assert_eq!(index.get_by_position(0).unwrap().id, "READ_049");
+    assert_eq!(index.get_by_position(49).unwrap().id, "READ_000");
+    assert!(index.get_by_position(50).is_none());
+    assert_eq!(index.position("READ_040"), Some(9));
+
+    let sorted: Vec<_> = index.sorted_ids().take(2).collect();
+    assert_eq!(sorted, ["READ_000", "READ_001"]);
+
+    let reader = IndexedReader::new(file.path(), index).unwrap();
+    let ids: Vec<_> = reader
+        .iter_range(10, 3)
+        .map(|r| String::from_utf8(r.id).unwrap())
+        .collect();
+    assert_eq!(ids, ["READ_039", "READ_038", "READ_037"]);
+    assert_eq!(reader.iter_range(48, 10).len(), 2);
+    assert_eq!(reader.iter_range(100, 10).count(), 0);
+    assert_eq!(reader.get_by_position(1).unwrap().id(), b"READ_048");
+}
+
+#[test]
+fn test_index_prefix_queries() {
+    let data =
+        b"@lane2_b\nA\n+\nI\n@lane1_b\nCA\nCA\n+\nII\nII\n@lane10\nG\n+\nI\n@lane1_a\nT\n+\nI\n";
+    let file = write_temp(data);
+    let index = FastqIndex::build(file.path()).unwrap();
+
+    let hits: Vec<_> = index.with_prefix("lane1").map(|e| e.id.as_str()).collect();
+    assert_eq!(hits, ["lane10", "lane1_a", "lane1_b"]);
+    assert_eq!(index.with_prefix("lane3").count(), 0);
+
+    let reader = IndexedReader::new(file.path(), index).unwrap();
+    let seqs: Vec<_> = reader.iter_prefix("lane1_").map(|r| r.seq).collect();
+    // lane1_b is wrapped and comes back with its lines joined.
+    assert_eq!(seqs, [b"T".to_vec(), b"CACA".to_vec()]);
+}
+
+#[test]
+fn test_index_duplicate_ids_resolve_to_first() {
+    let file = write_temp(b"@X\nAAAA\n+\nIIII\n@Y\nCCCC\n+\nIIII\n@X\nGGGG\n+\nIIII\n");
+    let index = FastqIndex::build(file.path()).unwrap();
+    assert_eq!(index.position("X"), Some(0));
+
+    let mut reader = RandomAccessReader::new(file.path(), index).unwrap();
+    assert_eq!(reader.get_record("X").unwrap().unwrap().seq, b"AAAA");
+    assert_eq!(reader.get_by_position(2).unwrap().unwrap().seq, b"GGGG");
+    assert!(reader.get_by_position(3).unwrap().is_none());
+}
+
+#[test]
+fn test_index_versioned_format() {
+    let file = write_temp(&numbered_fastq(5));
+    let index = FastqIndex::build(file.path()).unwrap();
+    let saved = NamedTempFile::new().unwrap();
+    index.save(saved.path()).unwrap();
+
+    let bytes = std::fs::read(saved.path()).unwrap();
+    assert_eq!(&bytes[..4], b"FQIX");
+
+    let loaded = FastqIndex::load(saved.path()).unwrap();
+    assert_eq!(loaded.len(), 5);
+    assert_eq!(
+        loaded.get("READ_002").unwrap().offset,
+        index.get("READ_002").unwrap().offset
+    );
+
+    let mut future = bytes.clone();
+    future[4] = 99;
+    let future_file = write_temp(&future);
+    assert!(matches!(
+        FastqIndex::load(future_file.path()),
+        Err(FastqError::UnsupportedIndexVersion { found: 99, .. })
+    ));
+
+    let garbage = write_temp(b"not an index");
+    assert!(matches!(
+        FastqIndex::load(garbage.path()),
+        Err(FastqError::InvalidIndex { .. })
+    ));
+}
+
+fn assert_matches_reader(data: &[u8]) {
+    let file = write_temp(data);
+    let index = FastqIndex::build(file.path()).unwrap();
+    let reader = IndexedReader::new(file.path(), index).unwrap();
+    let mut random =
+        RandomAccessReader::new(file.path(), FastqIndex::build(file.path()).unwrap()).unwrap();
+
+    let expected: Vec<_> = FastqReader::from_path(file.path())
+        .unwrap()
+        .into_records()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(reader.index().len(), expected.len());
+    for (i, expected) in expected.iter().enumerate() {
+        let id = std::str::from_utf8(&expected.id).unwrap();
+        let owned = reader.get_owned_record(id).unwrap();
+        assert_eq!(owned.as_record(), expected.as_record());
+        let owned = random.get_by_position(i).unwrap().unwrap();
+        assert_eq!(owned.as_record(), expected.as_record());
+        // Borrowed records are only available for unwrapped reads.
+        match reader.get_by_position(i) {
+            Some(record) => assert_eq!(record, expected.as_record()),
+            None => assert!(reader.index().get(id).unwrap().line_bases < expected.seq.len()),
+        }
+    }
+}
+
+#[test]
+fn test_index_matches_reader_on_crlf_and_wrapped_records() {
+    assert_matches_reader(b"@A desc\r\nACGT\r\n+\r\nIIII\r\n@B\tx\r\nGG\r\n+\r\nII\r\n");
+    assert_matches_reader(b"@A\nACGTACGT\nACG\n+\nIIIIIIII\nIII\n@B\nTT\n+B\nII\n");
+    assert_matches_reader(b"@A\r\nACGT\r\nAC\r\n+\r\nIIII\r\nII\r\n");
+    assert_matches_reader(b"@A\nACGT\n+\n@III\n");
+
+    let file = write_temp(b"@A\nACGTACGT\nACG\n+\nIIIIIIII\nIII\n");
+    let index = FastqIndex::build(file.path()).unwrap();
+    let entry = index.get("A").unwrap();
+    assert_eq!((entry.seq_offset, entry.seq_length), (3, 12));
+    assert_eq!((entry.qual_offset, entry.qual_length), (18, 12));
+    assert_eq!(entry.length, 31);
+}
+
+#[test]
+fn test_index_without_trailing_newline() {
+    let data = b"@A\nACGT\n+\nIIII\n@B\nTTGG\n+\nJJJJ";
+    assert_matches_reader(data);
+
+    let file = write_temp(data);
+    let reader = IndexedReader::new(file.path(), FastqIndex::build(file.path()).unwrap()).unwrap();
+    assert_eq!(reader.get_record("B").unwrap().qual(), b"JJJJ");
+}
+
+#[test]
+fn test_index_build_reports_line_numbers() {
+    let bad_header = write_temp(b"@A\nACGT\n+\nIIII\nB\nACGT\n+\nIIII\n");
+    assert!(matches!(
+        FastqIndex::build(bad_header.path()),
+        Err(FastqError::InvalidHeader { line: 5 })
+    ));
+
+    let no_separator = write_temp(b"@A\nACGT\n+\nIIII\n@B\nACGT\n-\nIIII\n");
+    assert!(matches!(
+        FastqIndex::build(no_separator.path()),
+        Err(FastqError::UnexpectedEof)
+    ));
+
+    let truncated = write_temp(b"@A\nACGT\n+\nIIII\n@B\nACGT\n+\nII");
+    assert!(matches!(
+        FastqIndex::build(truncated.path()),
+        Err(FastqError::LengthMismatch {
+            seq_len: 4,
+            qual_len: 2
+        })
+    ));
+}
+
+#[test]
+fn test_fai_matches_samtools_layout() {
+    let data = b"@r1\nACGT\n+\nIIII\n@r2 desc\nGGCC\n+\n!!!!\n@w\nACGTAC\nGT\n+\nIIIIII\nII\n";
+    let file = write_temp(data);
+    let index = FastqIndex::build(file.path()).unwrap();
+
+    let fai = NamedTempFile::new().unwrap();
+    index.write_fai(fai.path()).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(fai.path()).unwrap(),
+        "r1\t4\t4\t4\t5\t11\nr2\t4\t25\t4\t5\t32\nw\t8\t40\t6\t7\t52\n"
+    );
+
+    let loaded = FastqIndex::read_fai(file.path(), fai.path()).unwrap();
+    for (built, read) in index.entries().iter().zip(loaded.entries()) {
+        assert_eq!(built.offset, read.offset);
+        assert_eq!(built.length, read.length);
+        assert_eq!(built.seq_length, read.seq_length);
+        assert_eq!(built.qual_length, read.qual_length);
+    }
+
+    let reader = IndexedReader::from_fai(file.path(), fai.path()).unwrap();
+    let parsed: Vec<_> = Parser::new(data).collect();
+    for (i, expected) in parsed.iter().enumerate() {
+        assert_eq!(reader.get_by_position(i).as_ref(), Some(expected));
+    }
+
+    let mut random = RandomAccessReader::from_fai(file.path(), fai.path()).unwrap();
+    assert_eq!(
+        random.get_record("r2").unwrap().unwrap().desc.unwrap(),
+        b"desc"
+    );
+}
+
+#[test]
+fn test_fai_crlf_and_path_convention() {
+    let file = write_temp(b"@c1\r\nACGTA\r\nCG\r\n+\r\nIIIII\r\nII\r\n");
+    let index = FastqIndex::build(file.path()).unwrap();
+    let entry = index.get("c1").unwrap();
+    assert_eq!((entry.bases, entry.line_bases, entry.line_width), (7, 5, 7));
+
+    let fai = FastqIndex::fai_path(file.path());
+    assert_eq!(fai.extension().unwrap(), "fai");
+    index.write_fai(&fai).unwrap();
+    let reader = IndexedReader::from_fai(file.path(), fai.as_path()).unwrap();
+    assert!(reader.get_record("c1").is_none());
+    assert_eq!(reader.get_owned_record("c1").unwrap().qual, b"IIIIIII");
+    std::fs::remove_file(fai).unwrap();
+}
+
+#[test]
+fn test_fai_older_than_fastq_is_stale() {
+    let file = write_temp(b"@r1\nACGT\n+\nIIII\n");
+    let fai = NamedTempFile::new().unwrap();
+    FastqIndex::build(file.path())
+        .unwrap()
+        .write_fai(fai.path())
+        .unwrap();
+
+    let set_modified = |path: &std::path::Path, time| {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap()
+    };
+    let written = std::fs::metadata(fai.path()).unwrap().modified().unwrap();
+    let later = written + std::time::Duration::from_secs(10);
+    set_modified(file.path(), later);
+    assert!(matches!(
+        FastqIndex::read_fai(file.path(), fai.path()),
+        Err(FastqError::StaleIndex { .. })
+    ));
+
+    set_modified(fai.path(), later);
+    assert_eq!(
+        FastqIndex::read_fai(file.path(), fai.path()).unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn test_fai_rejects_mismatched_files() {
+    let file = write_temp(b"@r1\nACGT\n+\nIIII\n");
+    let wrong_name = write_temp(b"r9\t4\t4\t4\t5\t11\n");
+    let short = write_temp(b"r1\t4\t4\n");
+    let outside = write_temp(b"r1\t4\t4\t4\t5\t900\n");
+    let seq_past_end = write_temp(b"r1\t4\t100\t4\t5\t1\n");
+    let overlapping = write_temp(b"r1\t4\t4\t4\t5\t6\n");
+    let huge = format!("r1\t{}\t4\t1\t{}\t11\n", usize::MAX, usize::MAX);
+    let overflow = write_temp(huge.as_bytes());
+    let huge_qual = format!("r1\t4\t4\t4\t5\t{}\n", usize::MAX);
+    let qual_overflow = write_temp(huge_qual.as_bytes());
+
+    for fai in [
+        &wrong_name,
+        &short,
+        &outside,
+        &seq_past_end,
+        &overlapping,
+        &overflow,
+        &qual_overflow,
+    ] {
+        assert!(matches!(
+            FastqIndex::read_fai(file.path(), fai.path()),
+            Err(FastqError::InvalidIndex { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_region_parsing() {
+    assert_eq!(Region::parse("r1").unwrap(), Region::new("r1", None, None));
+    assert_eq!(
+        Region::parse("r1:1,001-2,000").unwrap(),
+        Region::new("r1", Some(1001), Some(2000))
+    );
+    assert_eq!(
+        Region::parse("r1:5").unwrap(),
+        Region::new("r1", Some(5), None)
+    );
+    assert_eq!(
+        Region::parse("r1:5-").unwrap(),
+        Region::new("r1", Some(5), None)
+    );
+    assert_eq!(Region::parse("a:b:3-4").unwrap().id, "a:b");
+    assert_eq!(Region::parse("a:b").unwrap().id, "a:b");
+    assert_eq!(Region::parse("r1:3-4").unwrap().to_string(), "r1:3-4");
+
+    assert!(matches!(
+        Region::parse("r1:0-4"),
+        Err(FastqError::InvalidRegion { .. })
+    ));
+    assert!(matches!(
+        Region::parse("r1:9-4"),
+        Err(FastqError::InvalidRegion { .. })
+    ));
+}
+
+#[test]
+fn test_region_fetch() {
+    let data = b"@long x\nACGTAC\nGTTT\n+\nABCDEF\nGHIJ\n@M0:1:FC:7\nAAAAC\n+\nIIIII\n";
+    let file = write_temp(data);
+    let reader = IndexedReader::new(file.path(), FastqIndex::build(file.path()).unwrap()).unwrap();
+
+    let region = Region::parse("long:5-8").unwrap();
+    let record = reader.fetch(&region, false).unwrap().unwrap();
+    assert_eq!(record.id, b"long:5-8");
+    assert_eq!(record.seq, b"ACGT");
+    assert_eq!(record.qual, b"EFGH");
+
+    let rc = reader.fetch(&region, true).unwrap().unwrap();
+    assert_eq!(rc.id, b"long:5-8/rc");
+    assert_eq!(rc.seq, b"ACGT");
+    assert_eq!(rc.qual, b"HGFE");
+
+    let clamped = reader
+        .fetch(&Region::parse("long:9-100").unwrap(), false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (clamped.id.as_slice(), clamped.seq.as_slice()),
+        (&b"long:9-10"[..], &b"TT"[..])
+    );
+    let past_end = reader
+        .fetch(&Region::parse("long:50").unwrap(), false)
+        .unwrap()
+        .unwrap();
+    assert!(past_end.seq.is_empty());
+    assert!(reader
+        .fetch(&Region::parse("nope:1-2").unwrap(), false)
+        .unwrap()
+        .is_none());
+
+    let mut random =
+        RandomAccessReader::new(file.path(), FastqIndex::build(file.path()).unwrap()).unwrap();
+    let from_disk = random.fetch(&region, true).unwrap().unwrap();
+    assert_eq!((from_disk.seq, from_disk.qual), (rc.seq, rc.qual));
+
+    // An indexed ID that looks like a region is fetched whole.
+    let whole = reader.fetch_regions(["M0:1:FC:7"], false).unwrap();
+    assert_eq!(whole[0].seq, b"AAAAC");
+}
+
+#[test]
+fn test_region_list_file() {
+    let file = write_temp(b"@r1\nACGTACGT\n+\nIIIIJJJJ\n@r2\nTTTTGGGG\n+\n!!!!####\n");
+    let reader = IndexedReader::new(file.path(), FastqIndex::build(file.path()).unwrap()).unwrap();
+
+    let list = write_temp(b"# regions\nr2:4-5\n\nr1:1-2\nr1\n");
+    let records = reader.fetch_region_file(list.path(), false).unwrap();
+    let seqs: Vec<_> = records.iter().map(|r| r.seq.as_slice()).collect();
+    assert_eq!(seqs, [&b"TG"[..], b"AC", b"ACGTACGT"]);
+    assert_eq!(records[0].qual, b"!#");
+
+    let missing = write_temp(b"r1:1-2\nr3:1-2\n");
+    assert!(matches!(
+        reader.fetch_region_file(missing.path(), false),
+        Err(FastqError::RegionNotFound { region }) if region == "r3:1-2"
+    ));
+}
+
+fn set_mtime(path: &std::path::Path, secs: u64) {
+    let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(time)
+        .unwrap();
+}
+
+#[test]
+fn test_stale_index_detected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reads.fq");
+    std::fs::write(&path, b"@r1\nACGT\n+\nIIII\n").unwrap();
+    let index_path = dir.path().join("reads.fqi");
+    FastqIndex::build(&path).unwrap().save(&index_path).unwrap();
+
+    // Same content, new mtime: the fingerprint still matches.
+    set_mtime(&path, 1_000_000);
+    assert!(IndexedReader::from_paths(&path, &index_path).is_ok());
+
+    // Same size, different content.
+    std::fs::write(&path, b"@r2\nTTTT\n+\nJJJJ\n").unwrap();
+    set_mtime(&path, 2_000_000);
+    let err = IndexedReader::from_paths(&path, &index_path).err().unwrap();
+    assert!(matches!(err, FastqError::StaleIndex { ref reason, .. } if reason.contains("content")));
+
+    // Different size.
+    std::fs::write(&path, b"@r1\nACGTA\n+\nIIIII\n").unwrap();
+    let index = FastqIndex::load(&index_path).unwrap();
+    assert!(matches!(
+        RandomAccessReader::new(&path, index),
+        Err(FastqError::StaleIndex { .. })
+    ));
+
+    // Indexes assembled by hand carry no stamp.
+    assert!(FastqIndex::new().verify(&path).is_ok());
+}
+
+#[test]
+fn test_open_or_build_caches_sidecar() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reads.fq");
+    std::fs::write(&path, b"@r1\nACGT\n+\nIIII\n").unwrap();
+    let sidecar = FastqIndex::sidecar_path(&path);
+    assert_eq!(sidecar, dir.path().join("reads.fq.fqi"));
+
+    let reader = IndexedReader::open_or_build(&path).unwrap();
+    assert_eq!(reader.get_record("r1").unwrap().seq(), b"ACGT");
+    assert!(sidecar.exists());
+
+    // A cached, fresh sidecar is reused as is.
+    set_mtime(&sidecar, 3_000_000);
+    let cached_mtime = std::fs::metadata(&sidecar).unwrap().modified().unwrap();
+    IndexedReader::open_or_build(&path).unwrap();
+    assert_eq!(
+        std::fs::metadata(&sidecar).unwrap().modified().unwrap(),
+        cached_mtime
+    );
+
+    // A stale sidecar is rebuilt.
+    std::fs::write(&path, b"@r1\nACGT\n+\nIIII\n@r2\nGG\n+\nII\n").unwrap();
+    let reader = IndexedReader::open_or_build(&path).unwrap();
+    assert_eq!(reader.get_record("r2").unwrap().seq(), b"GG");
+    assert_eq!(FastqIndex::load(&sidecar).unwrap().len(), 2);
+
+    // So is one that is not an index at all.
+    std::fs::write(&sidecar, b"garbage").unwrap();
+    assert_eq!(
+        IndexedReader::open_or_build(&path).unwrap().index().len(),
+        2
+    );
+}
+
+#[test]
+fn test_hash_index_external_sort() {
+    let mut data = Vec::new();
+    for i in 0..1000 {
+        writeln!(
+            data,
+            "@read_{} lane:1\nACGT{}\n+\nIIII{}",
+            i,
+            "A".repeat(i % 7),
+            "I".repeat(i % 7)
+        )
+        .unwrap();
+    }
+    let file = write_temp(&data);
+    let dir = tempfile::tempdir().unwrap();
+    let spill = dir.path().join("spill");
+    std::fs::create_dir(&spill).unwrap();
+    let index_path = dir.path().join("reads.fqh");
+
+    // 100 slots per run, so the build spills ten sorted runs and merges them.
+    let index = HashIndexBuilder::new()
+        .memory_budget(16 * 100)
+        .spill_dir(&spill)
+        .build(file.path(), &index_path)
+        .unwrap();
+    assert_eq!(index.len(), 1000);
+    assert_eq!(std::fs::read_dir(&spill).unwrap().count(), 0);
+    assert_eq!(
+        std::fs::metadata(&index_path).unwrap().len(),
+        48 + 16 * 1000
+    );
+
+    let in_memory = HashIndexBuilder::new()
+        .build(file.path(), dir.path().join("single.fqh"))
+        .unwrap();
+    assert_eq!(
+        std::fs::read(&index_path).unwrap(),
+        std::fs::read(dir.path().join("single.fqh")).unwrap()
+    );
+    drop(in_memory);
+
+    let reader = HashIndexedReader::from_paths(file.path(), index_path.as_path()).unwrap();
+    let parsed: Vec<_> = Parser::new(&data).collect();
+    for expected in &parsed {
+        let id = expected.id_str().unwrap();
+        assert_eq!(reader.get_record(id).as_ref(), Some(expected));
+    }
+    assert!(reader.get_record("read_1000").is_none());
+    assert!(reader.get_record("read_").is_none());
+    assert_eq!(reader.offset("read_0"), Some(0));
+}
+
+#[test]
+fn test_hash_index_verifies_ids_and_staleness() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("reads.fq");
+    std::fs::write(
+        &path,
+        b"@dup\nAAAA\n+\nIIII\n@other\nCCCC\n+\nIIII\n@dup\nGGGG\n+\nIIII\n",
+    )
+    .unwrap();
+    let index_path = dir.path().join("reads.fqh");
+    let index = HashIndexBuilder::new().build(&path, &index_path).unwrap();
+
+    // Both copies of `dup` share a hash; the first in file order wins.
+    assert_eq!(index.candidates(b"dup").collect::<Vec<_>>(), [0, 36]);
+    let reader = HashIndexedReader::new(&path, index).unwrap();
+    assert_eq!(reader.get_record("dup").unwrap().seq(), b"AAAA");
+
+    std::fs::write(&path, b"@dup\nAAAA\n+\nIIII\n").unwrap();
+    assert!(matches!(
+        HashIndexedReader::from_paths(&path, &index_path),
+        Err(FastqError::StaleIndex { .. })
+    ));
+
+    let bogus = dir.path().join("bogus.fqh");
+    std::fs::write(&bogus, b"FQIX").unwrap();
+    assert!(matches!(
+        HashIndex::open(&bogus),
+        Err(FastqError::InvalidIndex { .. })
+    ));
+
+    // A slot count whose table size overflows is corrupt, not a panic.
+    let mut header = std::fs::read(&index_path).unwrap();
+    header[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    std::fs::write(&bogus, &header).unwrap();
+    assert!(matches!(
+        HashIndex::open(&bogus),
+        Err(FastqError::InvalidIndex { .. })
+    ));
+}
+
+#[test]
+fn test_secondary_indexes() {
+    let data = b"@r1 1:N:0:AAAA\nACGTACGTTTTT\n+\nIIIIIIIIIIII\n\
+@r2 1:N:0:CCCC\nTTTTACGTGGGG\n+\nIIIIIIIIIIII\n\
+@r3 1:N:0:AAAA\nACGTCC\nCCTTTT\n+\nIIIIII\nIIIIII\n\
+@r4\nGG\n+\nII\n";
+    let file = write_temp(data);
+    let mut index = FastqIndex::build(file.path()).unwrap();
+
+    let sample_index = KeyExtractor::DescField {
+        delimiter: b':',
+        field: 3,
+    };
+    assert_eq!(
+        index
+            .add_secondary("sample", file.path(), &sample_index)
+            .unwrap(),
+        2
+    );
+    assert_eq!(index.lookup_secondary("sample", b"AAAA"), Some(&[0, 2][..]));
+    assert_eq!(index.lookup_secondary("sample", b"GGGG"), Some(&[][..]));
+    assert!(index.lookup_secondary("missing", b"AAAA").is_none());
+
+    let saved = NamedTempFile::new().unwrap();
+    index.save(saved.path()).unwrap();
+    let mut reader = IndexedReader::from_paths(file.path(), saved.path()).unwrap();
+    let ids: Vec<_> = reader
+        .records_for_key("sample", b"AAAA")
+        .unwrap()
+        .map(|r| (r.id, r.seq))
+        .collect();
+    // r3 is wrapped and comes back with its lines joined.
+    assert_eq!(
+        ids,
+        [
+            (b"r1".to_vec(), b"ACGTACGTTTTT".to_vec()),
+            (b"r3".to_vec(), b"ACGTCCCCTTTT".to_vec())
+        ]
+    );
+
+    let cells = BarcodeExtractor::new(BarcodeConfig::new(0, 4).with_umi(4, 4));
+    assert_eq!(
+        reader
+            .add_secondary("cell", &KeyExtractor::Barcode(cells))
+            .unwrap(),
+        2
+    );
+    let umis = BarcodeExtractor::new(BarcodeConfig::new(0, 4).with_umi(4, 4));
+    reader
+        .add_secondary("umi", &KeyExtractor::Umi(umis))
+        .unwrap();
+    let gc = KeyExtractor::custom(|r| (r.seq().len() > 2).then(|| r.seq()[..1].to_vec()));
+    reader.add_secondary("first_base", &gc).unwrap();
+
+    let index = reader.index();
+    assert_eq!(index.lookup_secondary("cell", b"ACGT"), Some(&[0, 2][..]));
+    assert_eq!(index.lookup_secondary("umi", b"ACGT"), Some(&[0, 1][..]));
+    assert_eq!(index.lookup_secondary("first_base", b"T"), Some(&[1][..]));
+    let keys: Vec<_> = index.secondary_keys("cell").unwrap().collect();
+    assert_eq!(keys, [(&b"ACGT"[..], 2), (&b"TTTT"[..], 1)]);
+    let names: Vec<_> = index.secondary_names().cloned().collect();
+    assert_eq!(names, ["cell", "first_base", "sample", "umi"]);
+
+    let by_tile = KeyExtractor::IdField {
+        delimiter: b'r',
+        field: 1,
+    };
+    let mut index = FastqIndex::build(file.path()).unwrap();
+    assert_eq!(index.add_secondary("n", file.path(), &by_tile).unwrap(), 4);
+    assert!(index.remove_secondary("n"));
+}
+
+fn entry_key(entry: &fastq_parser::index::IndexEntry) -> (String, u64, usize, u64, u64) {
+    (
+        entry.id.clone(),
+        entry.offset,
+        entry.length,
+        entry.seq_offset,
+        entry.qual_offset,
+    )
+}
+
+#[test]
+fn test_parallel_index_build_matches_sequential() {
+    let mut data = Vec::new();
+    for i in 0..40_000 {
+        let len = 40 + i % 50;
+        writeln!(
+            data,
+            "@read_{:05} x\n{}\n+\n{}",
+            40_000 - i,
+            "ACGT".repeat(len / 4),
+            "I".repeat(len / 4 * 4)
+        )
+        .unwrap();
+    }
+    assert!(data.len() > 3 << 20);
+    let file = write_temp(&data);
+
+    let sequential = FastqIndex::build(file.path()).unwrap();
+    let parallel = FastqIndex::build_parallel_with_threads(file.path(), 4).unwrap();
+    assert_eq!(parallel.len(), 40_000);
+    assert!(sequential
+        .entries()
+        .iter()
+        .zip(parallel.entries())
+        .all(|(a, b)| entry_key(a) == entry_key(b)));
+    assert!(sequential.sorted_ids().eq(parallel.sorted_ids()));
+    assert_eq!(parallel.stamp(), sequential.stamp());
+
+    // Wrapped records cannot be split safely and take the sequential path.
+    let wrapped = write_temp(b"@A\nACGT\nAC\n+\nIIII\nII\n@B\nGG\n+\nII\n");
+    assert_eq!(FastqIndex::build_parallel(wrapped.path()).unwrap().len(), 2);
+
+    // The wrapped quality lines look like a four-line record to the resync
+    // scan, so cuts may land inside records; validation must catch that.
+    let mut data = Vec::new();
+    for i in 0..60_000 {
+        writeln!(
+            data,
+            "@w{}\nACGT\nACGT\nACGT\nACGT\n+\n@III\nIIII\n+III\nIIII",
+            i
+        )
+        .unwrap();
+    }
+    assert!(data.len() > 2 << 20);
+    let file = write_temp(&data);
+    let sequential = FastqIndex::build(file.path()).unwrap();
+    let parallel = FastqIndex::build_parallel_with_threads(file.path(), 2).unwrap();
+    assert_eq!(parallel.len(), 60_000);
+    assert!(sequential
+        .entries()
+        .iter()
+        .zip(parallel.entries())
+        .all(|(a, b)| entry_key(a) == entry_key(b)));
+
+    let broken = write_temp(b"@A\nACGT\n+\nIIII\nB\nACGT\n+\nIIII\n");
+    assert!(matches!(
+        FastqIndex::build_parallel(broken.path()),
+        Err(FastqError::InvalidHeader { line: 5 })
+    ));
+}
+
+#[test]
+fn test_incremental_index_update() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("live.fq");
+    std::fs::write(&path, b"@c\nACGT\n+\nIIII\n@a\nGG\n+\nII").unwrap();
+
+    let mut index = FastqIndex::build(&path).unwrap();
+    index
+        .add_secondary("n", &path, &KeyExtractor::custom(|r| Some(r.id().to_vec())))
+        .unwrap();
+
+    // Append a complete record plus one still being written.
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(b"\n@b\nTTT\n+\nJJJ\n@d\nAC").unwrap();
+    assert_eq!(index.update(&path).unwrap(), 1);
+    assert!(index.secondary_names().next().is_none());
+    let sorted: Vec<_> = index.sorted_ids().cloned().collect();
+    assert_eq!(sorted, ["a", "b", "c"]);
+
+    file.write_all(b"GT\n+\nIIII\n").unwrap();
+    assert_eq!(index.update(&path).unwrap(), 1);
+    assert_eq!(index.update(&path).unwrap(), 0);
+
+    let reader = IndexedReader::new(&path, index).unwrap();
+    assert_eq!(reader.get_record("d").unwrap().seq(), b"ACGT");
+    assert_eq!(reader.get_by_position(2).unwrap().qual(), b"JJJ");
+    let data = std::fs::read(&path).unwrap();
+    let parsed: Vec<_> = Parser::new(&data).collect();
+    for (i, expected) in parsed.iter().enumerate() {
+        assert_eq!(reader.get_by_position(i).as_ref(), Some(expected));
+    }
+
+    // Rewriting already-indexed bytes requires a rebuild.
+    let mut index = FastqIndex::build(&path).unwrap();
+    let mut changed = data.clone();
+    changed[4] = b'T';
+    changed.extend_from_slice(b"@e\nA\n+\nI\n");
+    std::fs::write(&path, &changed).unwrap();
+    assert!(matches!(
+        index.update(&path),
+        Err(FastqError::StaleIndex { .. })
+    ));
+
+    // A fresh index can follow a file that has never been complete.
+    std::fs::write(&path, b"@x\nAC\n+\nII\n@y\nA").unwrap();
+    let mut index = FastqIndex::new();
+    assert_eq!(index.update(&path).unwrap(), 1);
+    assert!(index.contains("x"));
+}
@@ -44,6 +44,32 @@ fn test_multiline_sequences() {
     }
 }
 
+#[test]
+fn test_wrapped_sequences() {
+    let data = b"@SEQ_1\nACGT\nTG\n+\nIIII\nJJ\n@SEQ_2\nCA\n+\nII\n";
+
+    // The zero-copy parser cannot return a wrapped record without its line
+    // breaks, so it rejects it.
+    assert!(matches!(
+        Parser::new(data).parse_record(),
+        Err(FastqError::LengthMismatch { .. })
+    ));
+
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(data).unwrap();
+    for reader in [
+        FastqReader::from_path(file.path()).unwrap(),
+        FastqReader::from_reader(std::io::Cursor::new(data.to_vec())),
+    ] {
+        let records: Vec<_> = reader.into_records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].seq, b"ACGTTG");
+        assert_eq!(records[0].qual, b"IIIIJJ");
+        assert!(records[0].as_record().validate().is_ok());
+        assert_eq!(records[1].seq, b"CA");
+    }
+}
+
 #[test]
 fn test_windows_line_endings() {
     let data = b"@SEQ_1\r\nACGT\r\n+\r\nIIII\r\n";
@@ -159,6 +185,23 @@ fn test_parallel_parser() {
     }
 }
 
+#[test]
+fn test_parallel_parser_resyncs_at_cuts() {
+    // Quality lines starting with '@' and '+' look like headers and
+    // separators to a naive boundary scan.
+    let mut data = Vec::new();
+    for i in 0..100_000 {
+        writeln!(data, "@SEQ_{}\nACGTACGTAC\n+\n@+IIIIIIII", i).unwrap();
+    }
+    assert!(data.len() > 3 << 20);
+
+    let records = ParallelParser::with_threads(data, 4).parse().unwrap();
+    assert_eq!(records.len(), 100_000);
+    assert!(records
+        .iter()
+        .all(|r| r.seq == b"ACGTACGTAC" && r.qual == b"@+IIIIIIII"));
+}
+
 #[test]
 fn test_large_file_parsing() {
     let mut data = Vec::new();
@@ -0,0 +1,313 @@
+use fastq_parser::merge::{consensus_base, find_overlap};
+use fastq_parser::simd::reverse_complement;
+use fastq_parser::*;
+
+const FRAGMENT: &[u8] = b"ACGTTGCAAGGCTTACCGATGCATCGGATCCAGTTGACCATGGTACGATCGTAGCTAGG";
+
+fn pair_from_fragment(fragment: &[u8], read_len: usize) -> (OwnedRecord, OwnedRecord) {
+    let rc = reverse_complement(fragment);
+    let r1 = OwnedRecord {
+        id: b"FRAG/1".to_vec(),
+        desc: None,
+        seq: fragment[..read_len.min(fragment.len())].to_vec(),
+        qual: vec![b'5'; read_len.min(fragment.len())],
+    };
+    let r2 = OwnedRecord {
+        id: b"FRAG/2".to_vec(),
+        desc: None,
+        seq: rc[..read_len.min(rc.len())].to_vec(),
+        qual: vec![b'5'; read_len.min(rc.len())],
+    };
+    (r1, r2)
+}
+
+#[test]
+fn test_merge_reconstructs_fragment() {
+    let (r1, r2) = pair_from_fragment(FRAGMENT, 40);
+    let merged = ReadMerger::new()
+        .merge(&r1.as_record(), &r2.as_record())
+        .unwrap();
+
+    assert_eq!(merged.id, b"FRAG");
+    assert_eq!(merged.seq, FRAGMENT);
+    // Q20 + Q20 agreeing calls give a higher consensus quality in the overlap
+    assert_eq!(merged.qual[0], b'5');
+    assert!(merged.qual[30] > b'5');
+    assert_eq!(merged.qual[merged.qual.len() - 1], b'5');
+}
+
+#[test]
+fn test_merge_rejects_short_or_noisy_overlap() {
+    let (r1, r2) = pair_from_fragment(FRAGMENT, 25);
+    assert!(ReadMerger::new()
+        .min_overlap(10)
+        .merge(&r1.as_record(), &r2.as_record())
+        .is_none());
+
+    let unrelated = Record::new(
+        b"X/2",
+        None,
+        b"TTTTTTTTTTTTTTTTTTTTTTTTT",
+        b"IIIIIIIIIIIIIIIIIIIIIIIII",
+    );
+    assert!(ReadMerger::new()
+        .merge(&r1.as_record(), &unrelated)
+        .is_none());
+}
+
+#[test]
+fn test_staggered_overlap_trims_overhangs() {
+    let insert = &FRAGMENT[..30];
+    let adapter = b"AGATCGGAAGAGC";
+    let mut r1_seq = insert.to_vec();
+    r1_seq.extend_from_slice(adapter);
+    let mut r2_seq = reverse_complement(insert);
+    r2_seq.extend_from_slice(b"AGATCGGAAGAGC");
+
+    let r1 = Record::new(b"S/1", None, &r1_seq, &[b'I'; 43]);
+    let r2 = Record::new(b"S/2", None, &r2_seq, &[b'I'; 43]);
+
+    assert!(ReadMerger::new().merge(&r1, &r2).is_none());
+    let merged = ReadMerger::new()
+        .allow_staggered(true)
+        .merge(&r1, &r2)
+        .unwrap();
+    assert_eq!(merged.seq, insert);
+}
+
+#[test]
+fn test_consensus_quality() {
+    let (base, q) = consensus_base(b'A', 20, b'A', 20, 41);
+    assert_eq!(base, b'A');
+    assert!(q > 35);
+
+    let (base, q) = consensus_base(b'A', 30, b'C', 10, 41);
+    assert_eq!(base, b'A');
+    assert!(q < 30 && q > 10);
+
+    assert_eq!(consensus_base(b'N', 2, b'G', 30, 41), (b'G', 30));
+}
+
+#[test]
+fn test_find_overlap_ignores_n() {
+    let r1 = b"ACGTACGTNNAC";
+    let overlap = find_overlap(r1, b"ACGTACGTACAC", 10, 0.0, false).unwrap();
+    assert_eq!(overlap.shift, 0);
+    assert_eq!(overlap.mismatches, 0);
+}
+
+#[test]
+fn test_find_overlap_prefers_long_overlap_over_short_perfect_one() {
+    // Poly-A runs at both ends of the fragment give a short perfect overlap
+    // far from the true one, which carries a single sequencing error.
+    let mut fragment = vec![b'A'; 30];
+    fragment.extend(random_insert(100, 11));
+    fragment.extend([b'A'; 20]);
+    fragment.extend(random_insert(10, 12));
+    let r1 = &fragment[..150];
+    let mut r2_rc = fragment[10..].to_vec();
+    r2_rc[70] = if r2_rc[70] == b'C' { b'G' } else { b'C' };
+
+    let overlap = find_overlap(r1, &r2_rc, 10, 0.1, false).unwrap();
+    assert_eq!(overlap.shift, 10);
+    assert_eq!(overlap.length, 140);
+    assert_eq!(overlap.mismatches, 1);
+    assert_eq!(overlap.insert_size(r1.len(), r2_rc.len()), fragment.len());
+}
+
+#[test]
+fn test_merge_pairs_streams_and_histogram() {
+    let pairs = vec![
+        Ok(pair_from_fragment(FRAGMENT, 40)),
+        Ok(pair_from_fragment(&FRAGMENT[..50], 40)),
+        Ok((
+            pair_from_fragment(FRAGMENT, 20).0,
+            pair_from_fragment(FRAGMENT, 20).1,
+        )),
+    ];
+
+    let (mut merged, mut u1, mut u2) = (Vec::new(), Vec::new(), Vec::new());
+    let stats = {
+        let mut wm = FastqWriter::new(&mut merged);
+        let mut w1 = FastqWriter::new(&mut u1);
+        let mut w2 = FastqWriter::new(&mut u2);
+        ReadMerger::new()
+            .merge_pairs(pairs.into_iter(), &mut wm, &mut w1, &mut w2)
+            .unwrap()
+    };
+
+    assert_eq!(stats.pairs, 3);
+    assert_eq!(stats.merged, 2);
+    assert_eq!(stats.unmerged, 1);
+    assert_eq!(stats.length_histogram.get(&FRAGMENT.len()), Some(&1));
+    assert_eq!(stats.length_histogram.get(&50), Some(&1));
+    assert_eq!(Parser::new(&merged).count(), 2);
+    assert_eq!(Parser::new(&u1).count(), 1);
+    assert_eq!(Parser::new(&u2).count(), 1);
+}
+
+fn random_insert(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            b"ACGT"[(state >> 33) as usize % 4]
+        })
+        .collect()
+}
+
+#[test]
+fn test_paired_adapter_trim_unknown_adapter() {
+    let insert = random_insert(45, 7);
+    let unknown_adapter = b"GTCAGGTACCTTGAGCAGT";
+    let mut r1_seq = insert.clone();
+    r1_seq.extend_from_slice(unknown_adapter);
+    let mut r2_seq = reverse_complement(&insert);
+    r2_seq.extend_from_slice(b"TTCCGAGATCAGCTAGCAA");
+    let qual = vec![b'I'; r1_seq.len()];
+
+    let r1 = Record::new(b"P/1", None, &r1_seq, &qual);
+    let r2 = Record::new(b"P/2", None, &r2_seq, &qual);
+
+    let result = PairedAdapterTrimmer::new().trim(&r1, &r2);
+    assert_eq!(result.method, TrimMethod::Overlap);
+    assert_eq!(result.insert_size, Some(45));
+    assert_eq!(result.r1.seq, &insert[..]);
+    assert_eq!(result.r2.seq, &reverse_complement(&insert)[..]);
+    assert_eq!(result.r1.qual.len(), 45);
+}
+
+#[test]
+fn test_paired_adapter_trim_long_insert_untouched() {
+    let fragment = random_insert(150, 11);
+    let r1_seq = &fragment[..60];
+    let r2_seq = reverse_complement(&fragment)[..60].to_vec();
+    let qual = vec![b'I'; 60];
+    let r1 = Record::new(b"P/1", None, r1_seq, &qual);
+    let r2 = Record::new(b"P/2", None, &r2_seq, &qual);
+
+    let result = PairedAdapterTrimmer::new().trim(&r1, &r2);
+    assert_eq!(result.method, TrimMethod::Untrimmed);
+    assert_eq!(result.r1.len(), 60);
+    assert_eq!(result.r2.len(), 60);
+}
+
+#[test]
+fn test_paired_adapter_trim_falls_back_to_sequence() {
+    let mut r1_seq = random_insert(40, 3);
+    r1_seq.extend_from_slice(b"AGATCGGAAGAGC");
+    let r2_seq = random_insert(53, 99);
+    let qual = vec![b'I'; 53];
+    let r1 = Record::new(b"P/1", None, &r1_seq, &qual);
+    let r2 = Record::new(b"P/2", None, &r2_seq, &qual);
+
+    let result = PairedAdapterTrimmer::new().trim(&r1, &r2);
+    assert_eq!(result.method, TrimMethod::Sequence);
+    assert_eq!(result.insert_size, None);
+    assert_eq!(result.r1.len(), 40);
+}
+
+#[test]
+fn test_paired_adapter_trim_low_complexity_falls_back() {
+    // A dinucleotide repeat overlaps at several shifts within the mismatch
+    // budget, so the insert boundary comes from the adapter instead.
+    let insert = b"CA".repeat(20);
+    let mut r1_seq = insert.clone();
+    r1_seq.extend_from_slice(b"AGATCGGAAGAGC");
+    let mut r2_seq = reverse_complement(&insert);
+    r2_seq.extend_from_slice(b"AGATCGGAAGAGC");
+    let qual = vec![b'I'; 53];
+    let r1 = Record::new(b"P/1", None, &r1_seq, &qual);
+    let r2 = Record::new(b"P/2", None, &r2_seq, &qual);
+
+    let result = PairedAdapterTrimmer::new().trim(&r1, &r2);
+    assert_eq!(result.method, TrimMethod::Sequence);
+    assert_eq!(result.insert_size, None);
+    assert_eq!(result.r1.len(), 40);
+    assert_eq!(result.r2.len(), 40);
+}
+
+#[test]
+fn test_insert_size_estimation() {
+    let read_len = 50;
+    let mut pairs = Vec::new();
+    for (i, insert) in [40usize, 60, 60, 80, 300].iter().enumerate() {
+        let mut fragment = random_insert(*insert, i as u64 + 1);
+        let adapter = b"AGATCGGAAGAGCACACGTCTGAACTCCAGTCA";
+        fragment.extend_from_slice(adapter);
+        let mut rc = reverse_complement(&fragment[..*insert]);
+        rc.extend_from_slice(adapter);
+        pairs.push(Ok((
+            OwnedRecord {
+                id: format!("P{}/1", i).into_bytes(),
+                desc: None,
+                seq: fragment[..read_len].to_vec(),
+                qual: vec![b'I'; read_len],
+            },
+            OwnedRecord {
+                id: format!("P{}/2", i).into_bytes(),
+                desc: None,
+                seq: rc[..read_len].to_vec(),
+                qual: vec![b'I'; read_len],
+            },
+        )));
+    }
+    let dimer = b"AGATCGGAAGAGCACACGTCTGAACTCCAGTCACCCCCCCCCCCCCCCCC".to_vec();
+    pairs.push(Ok((
+        OwnedRecord {
+            id: b"D/1".to_vec(),
+            desc: None,
+            seq: dimer.clone(),
+            qual: vec![b'I'; dimer.len()],
+        },
+        OwnedRecord {
+            id: b"D/2".to_vec(),
+            desc: None,
+            seq: random_insert(dimer.len(), 1234),
+            qual: vec![b'I'; dimer.len()],
+        },
+    )));
+
+    let report = InsertSizeEstimator::new()
+        .estimate(pairs.into_iter())
+        .unwrap();
+
+    assert_eq!(report.pairs_sampled, 6);
+    assert_eq!(report.overlapping_pairs, 4);
+    assert_eq!(report.too_long_to_overlap, 1);
+    assert_eq!(report.adapter_dimers, 1);
+    assert_eq!(report.histogram.get(&60), Some(&2));
+    assert_eq!(report.mode, 60);
+    assert_eq!(report.median, 60);
+    assert!((report.mean - 60.0).abs() < 1e-9);
+    assert!((report.too_long_fraction() - 1.0 / 6.0).abs() < 1e-9);
+
+    let encoded = bincode::serialize(&report).unwrap();
+    let decoded: InsertSizeReport = bincode::deserialize(&encoded).unwrap();
+    assert_eq!(decoded.histogram, report.histogram);
+}
+
+#[test]
+fn test_insert_size_detects_dimer_by_overlap() {
+    // An 8 bp insert followed by adapters the trimmer does not know, so
+    // only the mate overlap can identify the dimer.
+    let insert = b"GATTACAG";
+    let mut r1_seq = insert.to_vec();
+    r1_seq.extend(random_insert(42, 21));
+    let mut r2_seq = reverse_complement(insert);
+    r2_seq.extend(random_insert(42, 22));
+    let qual = vec![b'I'; 50];
+
+    let estimator = InsertSizeEstimator::new();
+    let r1 = Record::new(b"D/1", None, &r1_seq, &qual);
+    let r2 = Record::new(b"D/2", None, &r2_seq, &qual);
+    assert_eq!(estimator.insert_size(&r1, &r2), Some(8));
+
+    let pair = (OwnedRecord::from_record(&r1), OwnedRecord::from_record(&r2));
+    let report = estimator.estimate(std::iter::once(Ok(pair))).unwrap();
+    assert_eq!(report.adapter_dimers, 1);
+    assert_eq!(report.overlapping_pairs, 0);
+    assert_eq!(report.too_long_to_overlap, 0);
+}
@@ -1,6 +1,7 @@
 use fastq_parser::*;
 use std::collections::{HashMap, HashSet};
 use std::io::Write;
+use std::sync::{Arc, Mutex};
 use tempfile::NamedTempFile;
 
 #[test]
@@ -79,6 +80,360 @@ fn test_fastq_writer() {
     assert_eq!(written, "@TEST desc\nACGT\n+\nIIII\n");
 }
 
+#[test]
+fn test_fastq_format() {
+    let record = Record::new(b"R1", Some(b"len=6"), b"ACGTAC", b"IIIIII");
+    let write = |format: FastqFormat| {
+        let mut writer = FastqWriter::new(Vec::new()).format(format);
+        writer.write_record(&record).unwrap();
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    };
+
+    assert_eq!(write(FastqFormat::new()), "@R1 len=6\nACGTAC\n+\nIIIIII\n");
+    assert_eq!(format!("{}", record), "@R1 len=6\nACGTAC\n+\nIIIIII");
+    assert_eq!(
+        write(FastqFormat::new().repeat_id(true).strip_comments(true)),
+        "@R1\nACGTAC\n+R1\nIIIIII\n"
+    );
+    assert_eq!(
+        write(FastqFormat::new().line_width(Some(4)).crlf(true)),
+        "@R1 len=6\r\nACGT\r\nAC\r\n+\r\nIIII\r\nII\r\n"
+    );
+    assert_eq!(
+        write(FastqFormat::new().mate_suffix(Some(1)).strip_comments(true)),
+        "@R1/1\nACGTAC\n+\nIIIIII\n"
+    );
+
+    let format = FastqFormat::new().repeat_id(true);
+    assert_eq!(
+        format!("{}", format.display(&record)),
+        "@R1 len=6\nACGTAC\n+R1 len=6\nIIIIII"
+    );
+
+    let mate = Record::new(b"R1/2", None, b"GT", b"II");
+    let mut writer = InterleavedWriter::new(Vec::new())
+        .format(FastqFormat::new().mate_suffix(Some(1)).strip_comments(true));
+    writer.write_pair(&record, &mate).unwrap();
+    assert_eq!(
+        String::from_utf8(writer.finish().unwrap()).unwrap(),
+        "@R1/1\nACGTAC\n+\nIIIIII\n@R1/2\nGT\n+\nII\n"
+    );
+}
+
+#[test]
+fn test_writer_finish() {
+    use std::io::Read;
+
+    let record = Record::new(b"TEST", None, b"ACGT", b"IIII");
+    let mut writer = FastqWriter::new_gzip(Vec::new(), flate2::Compression::fast());
+    writer.write_record(&record).unwrap();
+    let compressed = writer.finish().unwrap();
+
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&compressed[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "@TEST\nACGT\n+\nIIII\n");
+
+    struct Full;
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk full"))
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut writer = FastqWriter::new(Full);
+    writer.write_record(&record).unwrap();
+    assert!(writer.finish().is_err());
+}
+
+#[test]
+fn test_parallel_gzip_writer() {
+    use std::io::Read;
+
+    let records: Vec<OwnedRecord> = (0..2000)
+        .map(|i| OwnedRecord {
+            id: format!("read{}", i).into_bytes(),
+            desc: None,
+            seq: b"ACGTACGTTGCA".repeat(i % 7 + 1),
+            qual: b"IIIIFFFF####".repeat(i % 7 + 1),
+        })
+        .collect();
+
+    let mut plain = FastqWriter::new(Vec::new());
+    for record in &records {
+        plain.write_owned_record(record).unwrap();
+    }
+    let expected = plain.finish().unwrap();
+
+    for format in [GzipFormat::Gzip, GzipFormat::Bgzf] {
+        let options = ParallelGzip::new()
+            .threads(3)
+            .block_size(4096)
+            .format(format);
+        let mut writer = FastqWriter::new_parallel_gzip(Vec::new(), options);
+        for record in &records {
+            writer.write_owned_record(record).unwrap();
+        }
+        let compressed = writer.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        flate2::read::MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, expected);
+
+        if format == GzipFormat::Bgzf {
+            let mut offset = 0;
+            let mut blocks = 0;
+            while offset < compressed.len() {
+                assert_eq!(&compressed[offset + 12..offset + 14], b"BC");
+                let bsize = u16::from_le_bytes([compressed[offset + 16], compressed[offset + 17]]);
+                offset += bsize as usize + 1;
+                blocks += 1;
+            }
+            assert_eq!(offset, compressed.len());
+            assert!(blocks > expected.len() / 4096);
+            assert_eq!(compressed[compressed.len() - 28..][16], 0x1b);
+        }
+    }
+}
+
+#[test]
+fn test_reader_decodes_by_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let record = Record::new(b"TEST", None, b"ACGT", b"IIII");
+
+    #[allow(unused_mut)]
+    let mut extensions = vec!["gz", "bgz", "bgzf"];
+    #[cfg(feature = "zstd")]
+    extensions.push("zst");
+    #[cfg(feature = "bzip2")]
+    extensions.push("bz2");
+    #[cfg(feature = "xz")]
+    extensions.push("xz");
+
+    for extension in extensions {
+        let path = dir.path().join(format!("reads.fastq.{}", extension));
+        let mut writer = FastqWriter::to_file(&path).unwrap();
+        writer.write_record(&record).unwrap();
+        writer.finish().unwrap();
+
+        let records: Vec<_> = FastqReader::from_path(&path)
+            .unwrap()
+            .into_records()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records.len(), 1, "{}", extension);
+        assert_eq!(records[0].as_record(), record);
+    }
+
+    for (extension, enabled) in [
+        ("zst", cfg!(feature = "zstd")),
+        ("bz2", cfg!(feature = "bzip2")),
+        ("xz", cfg!(feature = "xz")),
+    ] {
+        let path = dir.path().join(format!("plain.fastq.{}", extension));
+        std::fs::write(&path, b"@TEST\nACGT\n+\nIIII\n").unwrap();
+        if !enabled {
+            assert!(matches!(
+                FastqReader::from_path(&path),
+                Err(FastqError::UnsupportedCompression { .. })
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_output_compression_by_extension() {
+    use std::io::Read;
+
+    let dir = tempfile::tempdir().unwrap();
+    let record = Record::new(b"TEST", None, b"ACGT", b"IIII");
+    let expected = b"@TEST\nACGT\n+\nIIII\n".to_vec();
+
+    type Decode = fn(&[u8]) -> Vec<u8>;
+    let gunzip: Decode = |data| {
+        let mut out = Vec::new();
+        flate2::read::MultiGzDecoder::new(data)
+            .read_to_end(&mut out)
+            .unwrap();
+        out
+    };
+    #[allow(unused_mut)]
+    let mut cases: Vec<(&str, Option<Decode>)> = vec![("gz", Some(gunzip)), ("bgz", Some(gunzip))];
+
+    #[cfg(feature = "zstd")]
+    cases.push(("zst", Some(|data| zstd::decode_all(data).unwrap())));
+    #[cfg(not(feature = "zstd"))]
+    cases.push(("zst", None));
+
+    #[cfg(feature = "bzip2")]
+    cases.push((
+        "bz2",
+        Some(|data| {
+            let mut out = Vec::new();
+            bzip2::read::BzDecoder::new(data)
+                .read_to_end(&mut out)
+                .unwrap();
+            out
+        }),
+    ));
+    #[cfg(not(feature = "bzip2"))]
+    cases.push(("bz2", None));
+
+    #[cfg(feature = "xz")]
+    cases.push((
+        "xz",
+        Some(|data| {
+            let mut out = Vec::new();
+            xz2::read::XzDecoder::new(data)
+                .read_to_end(&mut out)
+                .unwrap();
+            out
+        }),
+    ));
+    #[cfg(not(feature = "xz"))]
+    cases.push(("xz", None));
+
+    for (extension, decode) in cases {
+        let path = dir.path().join(format!("out.fastq.{}", extension));
+        match decode {
+            Some(decode) => {
+                let mut writer = FastqWriter::to_file(&path).unwrap();
+                writer.write_record(&record).unwrap();
+                writer.finish().unwrap();
+                assert_eq!(decode(&std::fs::read(&path).unwrap()), expected);
+            }
+            None => assert!(matches!(
+                FastqWriter::to_file(&path),
+                Err(FastqError::UnsupportedCompression { .. })
+            )),
+        }
+    }
+
+    let path = dir.path().join("out.fa.gz");
+    let mut writer = FastaWriter::to_file(&path).unwrap();
+    writer.write_record(&record).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(gunzip(&std::fs::read(&path).unwrap()), b">TEST\nACGT\n");
+}
+
+#[test]
+fn test_atomic_writer() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.fastq");
+    std::fs::write(&path, "old").unwrap();
+    let record = Record::new(b"TEST", None, b"ACGT", b"IIII");
+
+    let mut writer = FastqWriter::to_file_atomic(&path).unwrap();
+    writer.write_record(&record).unwrap();
+    drop(writer);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    let mut writer = FastqWriter::to_file_atomic(&path).unwrap();
+    writer.write_record(&record).unwrap();
+    writer.commit().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "@TEST\nACGT\n+\nIIII\n"
+    );
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_raw_pass_through() {
+    let input: &[u8] =
+        b"@r1 lane=1\nACGTACGT\n+r1 lane=1\nIIIIIIII\n@r2\nAC\n+\n##\n@r3\nGGGG\n+r3\nFFFF";
+
+    let mut writer = FastqWriter::new(Vec::new());
+    for record in Parser::new(input) {
+        if record.id() != b"r2" {
+            writer.write_raw(&record).unwrap();
+        }
+    }
+    assert_eq!(
+        writer.finish().unwrap(),
+        b"@r1 lane=1\nACGTACGT\n+r1 lane=1\nIIIIIIII\n@r3\nGGGG\n+r3\nFFFF\n"
+    );
+
+    // Replacing a field invalidates the raw bytes.
+    let mut record = Parser::new(input).nth(2).unwrap();
+    assert!(record.raw().is_some());
+    record.seq = &record.seq[..2];
+    record.qual = &record.qual[..2];
+    assert!(record.raw().is_none());
+    let mut writer = FastqWriter::new(Vec::new());
+    writer.write_raw(&record).unwrap();
+    assert_eq!(writer.finish().unwrap(), b"@r3\nGG\n+\nFF\n");
+
+    let raw: Vec<RawRecord> = StreamingReader::new(input)
+        .keep_raw(true)
+        .raw_records()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(raw[1].raw(), Some(&b"@r2\nAC\n+\n##\n"[..]));
+    assert_eq!(raw[1].record().seq, b"AC");
+    assert!(StreamingReader::new(input)
+        .raw_records()
+        .all(|r| r.unwrap().raw().is_none()));
+
+    // Taking the record out for editing drops the bytes.
+    let mut edited = StreamingReader::new(input)
+        .keep_raw(true)
+        .raw_records()
+        .next()
+        .unwrap()
+        .unwrap()
+        .into_record();
+    edited.seq.truncate(4);
+    edited.qual.truncate(4);
+    let mut writer = FastqWriter::new(Vec::new());
+    writer.write_owned_raw(&RawRecord::from(edited)).unwrap();
+    assert_eq!(writer.finish().unwrap(), b"@r1 lane=1\nACGT\n+\nIIII\n");
+
+    // The first read is quality-trimmed and re-serialized, the other two
+    // pass through untouched.
+    let input =
+        b"@a\nACGTACGT\n+a\nIIII####\n@b\nACGTACGT\n+b\nIIIIIIII\n@c\nACGTACGT\n+c\nIIIIIIII\n";
+    let output = Arc::new(Mutex::new(Vec::new()));
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let filter = QualityFilter::new()
+        .min_quality(0.0)
+        .min_length(1)
+        .trim_quality(Some(20));
+    fastq_parser::parallel::ParallelFilterProcessor::new(filter)
+        .raw(true)
+        .process(&input[..], Shared(Arc::clone(&output)))
+        .unwrap();
+    let output = output.lock().unwrap();
+    let mut records: Vec<&[u8]> = output
+        .split_inclusive(|&b| b == b'\n')
+        .collect::<Vec<_>>()
+        .chunks(4)
+        .map(|lines| lines[0])
+        .collect();
+    records.sort();
+    assert_eq!(records, [&b"@a\n"[..], b"@b\n", b"@c\n"]);
+    let text = String::from_utf8_lossy(&output);
+    assert!(text.contains("@a\nACGTAC\n+\nIIII##\n"));
+    assert!(text.contains("@b\nACGTACGT\n+b\nIIIIIIII\n"));
+    assert!(text.contains("@c\nACGTACGT\n+c\nIIIIIIII\n"));
+}
+
 #[test]
 fn test_barcode_extraction() {
     let config = BarcodeConfig::new(0, 8).with_umi(8, 10);
@@ -116,6 +471,62 @@ fn test_demultiplexing() {
     assert_eq!(demux.assign_sample(b"TTTTTTTT"), None);
 }
 
+#[test]
+fn test_demultiplex_to_files() {
+    let mut barcodes = HashMap::new();
+    barcodes.insert(b"AAAA".to_vec(), "S1".to_string());
+    barcodes.insert(b"CCCC".to_vec(), "S2".to_string());
+    let demux = Demultiplexer::new(BarcodeConfig::new(0, 4), barcodes);
+
+    let records = [
+        ("r1", "AAAAGTGT"),
+        ("r2", "CCCCGTGT"),
+        ("r3", "GGGGGTGT"),
+        ("r4", "AAAAGTGT"),
+    ]
+    .map(|(id, seq)| {
+        Ok(OwnedRecord {
+            id: id.as_bytes().to_vec(),
+            desc: None,
+            seq: seq.as_bytes().to_vec(),
+            qual: vec![b'I'; seq.len()],
+        })
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let stats = demux
+        .demultiplex_to_files(records.into_iter(), dir.path(), "run")
+        .unwrap();
+    assert_eq!(stats.assigned_reads, 3);
+
+    let count = |name: &str| {
+        FastqReader::from_path(dir.path().join(name))
+            .unwrap()
+            .into_records()
+            .count()
+    };
+    assert_eq!(count("run_S1.fastq"), 2);
+    assert_eq!(count("run_S2.fastq"), 1);
+    assert_eq!(count("run_undetermined.fastq"), 1);
+
+    // The prefix is used verbatim, not as a template.
+    let stats = demux
+        .demultiplex_to_files(
+            [Ok(OwnedRecord::from_record(&Record::new(
+                b"r5",
+                None,
+                b"CCCCGTGT",
+                b"IIIIIIII",
+            )))]
+            .into_iter(),
+            dir.path(),
+            "x{}",
+        )
+        .unwrap();
+    assert_eq!(stats.assigned_reads, 1);
+    assert_eq!(count("x{}_S2.fastq"), 1);
+}
+
 #[test]
 fn test_quality_metrics() {
     let data = b"@SEQ1\nACGTACGT\n+\nIIIIJJJJ\n@SEQ2\nGCGCGCGC\n+\nKKKKLLLL\n";
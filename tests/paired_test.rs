@@ -0,0 +1,494 @@
+use fastq_parser::paired::RepairStats;
+use fastq_parser::*;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+fn write_temp(data: &[u8]) -> NamedTempFile {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(data).unwrap();
+    file
+}
+
+fn records(data: &[u8]) -> Vec<Result<OwnedRecord>> {
+    Parser::new(data)
+        .map(|r| Ok(OwnedRecord::from_record(&r)))
+        .collect()
+}
+
+#[test]
+fn test_pair_matching_rules() {
+    let r1 = Record::new(b"READ1/1", None, b"A", b"I");
+    let r2 = Record::new(b"READ1/2", None, b"A", b"I");
+    assert!(PairMatching::StripSuffix.is_pair(&r1, &r2));
+    assert!(!PairMatching::Exact.is_pair(&r1, &r2));
+    assert!(PairMatching::StripSuffix.is_swapped(&r2, &r1));
+
+    let c1 = Record::new(b"M1:1:FC:1:1:10:20", Some(b"1:N:0:ACGT"), b"A", b"I");
+    let c2 = Record::new(b"M1:1:FC:1:1:10:20", Some(b"2:N:0:ACGT"), b"A", b"I");
+    assert!(PairMatching::Casava.is_pair(&c1, &c2));
+    assert_eq!(PairMatching::Casava.read_number(&c2), Some(2));
+    assert!(PairMatching::Casava.is_swapped(&c2, &c1));
+
+    let re = PairMatching::regex(r"^(\w+)_R(?P<read>[12])$").unwrap();
+    let x1 = Record::new(b"frag7_R1", None, b"A", b"I");
+    let x2 = Record::new(b"frag7_R2", None, b"A", b"I");
+    assert!(re.is_pair(&x1, &x2));
+    assert_eq!(re.read_number(&x2), Some(2));
+}
+
+#[test]
+fn test_swapped_files_detected() {
+    let r1 = write_temp(b"@READ1/2\nACGT\n+\nIIII\n");
+    let r2 = write_temp(b"@READ1/1\nACGT\n+\nIIII\n");
+
+    assert!(
+        PairedEndReader::detect_swapped(r1.path(), r2.path(), &PairMatching::StripSuffix).unwrap()
+    );
+
+    let reader = PairedEndReader::from_paths(r1.path(), r2.path()).unwrap();
+    let first = reader.into_paired_records().next().unwrap();
+    assert!(matches!(first, Err(FastqError::SwappedPairedFiles)));
+
+    let reader = PairedEndReader::from_paths(r1.path(), r2.path()).unwrap();
+    let mut pairs = reader.into_paired_records().strict_pairing(false);
+    assert!(pairs.next().unwrap().is_ok());
+}
+
+#[test]
+fn test_repair_out_of_order_mates() {
+    let r1 = b"@A/1\nAAAA\n+\nIIII\n@B/1\nCCCC\n+\nIIII\n@C/1\nGGGG\n+\nIIII\n@ORPHAN/1\nTTTT\n+\nIIII\n";
+    let r2 = b"@C/2\nGGGG\n+\nJJJJ\n@A/2\nAAAA\n+\nJJJJ\n@B/2\nCCCC\n+\nJJJJ\n";
+
+    let (mut out1, mut out2, mut single) = (Vec::new(), Vec::new(), Vec::new());
+    let stats = {
+        let mut w1 = FastqWriter::new(&mut out1);
+        let mut w2 = FastqWriter::new(&mut out2);
+        let mut ws = FastqWriter::new(&mut single);
+        PairRepairer::new()
+            .repair(
+                records(r1).into_iter(),
+                records(r2).into_iter(),
+                &mut w1,
+                &mut w2,
+                &mut ws,
+            )
+            .unwrap()
+    };
+
+    assert_eq!(
+        stats,
+        RepairStats {
+            pairs: 3,
+            singletons: 1,
+            spilled: 0
+        }
+    );
+
+    let ids1: Vec<_> = Parser::new(&out1).map(|r| r.id.to_vec()).collect();
+    let ids2: Vec<_> = Parser::new(&out2).map(|r| r.id.to_vec()).collect();
+    assert_eq!(ids1.len(), 3);
+    for (a, b) in ids1.iter().zip(&ids2) {
+        assert_eq!(a[..a.len() - 2], b[..b.len() - 2]);
+    }
+    assert_eq!(
+        String::from_utf8(single).unwrap(),
+        "@ORPHAN/1\nTTTT\n+\nIIII\n"
+    );
+}
+
+#[test]
+fn test_repair_spills_to_disk() {
+    let mut r1 = Vec::new();
+    let mut r2 = Vec::new();
+    // About 125 reads land in each first-level bucket, which is over the
+    // budget and gets partitioned again.
+    for i in 0..2000 {
+        write!(r1, "@R{}/1\nACGT\n+\nIIII\n", i).unwrap();
+        write!(r2, "@R{}/2\nTGCA\n+\nIIII\n", 1999 - i).unwrap();
+    }
+    write!(r2, "@LONELY/2\nTGCA\n+\nIIII\n").unwrap();
+
+    let spill_dir = tempfile::tempdir().unwrap();
+    let (mut out1, mut out2, mut single) = (Vec::new(), Vec::new(), Vec::new());
+    let stats = {
+        let mut w1 = FastqWriter::new(&mut out1);
+        let mut w2 = FastqWriter::new(&mut out2);
+        let mut ws = FastqWriter::new(&mut single);
+        PairRepairer::new()
+            .max_buffered(16)
+            .spill_dir(spill_dir.path())
+            .repair(
+                records(&r1).into_iter(),
+                records(&r2).into_iter(),
+                &mut w1,
+                &mut w2,
+                &mut ws,
+            )
+            .unwrap()
+    };
+
+    assert_eq!(stats.pairs, 2000);
+    assert_eq!(stats.singletons, 1);
+    assert!(stats.spilled > 0);
+    assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+
+    let pairs: Vec<_> = Parser::new(&out1).zip(Parser::new(&out2)).collect();
+    assert_eq!(pairs.len(), 2000);
+    assert!(pairs
+        .iter()
+        .all(|(a, b)| PairMatching::StripSuffix.is_pair(a, b)));
+}
+
+#[test]
+fn test_concurrent_repairs_share_a_spill_dir() {
+    let mut r1 = Vec::new();
+    let mut r2 = Vec::new();
+    for i in 0..500 {
+        write!(r1, "@R{}/1\nACGT\n+\nIIII\n", i).unwrap();
+        write!(r2, "@R{}/2\nTGCA\n+\nIIII\n", 499 - i).unwrap();
+    }
+
+    // Each repair gets its own scratch directory under the shared parent,
+    // so one finishing never deletes another's runs.
+    let spill_dir = tempfile::tempdir().unwrap();
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut w1 = FastqWriter::new(Vec::new());
+                    let mut w2 = FastqWriter::new(Vec::new());
+                    let mut ws = FastqWriter::new(Vec::new());
+                    PairRepairer::new()
+                        .max_buffered(16)
+                        .spill_dir(spill_dir.path())
+                        .repair(
+                            records(&r1).into_iter(),
+                            records(&r2).into_iter(),
+                            &mut w1,
+                            &mut w2,
+                            &mut ws,
+                        )
+                        .unwrap()
+                })
+            })
+            .collect();
+        for handle in handles {
+            let stats = handle.join().unwrap();
+            assert_eq!(stats.pairs, 500);
+            assert!(stats.spilled > 0);
+        }
+    });
+    assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_repair_interleaved_shuffled() {
+    let data = b"@X/2\nAC\n+\nII\n@Y/1\nAC\n+\nII\n@X/1\nAC\n+\nII\n@Y/2\nAC\n+\nII\n";
+    let (mut out1, mut out2, mut single) = (Vec::new(), Vec::new(), Vec::new());
+    let stats = {
+        let mut w1 = FastqWriter::new(&mut out1);
+        let mut w2 = FastqWriter::new(&mut out2);
+        let mut ws = FastqWriter::new(&mut single);
+        PairRepairer::new()
+            .repair_interleaved(records(data).into_iter(), &mut w1, &mut w2, &mut ws)
+            .unwrap()
+    };
+
+    assert_eq!(stats.pairs, 2);
+    assert!(Parser::new(&out1).all(|r| r.id.ends_with(b"/1")));
+    assert!(Parser::new(&out2).all(|r| r.id.ends_with(b"/2")));
+}
+
+#[test]
+fn test_interleaved_writer() {
+    let r1 = Record::new(b"P1/1", None, b"ACGT", b"IIII");
+    let r2 = Record::new(b"P1/2", None, b"TTGG", b"JJJJ");
+    let mut buffer = Vec::new();
+    {
+        let mut writer = InterleavedWriter::new(&mut buffer);
+        writer.write_pair(&r1, &r2).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        "@P1/1\nACGT\n+\nIIII\n@P1/2\nTTGG\n+\nJJJJ\n"
+    );
+}
+
+#[test]
+fn test_interleave_round_trip() {
+    let r1 = write_temp(b"@A/1\nAAAA\n+\nIIII\n@B/1\nCCCC\n+\nIIII\n");
+    let r2 = write_temp(b"@A/2\nTTTT\n+\nJJJJ\n@B/2\nGGGG\n+\nJJJJ\n");
+    let dir = tempfile::tempdir().unwrap();
+    let interleaved = dir.path().join("inter.fastq");
+    let out1 = dir.path().join("out_R1.fastq");
+    let out2 = dir.path().join("out_R2.fastq");
+
+    let pairs = FormatConverter::interleave(
+        r1.path(),
+        r2.path(),
+        interleaved.as_path(),
+        PairMatching::StripSuffix,
+    )
+    .unwrap();
+    assert_eq!(pairs, 2);
+
+    let ids: Vec<_> = std::fs::read_to_string(&interleaved)
+        .unwrap()
+        .lines()
+        .step_by(4)
+        .map(str::to_string)
+        .collect();
+    assert_eq!(ids, vec!["@A/1", "@A/2", "@B/1", "@B/2"]);
+
+    let pairs = FormatConverter::deinterleave(
+        interleaved.as_path(),
+        out1.as_path(),
+        out2.as_path(),
+        PairMatching::StripSuffix,
+    )
+    .unwrap();
+    assert_eq!(pairs, 2);
+    assert_eq!(
+        std::fs::read(&out1).unwrap(),
+        std::fs::read(r1.path()).unwrap()
+    );
+    assert_eq!(
+        std::fs::read(&out2).unwrap(),
+        std::fs::read(r2.path()).unwrap()
+    );
+}
+
+#[test]
+fn test_interleaved_reader_checks_mates_and_odd_count() {
+    let mismatched = write_temp(b"@A/1\nAC\n+\nII\n@B/2\nAC\n+\nII\n");
+    let mut iter = InterleavedReader::from_path(mismatched.path())
+        .unwrap()
+        .into_paired_records();
+    assert!(matches!(
+        iter.next(),
+        Some(Err(FastqError::PairedEndMismatch { .. }))
+    ));
+
+    let odd = write_temp(b"@A/1\nAC\n+\nII\n@A/2\nAC\n+\nII\n@C/1\nAC\n+\nII\n");
+    let results: Vec<_> = InterleavedReader::from_path(odd.path())
+        .unwrap()
+        .into_paired_records()
+        .collect();
+    assert_eq!(results.len(), 2);
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        results[1],
+        Err(FastqError::InterleavedOddCount { record: 3 })
+    ));
+}
+
+fn filter_input() -> (Vec<u8>, Vec<u8>) {
+    // GOOD: both mates pass; R2BAD: R2 low quality; BOTHBAD: both fail
+    let r1 = b"@GOOD/1\nACGTACGTAC\n+\nIIIIIIIIII\n@R2BAD/1\nACGTACGTAC\n+\nIIIIIIIIII\n@BOTHBAD/1\nACGTACGTAC\n+\n##########\n";
+    let r2 = b"@GOOD/2\nTTGGCCAATT\n+\nIIIIIIIIII\n@R2BAD/2\nTTGGCCAATT\n+\n##########\n@BOTHBAD/2\nTTGGCCAATT\n+\n##########\n";
+    (r1.to_vec(), r2.to_vec())
+}
+
+fn quality_only() -> QualityFilter {
+    QualityFilter::new()
+        .min_quality(20.0)
+        .min_length(5)
+        .trim_quality(None)
+}
+
+fn paired(r1: &[u8], r2: &[u8]) -> Vec<Result<(OwnedRecord, OwnedRecord)>> {
+    Parser::new(r1)
+        .zip(Parser::new(r2))
+        .map(|(a, b)| Ok((OwnedRecord::from_record(&a), OwnedRecord::from_record(&b))))
+        .collect()
+}
+
+#[test]
+fn test_paired_filter_policies() {
+    let (r1, r2) = filter_input();
+
+    for (policy, kept, singletons) in [
+        (PairPolicy::Both, 1, 0),
+        (PairPolicy::Either, 2, 0),
+        (PairPolicy::Rescue, 1, 1),
+    ] {
+        let (mut o1, mut o2, mut os) = (Vec::new(), Vec::new(), Vec::new());
+        let stats = {
+            let mut w1 = FastqWriter::new(&mut o1);
+            let mut w2 = FastqWriter::new(&mut o2);
+            let mut ws = FastqWriter::new(&mut os);
+            PairedFilter::new(policy)
+                .quality_filter(quality_only())
+                .filter_pairs(
+                    paired(&r1, &r2).into_iter(),
+                    &mut w1,
+                    &mut w2,
+                    Some(&mut ws),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(stats.kept_pairs, kept, "{:?}", policy);
+        assert_eq!(stats.r1_singletons, singletons, "{:?}", policy);
+        assert_eq!(stats.total_pairs, 3);
+        assert_eq!(Parser::new(&o1).count(), kept);
+        assert_eq!(Parser::new(&o2).count(), kept);
+        assert_eq!(Parser::new(&os).count(), singletons);
+        if singletons > 0 {
+            assert!(os.starts_with(b"@R2BAD/1\n"));
+        }
+    }
+}
+
+#[test]
+fn test_paired_filter_trims_mates_independently() {
+    let r1 = Record::new(b"T/1", None, b"ACGTACGTAC", b"IIIIIIII##");
+    let r2 = Record::new(b"T/2", None, b"TTGGCCAATT", b"IIIIIIIIII");
+    let filter = PairedFilter::new(PairPolicy::Both).quality_filter(
+        QualityFilter::new()
+            .min_quality(0.0)
+            .min_length(4)
+            .trim_quality(Some(20))
+            .window_size(1),
+    );
+
+    match filter.apply(&r1, &r2) {
+        fastq_parser::filter::PairDecision::Pair(a, b) => {
+            assert_eq!(a.seq, b"ACGTACGT");
+            assert_eq!(b.seq, b"TTGGCCAATT");
+        }
+        other => panic!("unexpected decision {:?}", other),
+    }
+}
+
+#[test]
+fn test_parallel_paired_filter_processor() {
+    let (r1, r2) = filter_input();
+    let dir = tempfile::tempdir().unwrap();
+    let (p1, p2, ps) = (
+        dir.path().join("out_R1.fq"),
+        dir.path().join("out_R2.fq"),
+        dir.path().join("single.fq"),
+    );
+
+    let stats = fastq_parser::parallel::ParallelPairedFilterProcessor::new(
+        PairedFilter::new(PairPolicy::Rescue).quality_filter(quality_only()),
+    )
+    .num_workers(2)
+    .process(
+        std::io::Cursor::new(r1),
+        std::io::Cursor::new(r2),
+        std::fs::File::create(&p1).unwrap(),
+        std::fs::File::create(&p2).unwrap(),
+        Some(std::fs::File::create(&ps).unwrap()),
+    )
+    .unwrap();
+
+    assert_eq!(stats.kept_pairs, 1);
+    assert_eq!(stats.r1_singletons, 1);
+    assert_eq!(stats.discarded_pairs, 1);
+    assert_eq!(
+        std::fs::read_to_string(&p1).unwrap(),
+        "@GOOD/1\nACGTACGTAC\n+\nIIIIIIIIII\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(&p2).unwrap(),
+        "@GOOD/2\nTTGGCCAATT\n+\nIIIIIIIIII\n"
+    );
+    assert!(std::fs::read_to_string(&ps)
+        .unwrap()
+        .starts_with("@R2BAD/1"));
+}
+
+#[test]
+fn test_parallel_paired_filter_detects_mismatch() {
+    let r1 = b"@A/1\nACGTACGTAC\n+\nIIIIIIIIII\n".to_vec();
+    let r2 = b"@B/2\nACGTACGTAC\n+\nIIIIIIIIII\n".to_vec();
+    let dir = tempfile::tempdir().unwrap();
+
+    let result = fastq_parser::parallel::ParallelPairedFilterProcessor::new(PairedFilter::new(
+        PairPolicy::Both,
+    ))
+    .process(
+        std::io::Cursor::new(r1),
+        std::io::Cursor::new(r2),
+        std::fs::File::create(dir.path().join("1")).unwrap(),
+        std::fs::File::create(dir.path().join("2")).unwrap(),
+        None,
+    );
+    assert!(matches!(result, Err(FastqError::PairedEndMismatch { .. })));
+}
+
+#[test]
+fn test_synchronized_reader_with_index_reads() {
+    let r1 = write_temp(b"@F1 1:N:0\nACGT\n+\nIIII\n@F2 1:N:0\nTTTT\n+\nIIII\n");
+    let r2 = write_temp(b"@F1 2:N:0\nGGGG\n+\nIIII\n@F2 2:N:0\nCCCC\n+\nIIII\n");
+    let i1 = write_temp(b"@F1 1:N:0\nAACCGGTT\n+\nIIIIIIII\n@F2 1:N:0\nGGTTAACC\n+\nIIIIIIII\n");
+    let i2 = write_temp(b"@F1 2:N:0\nTGCATGCA\n+\nIIIIIIII\n@F2 2:N:0\nCATGCATG\n+\nIIIIIIII\n");
+
+    let reader =
+        SynchronizedReader::from_paths(&[r1.path(), r2.path(), i1.path(), i2.path()]).unwrap();
+    assert_eq!(reader.num_files(), 4);
+
+    let steps: Vec<_> = reader
+        .matching(PairMatching::Casava)
+        .into_records()
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(steps.len(), 2);
+    assert_eq!(steps[1][0].seq, b"TTTT");
+    assert_eq!(steps[1][2].seq, b"GGTTAACC");
+
+    let config = BarcodeConfig::new(0, 8)
+        .barcode_read(2)
+        .with_umi(0, 4)
+        .umi_read(3);
+    let extractor = BarcodeExtractor::new(config);
+    let (barcode, umi) = extractor.extract_synchronized(&steps[0]).unwrap();
+    assert_eq!(barcode, b"AACCGGTT");
+    assert_eq!(umi.unwrap(), b"TGCA");
+
+    let missing = BarcodeExtractor::new(BarcodeConfig::new(0, 8).barcode_read(9));
+    assert!(missing.extract_synchronized(&steps[0]).is_none());
+}
+
+#[test]
+fn test_synchronized_reader_reports_mismatches() {
+    let r1 = write_temp(b"@A/1\nACGT\n+\nIIII\n@B/1\nACGT\n+\nIIII\n");
+    let r2 = write_temp(b"@A/2\nACGT\n+\nIIII\n@B/2\nACGT\n+\nIIII\n");
+    let short = write_temp(b"@A\nACGTAC\n+\nIIIIII\n");
+    let wrong = write_temp(b"@A\nACGTAC\n+\nIIIIII\n@C\nACGTAC\n+\nIIIIII\n");
+
+    let mut iter = SynchronizedReader::from_paths(&[r1.path(), short.path(), r2.path()])
+        .unwrap()
+        .into_records();
+    assert!(iter.next().unwrap().is_ok());
+    match iter.next() {
+        Some(Err(FastqError::SynchronizedLengthMismatch { file, records })) => {
+            assert_eq!((file, records), (1, 1));
+        }
+        other => panic!(
+            "expected length mismatch, got {:?}",
+            other.map(|r| r.is_ok())
+        ),
+    }
+    assert!(iter.next().is_none());
+
+    let mut iter = SynchronizedReader::from_paths(&[r1.path(), r2.path(), wrong.path()])
+        .unwrap()
+        .into_records();
+    assert!(iter.next().unwrap().is_ok());
+    match iter.next() {
+        Some(Err(FastqError::SynchronizedIdMismatch { file, record, .. })) => {
+            assert_eq!((file, record), (2, 2));
+        }
+        other => panic!("expected ID mismatch, got {:?}", other.map(|r| r.is_ok())),
+    }
+
+    let relaxed = SynchronizedReader::from_paths(&[r1.path(), wrong.path()])
+        .unwrap()
+        .into_records()
+        .strict_pairing(false)
+        .count();
+    assert_eq!(relaxed, 2);
+}
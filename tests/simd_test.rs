@@ -0,0 +1,218 @@
+use fastq_parser::parser::{record_spans, IndexedParser};
+use fastq_parser::simd::{find_newlines, StructuralIndex};
+use fastq_parser::{FastqError, Parser};
+use proptest::prelude::*;
+
+#[test]
+fn test_structural_index_matches_find_newlines() {
+    let mut data = Vec::new();
+    for i in 0..50 {
+        data.extend_from_slice(format!("@READ_{}\nACGTACGTAC\n+\nIIIIIIIIII\n", i).as_bytes());
+    }
+
+    let index = StructuralIndex::build(&data);
+    let positions: Vec<_> = index.newlines().collect();
+
+    assert_eq!(positions, find_newlines(&data));
+    assert_eq!(index.newline_count(), 200);
+    assert_eq!(index.blocks().len(), data.len().div_ceil(64));
+    assert_eq!(index, StructuralIndex::build_scalar(&data));
+}
+
+#[test]
+fn test_line_starts_without_trailing_newline() {
+    let data = b"@A\nAC\n+\nII";
+    let index = StructuralIndex::build(data);
+    assert_eq!(index.line_starts(), vec![0, 3, 6, 8]);
+}
+
+#[test]
+fn test_indexed_parser_matches_parser() {
+    let data = b"@SEQ1 desc\r\nACGT\r\n+\r\nIIII\r\n@SEQ2\nTTGCA\n+SEQ2\nJJJJJ\n\n";
+    let indexed: Vec<_> = IndexedParser::new(data).unwrap().collect();
+    let parsed: Vec<_> = Parser::new(data).collect();
+
+    assert_eq!(indexed, parsed);
+    assert_eq!(indexed[0].desc(), Some(&b"desc"[..]));
+}
+
+#[test]
+fn test_record_spans_rejects_multiline_records() {
+    let data = b"@SEQ1\nACGT\nACGT\n+\nIIIIIIII\n";
+    let index = StructuralIndex::build(data);
+
+    assert!(matches!(
+        record_spans(data, &index),
+        Err(FastqError::InvalidSeparator { line: 3 })
+    ));
+}
+
+#[test]
+fn test_record_spans_truncated_record() {
+    let data = b"@SEQ1\nACGT\n+\n";
+    let index = StructuralIndex::build(data);
+
+    assert!(matches!(
+        record_spans(data, &index),
+        Err(FastqError::UnexpectedEof)
+    ));
+}
+
+proptest! {
+    #[test]
+    fn prop_structural_index_dispatch_matches_scalar(data in prop::collection::vec(
+        prop::sample::select(vec![b'\n', b'A', b'@', b'+', b'\r']), 0..500
+    )) {
+        let index = StructuralIndex::build(&data);
+        prop_assert_eq!(&index, &StructuralIndex::build_scalar(&data));
+        prop_assert_eq!(index.newlines().collect::<Vec<_>>(), find_newlines(&data));
+    }
+}
+
+#[test]
+fn test_count_bases() {
+    let counts =
+        fastq_parser::simd::count_bases(b"ACGTNacgtnXX-ACGTACGTACGTACGTACGTACGTACGTACGTGGCC");
+    assert_eq!(counts.a, 10);
+    assert_eq!(counts.c, 12);
+    assert_eq!(counts.g, 12);
+    assert_eq!(counts.t, 10);
+    assert_eq!(counts.n, 2);
+    assert_eq!(counts.other, 3);
+    assert_eq!(counts.gc(), 24);
+}
+
+#[test]
+fn test_reverse_complement() {
+    let seq = b"ACGTNacgtnRACGTACGTACGTACGTACGTACGTTTTT";
+    let expected = fastq_parser::simd::scalar::count_bases(seq);
+    let rc = fastq_parser::simd::reverse_complement(seq);
+
+    assert_eq!(&rc[..10], b"AAAAACGTAC");
+    assert_eq!(&rc[rc.len() - 11..], b"RnacgtNACGT");
+    assert_eq!(fastq_parser::simd::reverse_complement(&rc), seq.to_vec());
+    assert_eq!(fastq_parser::simd::count_bases(&rc).n, expected.n);
+
+    let record = fastq_parser::Record::new(b"R1", None, b"AACG", b"ABCD");
+    let rc_record = record.reverse_complement();
+    assert_eq!(rc_record.seq, b"CGTT");
+    assert_eq!(rc_record.qual, b"DCBA");
+}
+
+#[test]
+fn test_force_scalar() {
+    fastq_parser::simd::force_scalar(true);
+    assert!(fastq_parser::simd::is_scalar_forced());
+    assert_eq!(
+        fastq_parser::simd::phred_scores(b"!+5?I", 33),
+        vec![0, 10, 20, 30, 40]
+    );
+    fastq_parser::simd::force_scalar(false);
+    assert!(!fastq_parser::simd::is_scalar_forced());
+}
+
+fn sequence_strategy() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(
+        prop_oneof![
+            8 => prop::sample::select(b"ACGTNacgtn".to_vec()),
+            1 => any::<u8>(),
+        ],
+        0..300,
+    )
+}
+
+proptest! {
+    #[test]
+    fn prop_count_bases_matches_scalar(seq in sequence_strategy()) {
+        use fastq_parser::simd::{count_bases, scalar};
+        prop_assert_eq!(count_bases(&seq), scalar::count_bases(&seq));
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                prop_assert_eq!(unsafe { fastq_parser::simd::count_bases_avx2(&seq) }, scalar::count_bases(&seq));
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                prop_assert_eq!(unsafe { fastq_parser::simd::count_bases_sse41(&seq) }, scalar::count_bases(&seq));
+            }
+        }
+    }
+
+    #[test]
+    fn prop_reverse_complement_matches_scalar(seq in sequence_strategy()) {
+        use fastq_parser::simd::{reverse_complement, scalar};
+        let mut expected = vec![0u8; seq.len()];
+        scalar::reverse_complement_into(&seq, &mut expected);
+        prop_assert_eq!(reverse_complement(&seq), expected.clone());
+        #[cfg(target_arch = "x86_64")]
+        {
+            let mut out = vec![0u8; seq.len()];
+            if is_x86_feature_detected!("avx2") {
+                unsafe { fastq_parser::simd::reverse_complement_avx2(&seq, &mut out) };
+                prop_assert_eq!(&out, &expected);
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                unsafe { fastq_parser::simd::reverse_complement_sse41(&seq, &mut out) };
+                prop_assert_eq!(&out, &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_to_uppercase_matches_scalar(seq in sequence_strategy()) {
+        use fastq_parser::simd::{scalar, to_uppercase};
+        let mut expected = seq.clone();
+        scalar::to_uppercase(&mut expected);
+        let mut actual = seq.clone();
+        to_uppercase(&mut actual);
+        prop_assert_eq!(&actual, &expected);
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                let mut actual = seq.clone();
+                unsafe { fastq_parser::simd::to_uppercase_avx2(&mut actual) };
+                prop_assert_eq!(&actual, &expected);
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                let mut actual = seq.clone();
+                unsafe { fastq_parser::simd::to_uppercase_sse41(&mut actual) };
+                prop_assert_eq!(&actual, &expected);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_validate_alphabet_matches_scalar(seq in sequence_strategy()) {
+        use fastq_parser::simd::{scalar, validate_alphabet};
+        let expected = scalar::validate_alphabet(&seq);
+        prop_assert_eq!(validate_alphabet(&seq), expected);
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                prop_assert_eq!(unsafe { fastq_parser::simd::validate_alphabet_avx2(&seq) }, expected);
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                prop_assert_eq!(unsafe { fastq_parser::simd::validate_alphabet_sse41(&seq) }, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn prop_phred_scores_matches_scalar(qual in prop::collection::vec(any::<u8>(), 0..300), offset in prop::sample::select(vec![33u8, 64])) {
+        use fastq_parser::simd::{phred_scores, scalar};
+        let mut expected = vec![0u8; qual.len()];
+        scalar::phred_scores_into(&qual, offset, &mut expected);
+        prop_assert_eq!(phred_scores(&qual, offset), expected.clone());
+        #[cfg(target_arch = "x86_64")]
+        {
+            let mut out = vec![0u8; qual.len()];
+            if is_x86_feature_detected!("avx2") {
+                unsafe { fastq_parser::simd::phred_scores_avx2(&qual, offset, &mut out) };
+                prop_assert_eq!(&out, &expected);
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                unsafe { fastq_parser::simd::phred_scores_sse41(&qual, offset, &mut out) };
+                prop_assert_eq!(&out, &expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use fastq_parser::*;
+use std::cmp::Ordering;
+use std::path::Path;
+
+fn read(id: &str, seq: &str) -> OwnedRecord {
+    OwnedRecord {
+        id: id.as_bytes().to_vec(),
+        desc: None,
+        seq: seq.as_bytes().to_vec(),
+        qual: vec![b'I'; seq.len()],
+    }
+}
+
+fn ids(path: &Path) -> Vec<String> {
+    FastqReader::from_path(path)
+        .unwrap()
+        .into_records()
+        .map(|r| String::from_utf8(r.unwrap().id).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_natural_cmp() {
+    assert_eq!(natural_cmp(b"read2", b"read10"), Ordering::Less);
+    assert_eq!(natural_cmp(b"lane1:10", b"lane1:9"), Ordering::Greater);
+    assert_eq!(natural_cmp(b"read007", b"read7"), Ordering::Less);
+    assert_eq!(natural_cmp(b"read7", b"read7a"), Ordering::Less);
+    assert_eq!(natural_cmp(b"a10", b"b2"), Ordering::Less);
+    assert_eq!(natural_cmp(b"x", b"x"), Ordering::Equal);
+}
+
+#[test]
+fn test_external_sort_spills_and_merges() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.fastq");
+    let output = dir.path().join("out.fastq");
+
+    // A one-byte budget spills every record as its own run, which forces
+    // a multi-pass merge.
+    let mut order: Vec<usize> = (0..300).collect();
+    order.reverse();
+    order.rotate_left(100);
+    let mut writer = FastqWriter::to_file(&input).unwrap();
+    for &i in &order {
+        writer
+            .write_owned_record(&read(&format!("read{}", i), "ACGT"))
+            .unwrap();
+    }
+    writer.finish().unwrap();
+
+    let stats = ExternalSorter::new()
+        .numeric(true)
+        .memory_limit(1)
+        .spill_dir(dir.path())
+        .sort_file(&input, &output)
+        .unwrap();
+    assert_eq!(stats.records, 300);
+    assert_eq!(stats.runs, 300);
+    let expected: Vec<String> = (0..300).map(|i| format!("read{}", i)).collect();
+    assert_eq!(ids(&output), expected);
+
+    // Spill files are cleaned up.
+    let mut entries: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    entries.sort();
+    assert_eq!(entries, ["in.fastq", "out.fastq"]);
+
+    let stats = ExternalSorter::new().sort_file(&input, &output).unwrap();
+    assert_eq!(stats.runs, 0);
+    assert_eq!(ids(&output)[..3], ["read0", "read1", "read10"]);
+}
+
+#[test]
+fn test_external_sort_keys_are_stable() {
+    let records = [
+        read("a", "TTTT"),
+        read("b", "AC"),
+        read("c", "GGGG"),
+        read("d", "AAAAAA"),
+        read("e", "CC"),
+    ];
+    let sorted = |sorter: ExternalSorter| {
+        let mut writer = FastqWriter::new(Vec::new());
+        let records = records
+            .iter()
+            .map(|r| Ok(OwnedRecord::from_record(&r.as_record())));
+        sorter.sort(records, &mut writer).unwrap();
+        FastqReader::from_reader(std::io::Cursor::new(writer.finish().unwrap()))
+            .into_records()
+            .map(|r| String::from_utf8(r.unwrap().id).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    for limit in [usize::MAX, 150] {
+        assert_eq!(
+            sorted(
+                ExternalSorter::new()
+                    .key(SortKey::Length)
+                    .memory_limit(limit)
+            ),
+            ["b", "e", "a", "c", "d"]
+        );
+        assert_eq!(
+            sorted(
+                ExternalSorter::new()
+                    .key(SortKey::Sequence)
+                    .memory_limit(limit)
+            ),
+            ["d", "b", "e", "c", "a"]
+        );
+    }
+
+    let by_base = KeyExtractor::custom(|record| record.seq.last().map(|&b| vec![b]));
+    assert_eq!(
+        sorted(
+            ExternalSorter::new()
+                .key(SortKey::Key(by_base))
+                .memory_limit(1)
+        ),
+        ["d", "b", "e", "c", "a"]
+    );
+}
+
+#[test]
+fn test_external_sort_paired() {
+    let dir = tempfile::tempdir().unwrap();
+    let r1_in = dir.path().join("R1.fastq");
+    let r2_in = dir.path().join("R2.fastq");
+    let r1_out = dir.path().join("R1.sorted.fastq");
+    let r2_out = dir.path().join("R2.sorted.fastq");
+
+    let order = [5, 12, 1, 9, 3, 10, 7];
+    let mut w1 = FastqWriter::to_file(&r1_in).unwrap();
+    let mut w2 = FastqWriter::to_file(&r2_in).unwrap();
+    for i in order {
+        w1.write_owned_record(&read(&format!("frag{}/1", i), "ACGT"))
+            .unwrap();
+        w2.write_owned_record(&read(&format!("frag{}/2", i), "TTGCA"))
+            .unwrap();
+    }
+    w1.finish().unwrap();
+    w2.finish().unwrap();
+
+    let stats = ExternalSorter::new()
+        .numeric(true)
+        .memory_limit(200)
+        .sort_paired_files(&r1_in, &r2_in, &r1_out, &r2_out)
+        .unwrap();
+    assert_eq!(stats.records, order.len());
+    assert!(stats.runs > 1);
+
+    let mut sorted = order;
+    sorted.sort();
+    let r1 = ids(&r1_out);
+    let r2 = ids(&r2_out);
+    assert_eq!(r1, sorted.map(|i| format!("frag{}/1", i)).to_vec());
+    for (a, b) in r1.iter().zip(&r2) {
+        assert_eq!(a.trim_end_matches("/1"), b.trim_end_matches("/2"));
+    }
+
+    let mut writer_r1 = FastqWriter::new(Vec::new());
+    let mut writer_r2 = FastqWriter::new(Vec::new());
+    let result = ExternalSorter::new().sort_paired(
+        [Ok(read("x/1", "A")), Ok(read("y/1", "A"))].into_iter(),
+        [Ok(read("x/2", "A"))].into_iter(),
+        &mut writer_r1,
+        &mut writer_r2,
+    );
+    assert!(matches!(result, Err(FastqError::PairedEndLengthMismatch)));
+
+    let result = ExternalSorter::new().sort_paired(
+        [Ok(read("x/1", "A"))].into_iter(),
+        [Ok(read("y/2", "A"))].into_iter(),
+        &mut writer_r1,
+        &mut writer_r2,
+    );
+    assert!(matches!(result, Err(FastqError::PairedEndMismatch { .. })));
+}
+
+#[test]
+fn test_external_sort_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let r1 = dir.path().join("reads_1.fastq");
+    let r2 = dir.path().join("reads_2.fastq");
+    let write_reversed = |path: &Path, mate: u8| {
+        let mut writer = FastqWriter::to_file(path).unwrap();
+        for i in (0..20_000).rev() {
+            writer
+                .write_owned_record(&read(&format!("read{}/{}", i, mate), "ACGT"))
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    };
+    let expected =
+        |mate: u8| -> Vec<String> { (0..20_000).map(|i| format!("read{}/{}", i, mate)).collect() };
+
+    // The output replaces the mapped input only after the merge.
+    write_reversed(&r1, 1);
+    let stats = ExternalSorter::new()
+        .numeric(true)
+        .memory_limit(64 << 10)
+        .spill_dir(dir.path())
+        .sort_file(&r1, &r1)
+        .unwrap();
+    assert_eq!(stats.records, 20_000);
+    assert_eq!(ids(&r1), expected(1));
+
+    write_reversed(&r1, 1);
+    write_reversed(&r2, 2);
+    ExternalSorter::new()
+        .numeric(true)
+        .sort_paired_files(&r1, &r2, &r1, &r2)
+        .unwrap();
+    assert_eq!(ids(&r1), expected(1));
+    assert_eq!(ids(&r2), expected(2));
+}
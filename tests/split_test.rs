@@ -0,0 +1,255 @@
+use fastq_parser::*;
+use std::path::{Path, PathBuf};
+
+fn read(id: usize, len: usize) -> OwnedRecord {
+    OwnedRecord {
+        id: format!("read{}", id).into_bytes(),
+        desc: None,
+        seq: vec![b'A'; len],
+        qual: vec![b'I'; len],
+    }
+}
+
+fn ids(path: &Path) -> Vec<String> {
+    FastqReader::from_path(path)
+        .unwrap()
+        .into_records()
+        .map(|r| String::from_utf8(r.unwrap().id).unwrap())
+        .collect()
+}
+
+fn names(paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn test_split_by_records_bases_and_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+
+    let mut writer = SplitWriter::new(template("part_{}.fastq"), SplitBy::Records(3)).unwrap();
+    for i in 0..7 {
+        writer.write_owned_record(&read(i, 10)).unwrap();
+    }
+    let paths = writer.finish().unwrap();
+    assert_eq!(
+        names(&paths),
+        ["part_001.fastq", "part_002.fastq", "part_003.fastq"]
+    );
+    assert_eq!(ids(&paths[0]), ["read0", "read1", "read2"]);
+    assert_eq!(ids(&paths[2]), ["read6"]);
+
+    // 10 + 10 + 10 fits in 30 bases, the 25bp read opens a new shard and
+    // the oversized 40bp read gets one to itself.
+    let mut writer = SplitWriter::new(template("bases_{}.fastq.gz"), SplitBy::Bases(30))
+        .unwrap()
+        .pad(1);
+    for (i, len) in [10, 10, 10, 25, 40, 5].into_iter().enumerate() {
+        writer.write_owned_record(&read(i, len)).unwrap();
+    }
+    let paths = writer.finish().unwrap();
+    assert_eq!(
+        names(&paths),
+        [
+            "bases_1.fastq.gz",
+            "bases_2.fastq.gz",
+            "bases_3.fastq.gz",
+            "bases_4.fastq.gz"
+        ]
+    );
+    assert_eq!(ids(&paths[1]), ["read3"]);
+    assert_eq!(ids(&paths[3]), ["read5"]);
+
+    // "@readN\n" + 4 + "\n+\n" + 4 + "\n" = 19 bytes per record
+    let mut writer = SplitWriter::new(template("bytes_{}.fastq"), SplitBy::Bytes(40)).unwrap();
+    for i in 0..5 {
+        writer.write_owned_record(&read(i, 4)).unwrap();
+    }
+    let paths = writer.finish().unwrap();
+    assert_eq!(paths.len(), 3);
+    for path in &paths {
+        assert!(std::fs::metadata(path).unwrap().len() <= 40);
+    }
+
+    assert!(matches!(
+        SplitWriter::new(template("no_placeholder.fastq"), SplitBy::Records(1)),
+        Err(FastqError::InvalidOutputTemplate { .. })
+    ));
+}
+
+#[test]
+fn test_paired_split_stays_in_step() {
+    let dir = tempfile::tempdir().unwrap();
+    let r1 = dir
+        .path()
+        .join("R1_{}.fastq")
+        .to_string_lossy()
+        .into_owned();
+    let r2 = dir
+        .path()
+        .join("R2_{}.fastq")
+        .to_string_lossy()
+        .into_owned();
+
+    let mut writer = PairedSplitWriter::new(r1, r2, SplitBy::RoundRobin(3)).unwrap();
+    for i in 0..8 {
+        writer.write_owned_pair(&read(i, 20), &read(i, 30)).unwrap();
+    }
+    let shards = writer.finish().unwrap();
+    assert_eq!(shards.len(), 3);
+
+    for (shard, (r1_path, r2_path)) in shards.iter().enumerate() {
+        let expected: Vec<String> = (0..8)
+            .filter(|i| i % 3 == shard)
+            .map(|i| format!("read{}", i))
+            .collect();
+        assert_eq!(ids(r1_path), expected);
+        assert_eq!(ids(r2_path), expected);
+    }
+
+    let r1 = dir
+        .path()
+        .join("S1_{}.fastq")
+        .to_string_lossy()
+        .into_owned();
+    let r2 = dir
+        .path()
+        .join("S2_{}.fastq")
+        .to_string_lossy()
+        .into_owned();
+    let mut writer = PairedSplitWriter::new(r1, r2, SplitBy::Bases(100))
+        .unwrap()
+        .format(FastqFormat::new().mate_suffix(Some(1)));
+    for i in 0..5 {
+        writer.write_owned_pair(&read(i, 20), &read(i, 30)).unwrap();
+    }
+    let shards = writer.finish().unwrap();
+    assert_eq!(shards.len(), 3);
+    assert_eq!(ids(&shards[0].0), ["read0/1", "read1/1"]);
+    assert_eq!(ids(&shards[0].1), ["read0/2", "read1/2"]);
+    assert_eq!(ids(&shards[2].1), ["read4/2"]);
+}
+
+#[test]
+fn test_partitioned_writer_routes_and_reopens() {
+    let dir = tempfile::tempdir().unwrap();
+    let lanes = [1, 2, 3, 1, 3, 2, 1, 4];
+
+    for extension in ["fastq", "fastq.gz"] {
+        let template = dir.path().join(format!("lane_{{}}.{}", extension));
+        let mut writer = PartitionedWriter::new(template.to_string_lossy())
+            .unwrap()
+            .key(KeyExtractor::IdField {
+                delimiter: b':',
+                field: 3,
+            })
+            .max_open(2);
+
+        for (i, lane) in lanes.iter().enumerate() {
+            let id = format!("M001:7:FC1:{}:1101:{}", lane, i);
+            let record = Record::new(id.as_bytes(), None, b"ACGT", b"IIII");
+            assert!(writer.write_record(&record).unwrap());
+            assert!(writer.open_count() <= 2);
+        }
+        assert!(!writer
+            .write_record(&Record::new(b"no_fields", None, b"A", b"I"))
+            .unwrap());
+        assert_eq!(writer.unassigned_count(), 1);
+
+        let summary = writer.finish().unwrap();
+        let counts: Vec<(&[u8], u64)> = summary
+            .iter()
+            .map(|p| (p.key.as_slice(), p.records))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (&b"1"[..], 3),
+                (&b"2"[..], 2),
+                (&b"3"[..], 2),
+                (&b"4"[..], 1)
+            ]
+        );
+        assert_eq!(summary[0].bases, 12);
+
+        // Lane 1 was evicted and reopened in append mode twice.
+        let lane1: Vec<String> = ids(&summary[0].path)
+            .iter()
+            .map(|id| id.rsplit(':').next().unwrap().to_string())
+            .collect();
+        assert_eq!(lane1, ["0", "3", "6"]);
+    }
+
+    let template = dir.path().join("split_{}.fastq");
+    let mut writer = PartitionedWriter::new(template.to_string_lossy())
+        .unwrap()
+        .key(KeyExtractor::IdHash { buckets: 4 });
+    for i in 0..50 {
+        for mate in 1..=2 {
+            let id = format!("pair{}/{}", i, mate);
+            writer
+                .write_record(&Record::new(id.as_bytes(), None, b"A", b"I"))
+                .unwrap();
+        }
+    }
+    for partition in writer.finish().unwrap() {
+        let ids = ids(&partition.path);
+        for pair in ids.chunks(2) {
+            assert_eq!(
+                pair[0].trim_end_matches("/1"),
+                pair[1].trim_end_matches("/2")
+            );
+        }
+    }
+}
+
+#[test]
+fn test_partition_keys_never_share_a_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let template = dir.path().join("p_{}.fastq");
+    let mut writer = PartitionedWriter::new(template.to_string_lossy()).unwrap();
+    let keys: [&[u8]; 10] = [
+        b"a/b", b"a_b", b"a\\b", b"a%2Fb", b"a\tb", b"a\xffb", b".", b"..", b".a", b"%2E",
+    ];
+    for (i, key) in keys.iter().enumerate() {
+        let id = format!("r{}", i);
+        writer
+            .write_keyed(key, &Record::new(id.as_bytes(), None, b"A", b"I"))
+            .unwrap();
+    }
+
+    let summary = writer.finish().unwrap();
+    assert_eq!(summary.len(), keys.len());
+    for partition in &summary {
+        assert_eq!(partition.path.parent(), Some(dir.path()));
+        assert_eq!(ids(&partition.path).len(), 1);
+    }
+    let names: Vec<_> = summary
+        .iter()
+        .map(|p| p.path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert!(names.contains(&"p_a%2Fb.fastq"));
+    assert!(names.contains(&"p_a_b.fastq"));
+    assert!(names.contains(&"p_a%252Fb.fastq"));
+    assert!(names.contains(&"p_a%FFb.fastq"));
+    assert!(names.contains(&"p_%2E.fastq"));
+    assert!(names.contains(&"p_%2E..fastq"));
+    assert!(names.contains(&"p_%2Ea.fastq"));
+
+    // Without a prefix, `..` would otherwise name the parent directory.
+    let mut bare = dir.path().as_os_str().to_owned();
+    bare.push("/");
+    let mut writer = PartitionedWriter::with_affixes(bare, "").unwrap();
+    for key in [&b"."[..], b".."] {
+        writer
+            .write_keyed(key, &Record::new(b"r", None, b"A", b"I"))
+            .unwrap();
+    }
+    for partition in &writer.finish().unwrap() {
+        assert_eq!(partition.path.parent(), Some(dir.path()));
+        assert_eq!(ids(&partition.path), ["r"]);
+    }
+}